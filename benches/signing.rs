@@ -0,0 +1,213 @@
+//! Per-phase timings of tree signing for each n: `cargo bench`, or e.g.
+//! `cargo bench -- round2/128` for a single case. `cargo bench -- mode`
+//! compares whole tree and flat signing runs side by side, and
+//! `cargo bench -- threads` each round's leaf work on one thread against
+//! all of them.
+//!
+//! Every run also prints the heap a full signing session peaks at, and how
+//! many allocations each round makes, for n = 512 and 1024, counted by the
+//! allocator below.
+
+use ark_usecase::bintree::BinTree;
+use ark_usecase::flat::flat_sign;
+use ark_usecase::keys::Keypair;
+use ark_usecase::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+use ark_usecase::treemusig::{SigningSession, build_key_tree, tree_sign, tree_verify};
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::params::Params;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SIZES: [usize; 7] = [2, 4, 8, 32, 128, 256, 512];
+const MODE_SIZES: [usize; 4] = [4, 16, 64, 256];
+const MSG: &[u8] = b"bench message";
+const MEMORY_SIZES: [usize; 2] = [512, 1024];
+const ROUND1_THREADS_SIZE: usize = 256;
+const ROUND2_THREADS_SIZE: usize = 128;
+
+/// The system allocator, keeping count of live bytes, their high-water
+/// mark and the allocations made.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+            ALLOCS.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+struct Fixture {
+    n: usize,
+    pubkeys: Vec<Secp256k1Point>,
+    secret_keys: HashMap<Secp256k1Point, Secp256k1Scalar>,
+    tree: BinTree<Secp256k1Point>,
+}
+
+fn fixtures(sizes: &[usize]) -> Vec<Fixture> {
+    sizes
+        .iter()
+        .map(|&n| {
+            let keys: Vec<Keypair> = (0..n).map(|_| Keypair::generate()).collect();
+            let pubkeys: Vec<_> = keys.iter().map(|kp| kp.pk.clone()).collect();
+            let tree = build_key_tree(pubkeys.clone()).unwrap();
+            let secret_keys = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+            Fixture { n, pubkeys, secret_keys, tree }
+        })
+        .collect()
+}
+
+fn bench_phases(c: &mut Criterion) {
+    let fixtures = fixtures(&SIZES);
+
+    let mut group = c.benchmark_group("build_key_tree");
+    for f in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(f.n), f, |b, f| {
+            b.iter_batched(|| f.pubkeys.clone(), |pubkeys| build_key_tree(pubkeys).unwrap(), BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("round1");
+    for f in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(f.n), f, |b, f| {
+            b.iter_batched(
+                || SigningSession::new(&f.tree, &f.secret_keys).unwrap(),
+                |session| session.round1().unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("round2");
+    for f in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(f.n), f, |b, f| {
+            b.iter_batched(
+                || SigningSession::new(&f.tree, &f.secret_keys).unwrap().round1().unwrap(),
+                |session| session.round2(MSG).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("verify");
+    for f in &fixtures {
+        let sig = TreeSignature::new(tree_sign(&f.tree, &f.secret_keys, MSG).unwrap());
+        let ctx = VerifyCtx::new(Params::default(), &AggregatedKey::new(f.tree.value().clone()));
+        group.bench_with_input(BenchmarkId::from_parameter(f.n), f, |b, _| b.iter(|| assert!(ctx.verify(MSG, &sig))));
+    }
+    group.finish();
+}
+
+/// Both rounds and aggregation, tree against flat, for the same keys.
+fn bench_modes(c: &mut Criterion) {
+    let params = Params::default();
+    let mut group = c.benchmark_group("mode");
+    for f in &fixtures(&MODE_SIZES) {
+        group.bench_with_input(BenchmarkId::new("tree", f.n), f, |b, f| {
+            b.iter(|| tree_sign(&f.tree, &f.secret_keys, MSG).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("flat", f.n), f, |b, f| {
+            b.iter(|| flat_sign(&f.pubkeys, &f.secret_keys, MSG, &params).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Each round with the leaves drawing nonces or signing one after another
+/// and concurrently. The sequential runs go through a one-thread pool,
+/// which every rayon call inside it then uses.
+#[cfg(feature = "parallel")]
+fn bench_threads(c: &mut Criterion) {
+    let one_thread = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+    let f = &fixtures(&[ROUND1_THREADS_SIZE])[0];
+    let mut group = c.benchmark_group("threads/round1");
+    let fresh = || SigningSession::new(&f.tree, &f.secret_keys).unwrap();
+    group.bench_function(BenchmarkId::new("sequential", f.n), |b| {
+        b.iter_batched(fresh, |session| one_thread.install(|| session.round1().unwrap()), BatchSize::SmallInput)
+    });
+    group.bench_function(BenchmarkId::new("parallel", f.n), |b| {
+        b.iter_batched(fresh, |session| session.round1().unwrap(), BatchSize::SmallInput)
+    });
+    group.finish();
+
+    let f = &fixtures(&[ROUND2_THREADS_SIZE])[0];
+    let mut group = c.benchmark_group("threads/round2");
+    let round1 = || SigningSession::new(&f.tree, &f.secret_keys).unwrap().round1().unwrap();
+    group.bench_function(BenchmarkId::new("sequential", f.n), |b| {
+        b.iter_batched(round1, |session| one_thread.install(|| session.round2(MSG).unwrap()), BatchSize::SmallInput)
+    });
+    group.bench_function(BenchmarkId::new("parallel", f.n), |b| {
+        b.iter_batched(round1, |session| session.round2(MSG).unwrap(), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "parallel"))]
+fn bench_threads(_: &mut Criterion) {}
+
+/// What `f` did to the heap while it ran.
+struct HeapUse {
+    /// Peak above the starting point.
+    peak: usize,
+    /// How much more (or less) is held when it returns.
+    held: isize,
+    allocs: usize,
+}
+
+fn heap_use<T>(f: impl FnOnce() -> T) -> (T, HeapUse) {
+    let base = LIVE.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let allocs = ALLOCS.load(Ordering::Relaxed);
+    let value = f();
+    let peak = PEAK.load(Ordering::Relaxed) - base;
+    let held = LIVE.load(Ordering::Relaxed) as isize - base as isize;
+    (value, HeapUse { peak, held, allocs: ALLOCS.load(Ordering::Relaxed) - allocs })
+}
+
+/// Not timed: prints how much heap each round of one session needs, in
+/// how many allocations, and what it keeps afterwards, which round 2 should
+/// bring down to the root's signature.
+fn bench_memory(_: &mut Criterion) {
+    for f in &fixtures(&MEMORY_SIZES) {
+        let session = SigningSession::new(&f.tree, &f.secret_keys).unwrap();
+        let (session, round1) = heap_use(|| session.round1().unwrap());
+        let (session, round2) = heap_use(|| session.round2(MSG).unwrap());
+        assert!(tree_verify(f.tree.value(), MSG, session.signature()));
+        for (round, used) in [(1, round1), (2, round2)] {
+            println!(
+                "memory/{}: round {} peak {} B in {} allocations, held {:+} B after",
+                f.n, round, used.peak, used.allocs, used.held
+            );
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    // The larger trees take a while per iteration.
+    config = Criterion::default().sample_size(10);
+    targets = bench_phases, bench_modes, bench_threads, bench_memory
+}
+criterion_main!(benches);
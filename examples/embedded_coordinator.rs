@@ -0,0 +1,95 @@
+//! A host application that embeds the coordinator. It builds the key tree
+//! from the participants' public keys, then runs both rounds with every
+//! participant on a thread of its own, speaking `wire` frames over
+//! in-memory channels where a deployment would use sockets.
+//!
+//! Keys come from a fixed seed, so every run has the same root key; the
+//! nonces are fresh each time. `cargo test` runs this through
+//! `tests/examples.rs`.
+//!
+//! ```text
+//! cargo run --example embedded_coordinator
+//! ```
+
+use ark_usecase::coordinator::Coordinator;
+use ark_usecase::keys::Keypair;
+use ark_usecase::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+use ark_usecase::signer::Signer;
+use ark_usecase::treemusig::build_sorted_key_tree_with;
+use ark_usecase::wire::WireMsg;
+use nested_musig2::params::Params;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::error::Error;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+
+const SEED: u64 = 212;
+const SIGNERS: usize = 6;
+const MSG: &[u8] = b"embedded coordinator";
+
+/// One participant: its round 1 output goes up, its round 2 inputs come
+/// down, its partial signature goes up.
+fn participant(mut signer: Signer, inbox: Receiver<Vec<u8>>, outbox: Sender<Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    let position = u32::try_from(signer.position())?;
+    outbox.send(WireMsg::Round1 { position, out: signer.round1()? }.to_frame())?;
+    let WireMsg::Round2Inputs { outs_by_depth, merkle_path } = WireMsg::from_frame(&inbox.recv()?)? else {
+        return Err("expected round 2 inputs".into());
+    };
+    let prime = signer.round2(&outs_by_depth, MSG, &merkle_path)?;
+    outbox.send(WireMsg::Round2 { position, prime }.to_frame())?;
+    Ok(())
+}
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng = ChaCha20Rng::seed_from_u64(SEED);
+    let keys: Vec<Keypair> = (0..SIGNERS).map(|_| Keypair::from_rng(&mut rng)).collect();
+    let params = Params::default();
+
+    // The coordinator only ever sees the public keys.
+    let tree = build_sorted_key_tree_with(keys.iter().map(|kp| kp.pk.clone()).collect(), &params)?;
+    let root = AggregatedKey::new(tree.value().clone());
+    println!("root key {}", root);
+
+    let (upstream, from_participants) = channel();
+    let mut downstream = Vec::with_capacity(SIGNERS);
+    let mut participants = Vec::with_capacity(SIGNERS);
+    for (position, pk) in tree.leaves().enumerate() {
+        let keypair = keys.iter().find(|kp| kp.pk == *pk).cloned().ok_or("no keypair for a leaf")?;
+        let signer = Signer::with_params(keypair, position, params.clone());
+        let (to_participant, inbox) = channel();
+        downstream.push(to_participant);
+        let outbox = upstream.clone();
+        participants.push(thread::spawn(move || participant(signer, inbox, outbox).map_err(|e| e.to_string())));
+    }
+    drop(upstream);
+
+    let mut coordinator = Coordinator::with_params(&tree, params.clone());
+    for _ in 0..SIGNERS {
+        match WireMsg::from_frame(&from_participants.recv()?)? {
+            WireMsg::Round1 { position, out } => coordinator.add_round1(position as usize, out)?,
+            _ => return Err("expected a round 1 output".into()),
+        }
+    }
+    coordinator.aggregate_round1()?;
+    for (position, to_participant) in downstream.iter().enumerate() {
+        let (outs_by_depth, merkle_path) = coordinator.round2_inputs(position)?;
+        to_participant.send(WireMsg::Round2Inputs { outs_by_depth, merkle_path }.to_frame())?;
+    }
+    for _ in 0..SIGNERS {
+        match WireMsg::from_frame(&from_participants.recv()?)? {
+            WireMsg::Round2 { position, prime } => coordinator.add_round2(position as usize, prime)?,
+            _ => return Err("expected a partial signature".into()),
+        }
+    }
+    let sig = TreeSignature::new(coordinator.aggregate_round2()?);
+    for handle in participants {
+        handle.join().map_err(|_| "a participant panicked")??;
+    }
+
+    if !VerifyCtx::new(params, &root).verify(MSG, &sig) {
+        return Err("the signature does not verify under the root key".into());
+    }
+    println!("signature {}", sig);
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! A host application that embeds one participant. It takes the key tree
+//! as published text, checks every aggregate in it, finds its own leaf, and
+//! signs when the coordinator asks, with `wire` frames over in-memory
+//! channels. The coordinator and the other participants run on a thread
+//! here, standing in for the network.
+//!
+//! Keys come from a fixed seed, so every run has the same root key; the
+//! nonces are fresh each time. `cargo test` runs this through
+//! `tests/examples.rs`.
+//!
+//! ```text
+//! cargo run --example embedded_participant
+//! ```
+
+use ark_usecase::bintree::BinTree;
+use ark_usecase::coordinator::Coordinator;
+use ark_usecase::keys::Keypair;
+use ark_usecase::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+use ark_usecase::signer::Signer;
+use ark_usecase::treefile::{format_key_tree, parse_key_tree};
+use ark_usecase::treemusig::{build_sorted_key_tree_with, import_key_tree_with};
+use ark_usecase::wire::WireMsg;
+use crypto_rs::secp256k1::Secp256k1Point;
+use nested_musig2::params::Params;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::error::Error;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+
+const SEED: u64 = 212;
+const SIGNERS: usize = 5;
+/// Which of the seeded keys is the embedded participant's.
+const MINE: usize = 2;
+const MSG: &[u8] = b"embedded participant";
+
+/// The coordinator and every participant but the embedded one, which it
+/// reaches only through frames.
+fn group(
+    tree: &BinTree<Secp256k1Point>,
+    others: Vec<Keypair>,
+    remote: usize,
+    to_remote: Sender<Vec<u8>>,
+    from_remote: Receiver<Vec<u8>>,
+    params: &Params,
+) -> Result<TreeSignature, Box<dyn Error>> {
+    let mut coordinator = Coordinator::with_params(tree, params.clone());
+    let mut local = Vec::with_capacity(others.len());
+    for (position, pk) in tree.leaves().enumerate().filter(|&(position, _)| position != remote) {
+        let keypair = others.iter().find(|kp| kp.pk == *pk).cloned().ok_or("no keypair for a leaf")?;
+        local.push(Signer::with_params(keypair, position, params.clone()));
+    }
+
+    for signer in &mut local {
+        coordinator.add_round1(signer.position(), signer.round1()?)?;
+    }
+    match WireMsg::from_frame(&from_remote.recv()?)? {
+        WireMsg::Round1 { position, out } if position as usize == remote => coordinator.add_round1(remote, out)?,
+        _ => return Err("expected the participant's round 1 output".into()),
+    }
+    coordinator.aggregate_round1()?;
+
+    let (outs_by_depth, merkle_path) = coordinator.round2_inputs(remote)?;
+    to_remote.send(WireMsg::Round2Inputs { outs_by_depth, merkle_path }.to_frame())?;
+    for signer in &mut local {
+        let (outs_by_depth, merkle_path) = coordinator.round2_inputs(signer.position())?;
+        coordinator.add_round2(signer.position(), signer.round2(&outs_by_depth, MSG, &merkle_path)?)?;
+    }
+    match WireMsg::from_frame(&from_remote.recv()?)? {
+        WireMsg::Round2 { position, prime } if position as usize == remote => coordinator.add_round2(remote, prime)?,
+        _ => return Err("expected the participant's partial signature".into()),
+    }
+    Ok(TreeSignature::new(coordinator.aggregate_round2()?))
+}
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng = ChaCha20Rng::seed_from_u64(SEED);
+    let keys: Vec<Keypair> = (0..SIGNERS).map(|_| Keypair::from_rng(&mut rng)).collect();
+    let params = Params::default();
+    let published = format_key_tree(&build_sorted_key_tree_with(keys.iter().map(|kp| kp.pk.clone()).collect(), &params)?);
+
+    // From here on the participant has its own keypair and the published
+    // text, nothing else.
+    let me = keys[MINE].clone();
+    let tree = import_key_tree_with(parse_key_tree(&published)?, &params)?;
+    let position = tree.leaves().position(|pk| *pk == me.pk).ok_or("my key is not in the published tree")?;
+    let root = AggregatedKey::new(tree.value().clone());
+    println!("signing as leaf {} under root key {}", position, root);
+
+    let (to_me, inbox) = channel();
+    let (outbox, from_me) = channel();
+    let others: Vec<Keypair> = keys.into_iter().filter(|kp| kp.pk != me.pk).collect();
+    let coordinator = {
+        let (tree, params) = (tree.clone(), params.clone());
+        thread::spawn(move || group(&tree, others, position, to_me, from_me, &params).map_err(|e| e.to_string()))
+    };
+
+    let wire_position = u32::try_from(position)?;
+    let mut signer = Signer::with_params(me, position, params.clone());
+    outbox.send(WireMsg::Round1 { position: wire_position, out: signer.round1()? }.to_frame())?;
+    let WireMsg::Round2Inputs { outs_by_depth, merkle_path } = WireMsg::from_frame(&inbox.recv()?)? else {
+        return Err("expected round 2 inputs".into());
+    };
+    let prime = signer.round2(&outs_by_depth, MSG, &merkle_path)?;
+    outbox.send(WireMsg::Round2 { position: wire_position, prime }.to_frame())?;
+
+    // The participant checks the result against the root it imported.
+    let sig = coordinator.join().map_err(|_| "the coordinator panicked")??;
+    if !VerifyCtx::new(params, &root).verify(MSG, &sig) {
+        return Err("the signature does not verify under the root key".into());
+    }
+    println!("signature {}", sig);
+    Ok(())
+}
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Input layout is documented in `ark_usecase::fuzzing`.
+fuzz_target!(|data: &[u8]| {
+    ark_usecase::fuzzing::check(data);
+});
@@ -0,0 +1,281 @@
+use crypto_rs::secp256k1::Secp256k1Point;
+use std::{collections::HashSet, fmt};
+
+use crate::indexed::{IndexedTree, NodeEntry};
+use crate::treemusig::{NodeState, StateMap};
+
+/// How far the signing run has progressed; decides which fields must be set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Setup,
+    Round1,
+    Round2,
+}
+
+/// A tree node identified by depth (root = 0) and left-to-right position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodePos {
+    pub depth: usize,
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssue {
+    MissingEntry(NodePos),
+    OrphanEntries(usize),
+    MissingField(NodePos, &'static str),
+    UnexpectedField(NodePos, &'static str),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    pub issues: Vec<AuditIssue>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for NodePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "depth {} position {}", self.depth, self.position)
+    }
+}
+
+impl fmt::Display for AuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditIssue::MissingEntry(pos) => write!(f, "no state entry for node at {}", pos),
+            AuditIssue::OrphanEntries(count) => {
+                write!(f, "{} state entries have no node in the tree", count)
+            }
+            AuditIssue::MissingField(pos, field) => {
+                write!(f, "node at {} is missing `{}`", pos, field)
+            }
+            AuditIssue::UnexpectedField(pos, field) => {
+                write!(f, "node at {} unexpectedly has `{}`", pos, field)
+            }
+        }
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every node with its own state entry, in preorder, on an explicit stack
+/// so that it runs before the tree's height has been checked.
+fn walk(tree: &IndexedTree<Secp256k1Point>) -> Vec<(NodePos, usize)> {
+    let mut out = Vec::new();
+    let mut next_pos: Vec<usize> = Vec::new();
+    let mut stack = vec![(tree.root(), 0)];
+    while let Some((mut idx, mut depth)) = stack.pop() {
+        // A single-child node carries its child's key and shares its state
+        // entry, so only the child is recorded.
+        while let NodeEntry { left: Some(left), right: None, .. } = tree.get(idx) {
+            idx = *left;
+            depth += 1;
+        }
+        if next_pos.len() <= depth {
+            next_pos.resize(depth + 1, 0);
+        }
+        out.push((NodePos { depth, position: next_pos[depth] }, idx));
+        next_pos[depth] += 1;
+        if let NodeEntry { left: Some(left), right: Some(right), .. } = tree.get(idx) {
+            stack.push((*right, depth + 1));
+            stack.push((*left, depth + 1));
+        }
+    }
+    out
+}
+
+/// Which fields a node must have (`true`) or must not have (`false`) at `phase`.
+/// Internal nodes only get an entry in round1, so they are not checked at setup.
+/// After round 2 only the root's entry is left, holding just the signature.
+fn expected_fields(is_leaf: bool, phase: Phase) -> [(&'static str, bool); 6] {
+    let r1 = phase == Phase::Round1;
+    let r2 = phase == Phase::Round2;
+    [
+        ("secret_key", is_leaf && !r2),
+        ("state", is_leaf && r1),
+        ("out", r1),
+        ("out_internal", !is_leaf && r1),
+        ("out_prime", r2),
+        ("state_prime", r2),
+    ]
+}
+
+fn present_fields(state: &NodeState) -> [bool; 6] {
+    [
+        state.secret_key.is_some(),
+        state.state.is_some(),
+        state.out.is_some(),
+        state.out_internal.is_some(),
+        state.out_prime.is_some(),
+        state.state_prime.is_some(),
+    ]
+}
+
+/// Cross-checks `state_map` against `tree`: one entry per node, no orphans, and
+/// each entry holding exactly the fields expected for its kind and `phase`.
+pub(crate) fn audit_state(tree: &IndexedTree<Secp256k1Point>, state_map: &StateMap, phase: Phase) -> AuditReport {
+    let mut nodes = walk(tree);
+    if phase == Phase::Round2 {
+        // the root comes first
+        nodes.truncate(1);
+    }
+
+    let mut report = AuditReport::default();
+    let mut seen: HashSet<usize> = HashSet::new();
+
+    for (pos, idx) in &nodes {
+        let is_leaf = tree.get(*idx).is_leaf();
+        if phase == Phase::Setup && !is_leaf {
+            continue;
+        }
+        seen.insert(*idx);
+        let Some(state) = state_map.get(idx) else {
+            report.issues.push(AuditIssue::MissingEntry(*pos));
+            continue;
+        };
+        let present = present_fields(state);
+        for ((field, expected), has) in expected_fields(is_leaf, phase).into_iter().zip(present) {
+            if expected && !has {
+                report.issues.push(AuditIssue::MissingField(*pos, field));
+            } else if !expected && has {
+                report.issues.push(AuditIssue::UnexpectedField(*pos, field));
+            }
+        }
+    }
+
+    let orphans = state_map.keys().filter(|k| !seen.contains(k)).count();
+    if orphans > 0 {
+        report.issues.push(AuditIssue::OrphanEntries(orphans));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bintree::BinTree;
+    use crate::secret::SecretScalar;
+    use crate::treemusig::{round1, round2, setup};
+    use nested_musig2::{keygen::keygen, params::Params};
+
+    const ROOT: NodePos = NodePos { depth: 0, position: 0 };
+
+    fn after_round1(n: u32) -> (IndexedTree<Secp256k1Point>, StateMap) {
+        let (tree, mut state_map) = setup(n);
+        round1(&tree, &mut state_map, &Params::default()).unwrap();
+        (tree, state_map)
+    }
+
+    #[test]
+    fn clean_through_all_phases() {
+        let (tree, mut state_map) = setup(5);
+        assert!(audit_state(&tree, &state_map, Phase::Setup).is_clean());
+        round1(&tree, &mut state_map, &Params::default()).unwrap();
+        assert!(audit_state(&tree, &state_map, Phase::Round1).is_clean());
+        round2(&tree, &mut state_map, b"audit", &Params::default()).unwrap();
+        assert!(audit_state(&tree, &state_map, Phase::Round2).is_clean());
+    }
+
+    #[test]
+    fn detects_missing_entry() {
+        let (tree, mut state_map) = after_round1(4);
+        state_map.remove(&tree.root());
+        let report = audit_state(&tree, &state_map, Phase::Round1);
+        assert_eq!(report.issues, vec![AuditIssue::MissingEntry(ROOT)]);
+    }
+
+    #[test]
+    fn detects_orphan_entry() {
+        let (tree, mut state_map) = after_round1(4);
+        let leaf_state = NodeState {
+            secret_key: Some(SecretScalar::new(keygen().sk)),
+            ..NodeState::default()
+        };
+        state_map.insert(tree.node_count(), leaf_state);
+        let report = audit_state(&tree, &state_map, Phase::Round1);
+        assert_eq!(report.issues, vec![AuditIssue::OrphanEntries(1)]);
+    }
+
+    #[test]
+    fn single_child_nodes_are_not_separate_entries() {
+        let (tree, mut state_map) = setup(3);
+        assert!(audit_state(&tree, &state_map, Phase::Setup).is_clean());
+        round1(&tree, &mut state_map, &Params::default()).unwrap();
+        assert!(audit_state(&tree, &state_map, Phase::Round1).is_clean());
+        round2(&tree, &mut state_map, b"audit", &Params::default()).unwrap();
+        assert!(audit_state(&tree, &state_map, Phase::Round2).is_clean());
+    }
+
+    #[test]
+    fn detects_leaf_without_secret() {
+        let (tree, mut state_map) = after_round1(2);
+        let left = tree.get(tree.root()).left.unwrap();
+        state_map.get_mut(&left).unwrap().secret_key = None;
+        let report = audit_state(&tree, &state_map, Phase::Round1);
+        assert_eq!(
+            report.issues,
+            vec![AuditIssue::MissingField(NodePos { depth: 1, position: 0 }, "secret_key")]
+        );
+    }
+
+    #[test]
+    fn detects_internal_node_with_secret() {
+        let (tree, mut state_map) = after_round1(2);
+        state_map.get_mut(&tree.root()).unwrap().secret_key = Some(SecretScalar::new(keygen().sk));
+        let report = audit_state(&tree, &state_map, Phase::Round1);
+        assert_eq!(report.issues, vec![AuditIssue::UnexpectedField(ROOT, "secret_key")]);
+    }
+
+    #[test]
+    fn round2_leaves_only_the_root() {
+        let (tree, mut state_map) = after_round1(6);
+        round2(&tree, &mut state_map, b"audit", &Params::default()).unwrap();
+        assert_eq!(state_map.len(), 1);
+        assert!(audit_state(&tree, &state_map, Phase::Round2).is_clean());
+
+        let leaf = tree.leaf_indices().next().unwrap();
+        state_map.insert(leaf, NodeState::default());
+        let report = audit_state(&tree, &state_map, Phase::Round2);
+        assert_eq!(report.issues, vec![AuditIssue::OrphanEntries(1)]);
+    }
+
+    #[test]
+    fn deep_tree_does_not_overflow() {
+        let depth = 100_000;
+        let pk = keygen().pk;
+        let mut tree = BinTree::leaf(pk.clone());
+        for _ in 0..depth {
+            tree = BinTree::node(tree, BinTree::leaf(pk.clone()), pk.clone());
+        }
+        let tree = IndexedTree::from_tree(&tree);
+        let report = audit_state(&tree, &StateMap::new(), Phase::Setup);
+        assert_eq!(report.issues.len(), depth + 1);
+        assert_eq!(report.issues[0], AuditIssue::MissingEntry(NodePos { depth, position: 0 }));
+    }
+
+    #[test]
+    fn detects_wrong_phase_fields() {
+        let (tree, state_map) = after_round1(2);
+        let report = audit_state(&tree, &state_map, Phase::Round2);
+        assert!(report.issues.contains(&AuditIssue::MissingField(ROOT, "out_prime")));
+        assert!(report.issues.contains(&AuditIssue::MissingField(ROOT, "state_prime")));
+
+        let report = audit_state(&tree, &state_map, Phase::Setup);
+        assert!(report.issues.contains(&AuditIssue::UnexpectedField(
+            NodePos { depth: 1, position: 0 },
+            "out"
+        )));
+    }
+}
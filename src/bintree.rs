@@ -1,34 +1,269 @@
+use std::{convert::Infallible, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError<E> {
+    Empty,
+    /// The leaf source failed on the `index`-th item.
+    Source { index: usize, error: E },
+    /// The aggregation callback failed.
+    Aggregation(E),
+    /// A `MultiTree` node was given room for fewer than two children.
+    Arity(usize),
+}
+
+/// How `from_vec_shaped` arranges the leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TreeShape {
+    /// Pairwise, level by level, as `from_vec` does: height ~log2(n) + 1.
+    #[default]
+    Balanced,
+    /// Each leaf is aggregated onto the running value of all before it, so
+    /// every new leaf sits one level higher: height n.
+    LeftList,
+}
+
+/// A node whose stored value is not the aggregate of its children, at
+/// `depth` (root = 0) and left-to-right `position` within that depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub depth: usize,
+    pub position: usize,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "node at depth {} position {} does not match its children",
+            self.depth, self.position
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Level sizes that no `from_vec` tree has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelsError {
+    Empty,
+    /// For the number of leaves in the last level, level `level` (root = 0)
+    /// should hold `expected` nodes.
+    LevelSize { level: usize, expected: usize, got: usize },
+}
+
+impl fmt::Display for LevelsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelsError::Empty => write!(f, "no levels given"),
+            LevelsError::LevelSize { level, expected, got } => {
+                write!(f, "level {} has {} nodes, expected {}", level, got, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelsError {}
+
+/// A tree built from a list of leaves, along with where in that list each
+/// leaf came from, which sorted construction otherwise loses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafOrigins<T> {
+    pub tree: BinTree<T>,
+    /// Input position of each leaf, left to right.
+    pub positions: Vec<usize>,
+}
+
+impl<T: Clone + PartialEq> LeafOrigins<T> {
+    /// The input position of the first leaf equal to `target`.
+    pub fn leaf_index_of(&self, target: &T) -> Option<usize> {
+        self.tree.leaves().position(|leaf| leaf == target).map(|i| self.positions[i])
+    }
+}
+
+/// Cached facts about an internal node's subtree, so `height` and
+/// `leaf_count` need no walk. Every constructor and mutating method keeps
+/// it up to date; build nodes through them rather than as literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Meta {
+    pub height: usize,
+    pub leaf_count: usize,
+}
+
+impl Meta {
+    fn of<T>(left: &BinTree<T>, right: Option<&BinTree<T>>) -> Self {
+        Meta {
+            height: 1 + left.height().max(right.map_or(0, BinTree::height)),
+            leaf_count: left.leaf_count() + right.map_or(0, BinTree::leaf_count),
+        }
+    }
+}
+
+/// Serialized without `meta`, which is recomputed on the way back in
+/// rather than taken on trust from the input.
+///
+/// Dropping runs on an explicit stack, as do the walks below unless noted.
+/// The derived `Clone`, `PartialEq`, `Debug` and serde impls recurse once
+/// per level, so they need a stack that fits the tree's height; trees from
+/// untrusted input should have their height checked first.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(rename_all = "snake_case", from = "TreeRepr<T>", bound(deserialize = "T: serde::Deserialize<'de>"))
+)]
 pub enum BinTree<T> {
     Leaf(T),
+    /// An internal node. `right` is `None` for the odd subtree left over at
+    /// the end of a level; such a node carries its child's value unchanged.
     Node {
         left: Box<BinTree<T>>,
-        right: Box<BinTree<T>>,
+        right: Option<Box<BinTree<T>>>,
+        value: T,
+        #[cfg_attr(feature = "serde", serde(skip_serializing))]
+        meta: Meta,
+    },
+}
+
+/// `BinTree` as serialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TreeRepr<T> {
+    Leaf(T),
+    Node {
+        left: Box<TreeRepr<T>>,
+        right: Option<Box<TreeRepr<T>>>,
         value: T,
     },
 }
 
+#[cfg(feature = "serde")]
+impl<T> From<TreeRepr<T>> for BinTree<T> {
+    fn from(repr: TreeRepr<T>) -> Self {
+        match repr {
+            TreeRepr::Leaf(value) => BinTree::Leaf(value),
+            TreeRepr::Node { left, right, value } => {
+                BinTree::from_parts((*left).into(), right.map(|right| (*right).into()), value)
+            }
+        }
+    }
+}
+
+impl<T> BinTree<T> {
+    /// Levels in the tree, leaves included.
+    pub fn height(&self) -> usize {
+        match self {
+            BinTree::Leaf(_) => 1,
+            BinTree::Node { meta, .. } => meta.height,
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        match self {
+            BinTree::Leaf(_) => 1,
+            BinTree::Node { meta, .. } => meta.leaf_count,
+        }
+    }
+
+    /// An internal node with `value` stored as given, whether or not it is
+    /// the aggregate of its children. For reassembling a tree from its
+    /// parts; `node` and `unary` are the usual constructors.
+    pub fn from_parts(left: Self, right: Option<Self>, value: T) -> Self {
+        let meta = Meta::of(&left, right.as_ref());
+        BinTree::Node {
+            left: Box::new(left),
+            right: right.map(Box::new),
+            value,
+            meta,
+        }
+    }
+
+    /// Moves the value and, for a node, the children and `meta` out of the
+    /// tree, which `Drop` otherwise forbids.
+    pub(crate) fn into_parts(self) -> (T, Option<Children<T>>) {
+        let this = std::mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, and each field is read out once.
+        unsafe {
+            match &*this {
+                BinTree::Leaf(value) => (std::ptr::read(value), None),
+                BinTree::Node { left, right, value, meta } => {
+                    (std::ptr::read(value), Some((std::ptr::read(left), std::ptr::read(right), *meta)))
+                }
+            }
+        }
+    }
+}
+
+/// A node's children and `meta`, as moved out by `into_parts`.
+pub(crate) type Children<T> = (Box<BinTree<T>>, Option<Box<BinTree<T>>>, Meta);
+
+/// Turns the node in `slot` into a leaf holding its own value and returns
+/// its children, so no drop ever recurses into them.
+fn take_children<T>(slot: &mut BinTree<T>) -> Option<Children<T>> {
+    if slot.is_leaf() {
+        return None;
+    }
+    // SAFETY: nothing between the read and the write can panic, so `slot`
+    // is never observed or dropped while its contents are moved out.
+    unsafe {
+        let (value, children) = std::ptr::read(slot).into_parts();
+        std::ptr::write(slot, BinTree::Leaf(value));
+        children
+    }
+}
+
+impl<T> Drop for BinTree<T> {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        let mut next = take_children(self);
+        loop {
+            if let Some((left, right, _)) = next {
+                stack.push(left);
+                stack.extend(right);
+            }
+            let Some(mut child) = stack.pop() else {
+                break;
+            };
+            next = take_children(&mut child);
+        }
+    }
+}
+
+impl<T, U> BinTree<(T, U)> {
+    /// Splits a zipped tree back into its two halves.
+    pub fn unzip(self) -> (BinTree<T>, BinTree<U>) {
+        match self.into_parts() {
+            ((x, y), None) => (BinTree::Leaf(x), BinTree::Leaf(y)),
+            ((x, y), Some((left, right, meta))) => {
+                let (a_left, b_left) = left.unzip();
+                let (a_right, b_right) = right.map(|right| right.unzip()).unzip();
+                let a = BinTree::Node { left: Box::new(a_left), right: a_right.map(Box::new), value: x, meta };
+                let b = BinTree::Node { left: Box::new(b_left), right: b_right.map(Box::new), value: y, meta };
+                (a, b)
+            }
+        }
+    }
+}
+
 impl<T: Clone> BinTree<T> {
     pub fn leaf(value: T) -> Self {
         Self::Leaf(value)
     }
 
     pub fn node(left: Self, right: Self, value: T) -> Self {
-        Self::Node {
-            left: Box::new(left),
-            right: Box::new(right),
-            value,
-        }
+        Self::from_parts(left, Some(right), value)
+    }
+
+    /// A single-child node passing `child`'s value through unaggregated.
+    pub fn unary(child: Self) -> Self {
+        let value = child.value().clone();
+        Self::from_parts(child, None, value)
     }
 
     pub fn value(&self) -> &T {
         match self {
             BinTree::Leaf(value) => value,
-            BinTree::Node {
-                left: _,
-                right: _,
-                value,
-            } => value,
+            BinTree::Node { value, .. } => value,
         }
     }
 
@@ -40,59 +275,730 @@ impl<T: Clone> BinTree<T> {
         matches!(self, Self::Node { .. })
     }
 
-    pub fn height(&self) -> usize {
-        match self {
-            Self::Leaf(_) => 1,
-            Self::Node {
-                left,
-                right,
-                value: _,
-            } => {
-                1 + left.height().max(right.height())
+    /// A tree of the same shape with every value passed through `f`
+    /// (called in preorder).
+    pub fn map<U, F>(&self, mut f: F) -> BinTree<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        fn go<T, U, F: FnMut(&T) -> U>(node: &BinTree<T>, f: &mut F) -> BinTree<U> {
+            match node {
+                BinTree::Leaf(value) => BinTree::Leaf(f(value)),
+                BinTree::Node { left, right, value, meta } => {
+                    let value = f(value);
+                    BinTree::Node {
+                        left: Box::new(go(left, f)),
+                        right: right.as_ref().map(|right| Box::new(go(right, f))),
+                        value,
+                        meta: *meta,
+                    }
+                }
             }
         }
+        go(self, &mut f)
     }
 
-    pub fn leaf_count(&self) -> usize {
-        match self {
-            Self::Leaf(_) => 1,
-            Self::Node {
-                left,
-                right,
-                value: _,
-            } => {
-                left.leaf_count()
-                    + right.leaf_count()
+    /// Consuming `map`.
+    pub fn into_map<U, F>(self, mut f: F) -> BinTree<U>
+    where
+        F: FnMut(T) -> U,
+    {
+        fn go<T, U, F: FnMut(T) -> U>(node: BinTree<T>, f: &mut F) -> BinTree<U> {
+            match node.into_parts() {
+                (value, None) => BinTree::Leaf(f(value)),
+                (value, Some((left, right, meta))) => {
+                    let value = f(value);
+                    BinTree::Node {
+                        left: Box::new(go(*left, f)),
+                        right: right.map(|right| Box::new(go(*right, f))),
+                        value,
+                        meta,
+                    }
+                }
+            }
+        }
+        go(self, &mut f)
+    }
+
+    /// The tree with every value dropped; two trees have the same shape iff
+    /// their `shape()`s are equal.
+    pub fn shape(&self) -> BinTree<()> {
+        self.map(|_| ())
+    }
+
+    /// Pairs every value with the one at the same position in `other`, or
+    /// `None` if the two trees differ in shape.
+    pub fn zip<U: Clone>(&self, other: &BinTree<U>) -> Option<BinTree<(T, U)>> {
+        fn go<T: Clone, U: Clone>(a: &BinTree<T>, b: &BinTree<U>) -> BinTree<(T, U)> {
+            match (a, b) {
+                (BinTree::Leaf(x), BinTree::Leaf(y)) => BinTree::Leaf((x.clone(), y.clone())),
+                (
+                    BinTree::Node { left: a_left, right: a_right, value: x, meta },
+                    BinTree::Node { left: b_left, right: b_right, value: y, .. },
+                ) => BinTree::Node {
+                    left: Box::new(go(a_left, b_left)),
+                    right: a_right.as_deref().zip(b_right.as_deref()).map(|(a, b)| Box::new(go(a, b))),
+                    value: (x.clone(), y.clone()),
+                    meta: *meta,
+                },
+                _ => unreachable!("shapes are checked before zipping"),
+            }
+        }
+        self.same_shape(other).then(|| go(self, other))
+    }
+
+    /// Whether `other` has the same leaf/node structure, whatever its values.
+    /// A single-child node only matches another single-child node.
+    pub fn same_shape<U>(&self, other: &BinTree<U>) -> bool {
+        let mut stack: Vec<(&BinTree<T>, &BinTree<U>)> = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (BinTree::Leaf(_), BinTree::Leaf(_)) => {}
+                (
+                    BinTree::Node { left: a_left, right: a_right, value: _, .. },
+                    BinTree::Node { left: b_left, right: b_right, value: _, .. },
+                ) => {
+                    match (a_right, b_right) {
+                        (Some(a), Some(b)) => stack.push((a, b)),
+                        (None, None) => {}
+                        _ => return false,
+                    }
+                    stack.push((a_left, b_left));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Leaf values, left to right.
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        self.iter_preorder_nodes().filter_map(|node| match node {
+            BinTree::Leaf(value) => Some(value),
+            BinTree::Node { .. } => None,
+        })
+    }
+
+    /// Every node value, parent before children, left before right.
+    pub fn iter_preorder(&self) -> impl Iterator<Item = &T> {
+        self.iter_preorder_nodes().map(|node| node.value())
+    }
+
+    /// Every node value, children (left, then right) before their parent.
+    pub fn iter_postorder(&self) -> impl Iterator<Item = &T> {
+        // `true` marks a node whose children have already been pushed.
+        let mut stack = vec![(self, false)];
+        std::iter::from_fn(move || {
+            while let Some((node, expanded)) = stack.pop() {
+                match node {
+                    BinTree::Node { left, right, value: _, .. } if !expanded => {
+                        stack.push((node, true));
+                        if let Some(right) = right {
+                            stack.push((right, false));
+                        }
+                        stack.push((left, false));
+                    }
+                    _ => return Some(node.value()),
+                }
+            }
+            None
+        })
+    }
+
+    pub fn contains_leaf(&self, target: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.leaves().any(|value| value == target)
+    }
+
+    /// First value in preorder (internal nodes included) matching `pred`.
+    pub fn find<P>(&self, pred: P) -> Option<&T>
+    where
+        P: Fn(&T) -> bool,
+    {
+        self.iter_preorder().find(|value| pred(value))
+    }
+
+    /// Distance from the root (depth 0) to the first node, in preorder, whose
+    /// value equals `target`.
+    pub fn depth_of(&self, target: &T) -> Option<usize>
+    where
+        T: PartialEq,
+    {
+        self.iter_preorder_depths()
+            .find(|(_, node)| node.value() == target)
+            .map(|(depth, _)| depth)
+    }
+
+    /// Every leaf with its depth, left to right.
+    pub fn leaf_depths(&self) -> Vec<(usize, &T)> {
+        self.iter_preorder_depths()
+            .filter_map(|(depth, node)| match node {
+                BinTree::Leaf(value) => Some((depth, value)),
+                BinTree::Node { .. } => None,
+            })
+            .collect()
+    }
+
+    /// The leftmost of the leaves furthest from the root, found by following
+    /// the taller child down.
+    pub fn deepest_leaf(&self) -> &T {
+        let mut node = self;
+        while let BinTree::Node { left, right, .. } = node {
+            node = match right {
+                Some(right) if right.height() > left.height() => right,
+                _ => left,
+            };
+        }
+        node.value()
+    }
+
+    /// Values of all nodes `depth` edges below the root, left to right. A
+    /// promoted leaf only shows up at the depth it actually sits at.
+    pub fn nodes_at_depth(&self, depth: usize) -> Vec<&T> {
+        self.levels().into_iter().nth(depth).unwrap_or_default()
+    }
+
+    /// Level-order decomposition: `levels()[d]` is `nodes_at_depth(d)`.
+    pub fn levels(&self) -> Vec<Vec<&T>> {
+        let mut levels = Vec::new();
+        let mut current = vec![self];
+        while !current.is_empty() {
+            let mut next = Vec::new();
+            for node in &current {
+                if let BinTree::Node { left, right, value: _, .. } = node {
+                    next.push(left.as_ref());
+                    if let Some(right) = right {
+                        next.push(right.as_ref());
+                    }
+                }
+            }
+            levels.push(current.into_iter().map(|node| node.value()).collect());
+            current = next;
+        }
+        levels
+    }
+
+    /// Sibling values on the way from the first leaf equal to `target` up to
+    /// the root (nearest sibling first), or `None` if no leaf matches.
+    /// Single-child nodes have no sibling to contribute, so a leaf under one
+    /// has a path shorter than its depth.
+    pub fn merkle_path(&self, target: &T) -> Option<Vec<T>>
+    where
+        T: PartialEq,
+    {
+        // Depth-first, tracking the siblings along the current root-to-node
+        // path. `depth` only counts two-child ancestors, i.e. path entries.
+        let mut stack: Vec<(&BinTree<T>, usize, Option<&T>)> = vec![(self, 0, None)];
+        let mut siblings: Vec<&T> = Vec::new();
+        while let Some((node, depth, sibling)) = stack.pop() {
+            match sibling {
+                Some(sibling) => {
+                    siblings.truncate(depth - 1);
+                    siblings.push(sibling);
+                }
+                None => siblings.truncate(depth),
+            }
+            match node {
+                BinTree::Leaf(value) if value == target => {
+                    return Some(siblings.into_iter().rev().cloned().collect());
+                }
+                BinTree::Leaf(_) => {}
+                BinTree::Node { left, right: Some(right), value: _, .. } => {
+                    stack.push((right, depth + 1, Some(left.value())));
+                    stack.push((left, depth + 1, Some(right.value())));
+                }
+                BinTree::Node { left, right: None, value: _, .. } => {
+                    stack.push((left, depth, None));
+                }
+            }
+        }
+        None
+    }
+
+    /// Draws the tree one node per line with box-drawing connectors, root
+    /// first; leaves are marked with `(leaf)`.
+    pub fn render<F>(&self, fmt_value: F) -> String
+    where
+        F: Fn(&T) -> String,
+    {
+        let mut out = String::new();
+        // (node, prefix for this line, prefix for its children)
+        let mut stack = vec![(self, String::new(), String::new())];
+        while let Some((node, line_prefix, child_prefix)) = stack.pop() {
+            out.push_str(&line_prefix);
+            out.push_str(&fmt_value(node.value()));
+            match node {
+                BinTree::Leaf(_) => out.push_str(" (leaf)\n"),
+                BinTree::Node { left, right: Some(right), value: _, .. } => {
+                    out.push('\n');
+                    stack.push((right, format!("{}└── ", child_prefix), format!("{}    ", child_prefix)));
+                    stack.push((left, format!("{}├── ", child_prefix), format!("{}│   ", child_prefix)));
+                }
+                BinTree::Node { left, right: None, value: _, .. } => {
+                    out.push('\n');
+                    stack.push((left, format!("{}└── ", child_prefix), format!("{}    ", child_prefix)));
+                }
+            }
+        }
+        out
+    }
+
+    fn iter_preorder_depths(&self) -> impl Iterator<Item = (usize, &BinTree<T>)> {
+        let mut stack = vec![(0, self)];
+        std::iter::from_fn(move || {
+            let (depth, node) = stack.pop()?;
+            if let BinTree::Node { left, right, value: _, .. } = node {
+                if let Some(right) = right {
+                    stack.push((depth + 1, right));
+                }
+                stack.push((depth + 1, left));
+            }
+            Some((depth, node))
+        })
+    }
+
+    fn iter_preorder_nodes(&self) -> impl Iterator<Item = &BinTree<T>> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            if let BinTree::Node { left, right, value: _, .. } = node {
+                if let Some(right) = right {
+                    stack.push(right);
+                }
+                stack.push(left);
+            }
+            Some(node)
+        })
+    }
+
+    /// Bottom-up reduction: `leaf_f` maps each leaf, `node_f` combines the
+    /// left result, the right result (if the node has a right child) and the
+    /// node's own value. Runs on an explicit stack.
+    pub fn fold<A, L, N>(&self, mut leaf_f: L, mut node_f: N) -> A
+    where
+        L: FnMut(&T) -> A,
+        N: FnMut(A, Option<A>, &T) -> A,
+    {
+        let mut results: Vec<A> = Vec::new();
+        // `true` marks a node whose children are already on `results`.
+        let mut stack = vec![(self, false)];
+        while let Some((node, expanded)) = stack.pop() {
+            match node {
+                BinTree::Leaf(value) => results.push(leaf_f(value)),
+                BinTree::Node { left, right, value: _, .. } if !expanded => {
+                    stack.push((node, true));
+                    if let Some(right) = right {
+                        stack.push((right, false));
+                    }
+                    stack.push((left, false));
+                }
+                BinTree::Node { left: _, right, value, .. } => {
+                    let right = right.as_ref().map(|_| results.pop().expect("right result"));
+                    let left = results.pop().expect("left result");
+                    results.push(node_f(left, right, value));
+                }
             }
         }
+        results.pop().expect("fold produces exactly one result")
     }
 
-    pub fn from_vec(leaves: Vec<T>, agg: fn(T, T) -> T) -> Self {
+    pub fn from_vec<F>(leaves: Vec<T>, mut agg: F) -> Self
+    where
+        F: FnMut(T, T) -> T,
+    {
         assert!(!leaves.is_empty(), "cannot build tree from empty vec");
         let nodes: Vec<BinTree<T>> = leaves.into_iter().map(|x| Self::leaf(x)).collect();
-        Self::build_tree(nodes, agg)
+        match Self::build_tree(nodes, &mut |a, b| Ok::<T, Infallible>(agg(a, b))) {
+            Ok(tree) => tree,
+            Err(never) => match never {},
+        }
     }
 
-    fn build_tree(nodes: Vec<BinTree<T>>, agg: fn(T, T) -> T) -> Self {
-        assert!(!nodes.is_empty(), "cannot build tree from empty vec");
-        if nodes.len() == 1 {
-            nodes[0].clone()
-        } else {
-            let mut _nodes = Vec::new();
-            let n = nodes.len();
-
-            for i in (0..n).step_by(2) {
-                let left = nodes[i].clone();
-                let mut value = left.value().clone();
-                if i + 1 < n {
-                    value = agg(value, nodes[i + 1].value().clone());
-                    _nodes.push(Self::node(left, nodes[i+1].clone(), value));
-                } else {
-                    _nodes.push(left);
+    /// Like `from_vec`, but stops at the first failing aggregation and
+    /// reports an empty input as an error instead of panicking.
+    pub fn try_from_vec<E, F>(leaves: Vec<T>, mut agg: F) -> Result<Self, BuildError<E>>
+    where
+        F: FnMut(T, T) -> Result<T, E>,
+    {
+        if leaves.is_empty() {
+            return Err(BuildError::Empty);
+        }
+        let nodes: Vec<BinTree<T>> = leaves.into_iter().map(|x| Self::leaf(x)).collect();
+        Self::build_tree(nodes, &mut agg).map_err(BuildError::Aggregation)
+    }
+
+    pub fn from_vec_shaped<F>(leaves: Vec<T>, shape: TreeShape, mut agg: F) -> Self
+    where
+        F: FnMut(T, T) -> T,
+    {
+        match shape {
+            TreeShape::Balanced => Self::from_vec(leaves, agg),
+            TreeShape::LeftList => {
+                let mut leaves = leaves.into_iter();
+                let first = leaves.next().expect("cannot build tree from empty vec");
+                leaves.fold(Self::leaf(first), |acc, leaf| {
+                    let value = agg(acc.value().clone(), leaf.clone());
+                    Self::node(acc, Self::leaf(leaf), value)
+                })
+            }
+        }
+    }
+
+    /// `from_vec` over the leaves sorted by `key_fn`, so the tree does not
+    /// depend on the order they arrived in. Leaves with equal keys keep their
+    /// input order, so keys should be unique.
+    pub fn from_vec_sorted<K, KF, F>(mut leaves: Vec<T>, key_fn: KF, agg: F) -> Self
+    where
+        K: Ord,
+        KF: FnMut(&T) -> K,
+        F: FnMut(T, T) -> T,
+    {
+        leaves.sort_by_cached_key(key_fn);
+        Self::from_vec(leaves, agg)
+    }
+
+    /// `try_from_vec` over the leaves sorted by `key_fn`.
+    pub fn try_from_vec_sorted<K, KF, E, F>(mut leaves: Vec<T>, key_fn: KF, agg: F) -> Result<Self, BuildError<E>>
+    where
+        K: Ord,
+        KF: FnMut(&T) -> K,
+        F: FnMut(T, T) -> Result<T, E>,
+    {
+        leaves.sort_by_cached_key(key_fn);
+        Self::try_from_vec(leaves, agg)
+    }
+
+    /// `from_vec`, remembering each leaf's position in `leaves`.
+    pub fn from_vec_indexed<F>(leaves: Vec<T>, agg: F) -> LeafOrigins<T>
+    where
+        F: FnMut(T, T) -> T,
+    {
+        let positions = (0..leaves.len()).collect();
+        LeafOrigins { tree: Self::from_vec(leaves, agg), positions }
+    }
+
+    /// `try_from_vec_sorted`, remembering where each leaf was in `leaves`
+    /// before sorting.
+    pub fn try_from_vec_sorted_indexed<K, KF, E, F>(leaves: Vec<T>, mut key_fn: KF, agg: F) -> Result<LeafOrigins<T>, BuildError<E>>
+    where
+        K: Ord,
+        KF: FnMut(&T) -> K,
+        F: FnMut(T, T) -> Result<T, E>,
+    {
+        let mut tagged: Vec<(usize, T)> = leaves.into_iter().enumerate().collect();
+        tagged.sort_by_cached_key(|(_, leaf)| key_fn(leaf));
+        let (positions, leaves) = tagged.into_iter().unzip();
+        Ok(LeafOrigins { tree: Self::try_from_vec(leaves, agg)?, positions })
+    }
+
+    /// Rebuilds a `from_vec`-shaped tree from its `levels()`, root first,
+    /// taking every value as given; nothing is aggregated, so run `validate`
+    /// on a tree from an untrusted source. Every leaf is in the last level,
+    /// which fixes how many nodes each level above must hold.
+    pub fn from_levels(levels: Vec<Vec<T>>) -> Result<Self, LevelsError> {
+        let n = levels.last().map_or(0, Vec::len);
+        if n == 0 {
+            return Err(LevelsError::Empty);
+        }
+        let mut sizes = vec![n];
+        while sizes[0] > 1 {
+            sizes.insert(0, sizes[0].div_ceil(2));
+        }
+        for (level, values) in levels.iter().enumerate() {
+            let expected = sizes.get(level).copied().unwrap_or(0);
+            if values.len() != expected {
+                return Err(LevelsError::LevelSize { level, expected, got: values.len() });
+            }
+        }
+
+        let mut levels = levels.into_iter().rev();
+        let mut nodes: Vec<Self> = levels.next().expect("checked above").into_iter().map(Self::leaf).collect();
+        for values in levels {
+            let mut children = nodes.into_iter();
+            nodes = values
+                .into_iter()
+                .map(|value| {
+                    let left = children.next().expect("level sizes checked above");
+                    Self::from_parts(left, children.next(), value)
+                })
+                .collect();
+        }
+        Ok(nodes.pop().expect("the root level holds one node"))
+    }
+
+    /// Builds the same tree as `from_vec` while consuming leaves one at a
+    /// time. At most one pending subtree is held per level, so apart from the
+    /// tree itself memory stays O(log n).
+    pub fn from_iter_streaming<E, I, F>(leaves: I, mut agg: F) -> Result<Self, BuildError<E>>
+    where
+        I: IntoIterator<Item = Result<T, E>>,
+        F: FnMut(T, T) -> T,
+    {
+        let mut pending: Vec<Option<BinTree<T>>> = Vec::new();
+        for (index, leaf) in leaves.into_iter().enumerate() {
+            let leaf = leaf.map_err(|error| BuildError::Source { index, error })?;
+            let mut carry = Self::leaf(leaf);
+            let mut level = 0;
+            loop {
+                if level == pending.len() {
+                    pending.push(None);
+                }
+                match pending[level].take() {
+                    Some(left) => {
+                        let value = agg(left.value().clone(), carry.value().clone());
+                        carry = Self::node(left, carry, value);
+                        level += 1;
+                    }
+                    None => {
+                        pending[level] = Some(carry);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Fold the leftovers bottom-up. Below the top level a lone subtree is
+        // the odd one out of its level, so it moves up inside a single-child
+        // node, exactly as in `build_tree`.
+        let top = pending.len().checked_sub(1).ok_or(BuildError::Empty)?;
+        let mut carry: Option<BinTree<T>> = None;
+        for (level, slot) in pending.into_iter().enumerate() {
+            carry = match (slot, carry) {
+                (Some(left), Some(right)) => {
+                    let value = agg(left.value().clone(), right.value().clone());
+                    Some(Self::node(left, right, value))
+                }
+                (Some(lone), None) | (None, Some(lone)) if level < top => Some(Self::unary(lone)),
+                (lone, None) | (None, lone) => lone,
+            };
+        }
+        carry.ok_or(BuildError::Empty)
+    }
+
+    /// Recomputes every internal value from its children and reports the
+    /// deepest mismatch (leftmost on ties). A tampered value also breaks its
+    /// parent's check, so the deepest mismatch is the node that was changed.
+    /// A single-child node must carry its child's value.
+    pub fn validate(&self, mut agg: impl FnMut(&T, &T) -> T) -> Result<(), ValidationError>
+    where
+        T: PartialEq,
+    {
+        let mut levels: Vec<Vec<&BinTree<T>>> = vec![vec![self]];
+        loop {
+            let next: Vec<&BinTree<T>> = levels[levels.len() - 1]
+                .iter()
+                .flat_map(|node| match node {
+                    BinTree::Leaf(_) => Vec::new(),
+                    BinTree::Node { left, right, value: _, .. } => {
+                        std::iter::once(left.as_ref()).chain(right.as_deref()).collect()
+                    }
+                })
+                .collect();
+            if next.is_empty() {
+                break;
+            }
+            levels.push(next);
+        }
+
+        for (depth, level) in levels.iter().enumerate().rev() {
+            for (position, node) in level.iter().enumerate() {
+                let BinTree::Node { left, right, value, .. } = node else {
+                    continue;
                 };
+                let ok = match right {
+                    Some(right) => agg(left.value(), right.value()) == *value,
+                    None => left.value() == value,
+                };
+                if !ok {
+                    return Err(ValidationError { depth, position });
+                }
             }
-            Self::build_tree(_nodes, agg)
         }
+        Ok(())
+    }
+
+    /// Adds one leaf at the shallowest free spot, found breadth-first: the
+    /// empty slot of a single-child node, or else a leaf, which becomes a node
+    /// holding the old and the new leaf. Only the values on the path back to
+    /// the root are recomputed; every other subtree is left where it is.
+    pub fn insert_leaf(&mut self, value: T, mut agg: impl FnMut(&T, &T) -> T) {
+        // (node, parent index in `seen`, reached via the right child)
+        let mut seen: Vec<(&BinTree<T>, usize, bool)> = vec![(self, 0, false)];
+        let mut i = 0;
+        let target = loop {
+            match seen[i].0 {
+                BinTree::Leaf(_) | BinTree::Node { left: _, right: None, value: _, .. } => break i,
+                BinTree::Node { left, right: Some(right), value: _, .. } => {
+                    seen.push((left, i, false));
+                    seen.push((right, i, true));
+                }
+            }
+            i += 1;
+        };
+        let mut path = Vec::new();
+        let mut i = target;
+        while i != 0 {
+            path.push(seen[i].2);
+            i = seen[i].1;
+        }
+        path.reverse();
+        Self::attach(self, &path, value, &mut agg);
+    }
+
+    /// Removes the first leaf equal to `target`. Its nearest two-child
+    /// ancestor is replaced by the other child (dropping any single-child
+    /// nodes in between), and the values above are recomputed. Returns
+    /// `false`, leaving the tree as it was, if no leaf matches or `target` is
+    /// the tree's only leaf, since a tree cannot be empty.
+    pub fn remove_leaf(&mut self, target: &T, mut agg: impl FnMut(&T, &T) -> T) -> bool
+    where
+        T: PartialEq,
+    {
+        let Some(path) = self.leaf_path(target) else {
+            return false;
+        };
+        let mut cut = None;
+        let mut node = &*self;
+        for (i, &go_right) in path.iter().enumerate() {
+            let BinTree::Node { left, right, value: _, .. } = node else {
+                unreachable!("leaf path runs through internal nodes only");
+            };
+            node = match right {
+                Some(right) => {
+                    cut = Some(i);
+                    if go_right { &**right } else { &**left }
+                }
+                None => &**left,
+            };
+        }
+        let Some(cut) = cut else {
+            return false;
+        };
+        Self::detach(self, &path[..cut], path[cut], &mut agg);
+        true
+    }
+
+    /// Left/right turns (`true` = right) from the root to the first leaf
+    /// equal to `target`, searched in preorder.
+    fn leaf_path(&self, target: &T) -> Option<Vec<bool>>
+    where
+        T: PartialEq,
+    {
+        let mut stack: Vec<(&BinTree<T>, usize, bool)> = vec![(self, 0, false)];
+        let mut path = Vec::new();
+        while let Some((node, depth, go_right)) = stack.pop() {
+            if depth > 0 {
+                path.truncate(depth - 1);
+                path.push(go_right);
+            }
+            match node {
+                BinTree::Leaf(value) if value == target => return Some(path),
+                BinTree::Leaf(_) => {}
+                BinTree::Node { left, right, value: _, .. } => {
+                    if let Some(right) = right {
+                        stack.push((right, depth + 1, true));
+                    }
+                    stack.push((left, depth + 1, false));
+                }
+            }
+        }
+        None
+    }
+
+    /// Follows `path` down to a two-child node and replaces it with the child
+    /// not on the `drop_right` side, then recomputes values on the way back.
+    fn detach<F>(node: &mut Self, path: &[bool], drop_right: bool, agg: &mut F)
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        let Some((&go_right, rest)) = path.split_first() else {
+            let placeholder = Self::leaf(node.value().clone());
+            let BinTree::Node { left, right: Some(right), value: _, .. } = node else {
+                unreachable!("detach point is a two-child node");
+            };
+            let kept = if drop_right { &mut **left } else { &mut **right };
+            let kept = std::mem::replace(kept, placeholder);
+            *node = kept;
+            return;
+        };
+        let BinTree::Node { left, right, value, meta } = node else {
+            unreachable!("leaf path runs through internal nodes only");
+        };
+        match right {
+            Some(right) => {
+                let child = if go_right { &mut **right } else { &mut **left };
+                Self::detach(child, rest, drop_right, agg);
+                *value = agg(left.value(), right.value());
+            }
+            None => {
+                Self::detach(left, rest, drop_right, agg);
+                *value = left.value().clone();
+            }
+        }
+        *meta = Meta::of(left, right.as_deref());
+    }
+
+    fn attach<F>(node: &mut Self, path: &[bool], new: T, agg: &mut F)
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        let Some((&go_right, rest)) = path.split_first() else {
+            match node {
+                BinTree::Leaf(old) => {
+                    let value = agg(old, &new);
+                    let old = std::mem::replace(node, Self::leaf(new.clone()));
+                    *node = Self::node(old, Self::leaf(new), value);
+                }
+                BinTree::Node { left, right, value, meta } => {
+                    *value = agg(left.value(), &new);
+                    *right = Some(Box::new(Self::leaf(new)));
+                    *meta = Meta::of(left, right.as_deref());
+                }
+            }
+            return;
+        };
+        let BinTree::Node { left, right, value, meta } = node else {
+            unreachable!("insertion path runs through internal nodes only");
+        };
+        match right {
+            Some(right) => {
+                let child = if go_right { &mut **right } else { &mut **left };
+                Self::attach(child, rest, new, agg);
+                *value = agg(left.value(), right.value());
+            }
+            None => {
+                Self::attach(left, rest, new, agg);
+                *value = left.value().clone();
+            }
+        }
+        *meta = Meta::of(left, right.as_deref());
+    }
+
+    fn build_tree<E, F>(mut nodes: Vec<BinTree<T>>, agg: &mut F) -> Result<Self, E>
+    where
+        F: FnMut(T, T) -> Result<T, E>,
+    {
+        assert!(!nodes.is_empty(), "cannot build tree from empty vec");
+        // One pass per level until a single root remains. Subtrees are moved,
+        // never cloned; only the values handed to `agg` (and the one passed up
+        // by a single-child node) are copied.
+        while nodes.len() > 1 {
+            let mut next = Vec::with_capacity(nodes.len().div_ceil(2));
+            let mut level = nodes.into_iter();
+            while let Some(left) = level.next() {
+                match level.next() {
+                    Some(right) => {
+                        let value = agg(left.value().clone(), right.value().clone())?;
+                        next.push(Self::node(left, right, value));
+                    }
+                    None => next.push(Self::unary(left)),
+                }
+            }
+            nodes = next;
+        }
+        Ok(nodes.pop().expect("non-empty level"))
     }
 }
 
@@ -105,21 +1011,36 @@ mod tests {
         x.saturating_add(y)
     }
 
-    // Collect leaf values left-to-right (preorder-ish but stable for comparing multisets)
-    fn collect_leaves<T: Copy>(t: &BinTree<T>, out: &mut Vec<T>) {
+    // Collect leaf values left-to-right (stable for comparing multisets)
+    fn collect_leaves<T: Copy>(t: &BinTree<T>) -> Vec<T> {
+        t.leaves().copied().collect()
+    }
+
+    // (leaf, number of two-child ancestors) for every leaf
+    fn binary_ancestors<T: Copy>(t: &BinTree<T>, count: usize, out: &mut Vec<(T, usize)>) {
         match t {
-            BinTree::Leaf(v) => out.push(*v),
-            BinTree::Node {
-                left,
-                right,
-                value: _,
-            } => {
-                collect_leaves(left, out);
-                collect_leaves(right, out);
+            BinTree::Leaf(v) => out.push((*v, count)),
+            BinTree::Node { left, right: Some(right), value: _, .. } => {
+                binary_ancestors(left, count + 1, out);
+                binary_ancestors(right, count + 1, out);
             }
+            BinTree::Node { left, right: None, value: _, .. } => binary_ancestors(left, count, out),
         }
     }
 
+    //          20
+    //         /  \
+    //       15    5
+    //      /  \    \
+    //     3    12   5
+    //    / \   / \   \
+    //   1   2 4   8   5
+    //
+    // The odd leaf 5 is carried up through two single-child nodes.
+    fn five_leaf_tree() -> BinTree<u32> {
+        BinTree::from_vec(vec![1u32, 2, 4, 8, 5], add)
+    }
+
     // -------------------------
     // Unit tests
     // -------------------------
@@ -152,8 +1073,7 @@ mod tests {
         let input = vec![5u32, 1, 5, 9, 1, 2];
         let t = BinTree::from_vec(input.clone(), add);
 
-        let mut got = Vec::new();
-        collect_leaves(&t, &mut got);
+        let got = collect_leaves(&t);
 
         let mut a = input;
         let mut b = got;
@@ -175,6 +1095,581 @@ mod tests {
         }
     }
 
+    #[test]
+    fn from_vec_accepts_capturing_closure() {
+        let mut calls = 0;
+        let t = BinTree::from_vec(vec![1u32, 2, 3, 4, 5], |a, b| {
+            calls += 1;
+            a + b
+        });
+        // every internal node aggregates exactly once
+        assert_eq!(calls, 4);
+        assert_eq!(*t.value(), 15);
+    }
+
+    #[test]
+    fn try_from_vec_matches_from_vec_when_agg_succeeds() {
+        let input = vec![3u32, 1, 4, 1, 5, 9, 2];
+        let t = BinTree::try_from_vec(input.clone(), |a, b| Ok::<_, ()>(add(a, b))).unwrap();
+        assert_eq!(t, BinTree::from_vec(input, add));
+    }
+
+    #[test]
+    fn try_from_vec_empty_is_error() {
+        let r = BinTree::try_from_vec(Vec::<u32>::new(), |a, b| Ok::<_, ()>(add(a, b)));
+        assert_eq!(r, Err(BuildError::Empty));
+    }
+
+    #[test]
+    fn try_from_vec_surfaces_failing_pair() {
+        let mut calls = Vec::new();
+        let r = BinTree::try_from_vec(vec![1u32, 2, 3, 4, 5, 6], |a, b| {
+            calls.push((a, b));
+            if (a, b) == (3, 4) { Err(format!("bad pair {} {}", a, b)) } else { Ok(a + b) }
+        });
+        assert_eq!(r, Err(BuildError::Aggregation("bad pair 3 4".to_string())));
+        // construction stopped at the failing pair
+        assert_eq!(calls, vec![(1, 2), (3, 4)]);
+    }
+
+    #[test]
+    fn from_iter_streaming_empty_is_error() {
+        let r = BinTree::from_iter_streaming(Vec::<Result<u32, ()>>::new(), add);
+        assert_eq!(r, Err(BuildError::Empty));
+    }
+
+    #[test]
+    fn from_iter_streaming_reports_source_error_index() {
+        let src = vec![Ok(1u32), Ok(2), Ok(3), Err("bad row"), Ok(5)];
+        let r = BinTree::from_iter_streaming(src, add);
+        assert_eq!(r, Err(BuildError::Source { index: 3, error: "bad row" }));
+    }
+
+    #[test]
+    fn from_iter_streaming_matches_from_vec_small() {
+        for n in 1..=33u32 {
+            let input: Vec<u32> = (0..n).collect();
+            let streamed =
+                BinTree::from_iter_streaming(input.iter().map(|&x| Ok::<_, ()>(x)), add).unwrap();
+            assert_eq!(streamed, BinTree::from_vec(input, add), "n = {}", n);
+        }
+    }
+
+    // Run with `cargo test -- --ignored` and watch RSS: only the final tree and
+    // one pending node per level should be resident.
+    #[test]
+    #[ignore]
+    fn from_iter_streaming_large_n() {
+        let n = 1u32 << 22;
+        let t = BinTree::from_iter_streaming((0..n).map(Ok::<_, ()>), add).unwrap();
+        assert_eq!(t.leaf_count(), n as usize);
+        assert_eq!(t.height(), 23);
+    }
+
+    #[test]
+    fn leaves_are_left_to_right() {
+        let t = five_leaf_tree();
+        assert_eq!(collect_leaves(&t), vec![1, 2, 4, 8, 5]);
+    }
+
+    #[test]
+    fn preorder_and_postorder_on_five_leaves() {
+        let t = five_leaf_tree();
+        let pre: Vec<u32> = t.iter_preorder().copied().collect();
+        let post: Vec<u32> = t.iter_postorder().copied().collect();
+        assert_eq!(pre, vec![20, 15, 3, 1, 2, 12, 4, 8, 5, 5, 5]);
+        assert_eq!(post, vec![1, 2, 3, 4, 8, 12, 15, 5, 5, 5, 20]);
+    }
+
+    #[test]
+    fn iterators_on_single_leaf() {
+        let t = BinTree::leaf(7u32);
+        assert_eq!(t.leaves().collect::<Vec<_>>(), vec![&7]);
+        assert_eq!(t.iter_preorder().collect::<Vec<_>>(), vec![&7]);
+        assert_eq!(t.iter_postorder().collect::<Vec<_>>(), vec![&7]);
+    }
+
+    #[test]
+    fn map_preserves_shape_and_positions() {
+        let t = five_leaf_tree();
+        let m = t.map(|v| format!("{:02x}", v));
+        assert_eq!(m.height(), t.height());
+        assert_eq!(m.leaf_count(), t.leaf_count());
+        for (v, s) in t.iter_preorder().zip(m.iter_preorder()) {
+            assert_eq!(&format!("{:02x}", v), s);
+        }
+        assert_eq!(m.value(), "14");
+    }
+
+    #[test]
+    fn into_map_matches_map() {
+        let t = five_leaf_tree();
+        let by_ref = t.map(|v| u64::from(*v) * 3);
+        let by_val = t.into_map(|v| u64::from(v) * 3);
+        assert_eq!(by_ref, by_val);
+    }
+
+    #[test]
+    fn fold_sum_matches_flat_sum() {
+        let input = vec![1u64, 2, 4, 8, 5, 7];
+        let t = BinTree::from_vec(input.clone(), |a, b| a.max(b));
+        let leaf_sum = t.fold(|v| *v, |l, r, _| l + r.unwrap_or(0));
+        assert_eq!(leaf_sum, input.iter().sum::<u64>());
+    }
+
+    #[test]
+    fn fold_sees_node_values() {
+        let t = five_leaf_tree();
+        // sum of every node value, internal ones included
+        let all = t.fold(|v| *v, |l, r, v| l + r.unwrap_or(0) + v);
+        assert_eq!(all, t.iter_preorder().sum::<u32>());
+    }
+
+    #[test]
+    fn indexed_construction_remembers_input_positions() {
+        let origins = BinTree::from_vec_indexed(vec![7u32, 3, 9], add);
+        assert_eq!(origins.positions, vec![0, 1, 2]);
+        assert_eq!(origins.leaf_index_of(&9), Some(2));
+        assert_eq!(origins.leaf_index_of(&19), None);
+
+        let xs = vec![40u32, 10, 30, 20, 50];
+        let sorted = BinTree::try_from_vec_sorted_indexed(xs.clone(), |x| *x, |a, b| Ok::<_, Infallible>(add(a, b))).unwrap();
+        assert_eq!(collect_leaves(&sorted.tree), vec![10, 20, 30, 40, 50]);
+        assert_eq!(sorted.positions, vec![1, 3, 2, 0, 4]);
+        for (i, x) in xs.iter().enumerate() {
+            assert_eq!(sorted.leaf_index_of(x), Some(i));
+        }
+        assert_eq!(sorted.tree, BinTree::from_vec_sorted(xs, |x| *x, add));
+    }
+
+    #[test]
+    fn from_levels_rejects_impossible_sizes() {
+        assert_eq!(BinTree::<u32>::from_levels(vec![]), Err(LevelsError::Empty));
+        assert_eq!(BinTree::<u32>::from_levels(vec![vec![]]), Err(LevelsError::Empty));
+        assert_eq!(BinTree::from_levels(vec![vec![7u32]]), Ok(BinTree::leaf(7)));
+        // five leaves need levels of 1, 2, 3 and 5
+        let err = BinTree::from_levels(vec![vec![0u32], vec![0, 0], vec![0, 0], vec![1, 2, 3, 4, 5]]).unwrap_err();
+        assert_eq!(err, LevelsError::LevelSize { level: 2, expected: 3, got: 2 });
+        let err = BinTree::from_levels(vec![vec![0u32, 0], vec![1, 2]]).unwrap_err();
+        assert_eq!(err.to_string(), "level 0 has 2 nodes, expected 1");
+        let err = BinTree::from_levels(vec![vec![3u32], vec![3], vec![1, 2]]).unwrap_err();
+        assert_eq!(err, LevelsError::LevelSize { level: 1, expected: 2, got: 1 });
+
+        // sizes alone are checked; a bad value is left to `validate`
+        let t = BinTree::from_levels(vec![vec![9u32], vec![1, 2]]).unwrap();
+        assert_eq!(t.validate(|a, b| a + b), Err(ValidationError { depth: 0, position: 0 }));
+    }
+
+    #[test]
+    fn zip_pairs_same_shape_trees() {
+        let keys = BinTree::from_vec(vec![1u32, 2, 3, 4, 5], add);
+        let tags = keys.map(|k| k.to_string());
+        let zipped = keys.zip(&tags).unwrap();
+        assert!(zipped.iter_preorder().all(|(k, t)| k.to_string() == *t));
+        assert_eq!(zipped.height(), keys.height());
+        assert_eq!(zipped.unzip(), (keys.clone(), tags));
+
+        // a sixth leaf turns the unary node above the fifth into a pair
+        let six = BinTree::from_vec(vec![1u32, 2, 3, 4, 5, 6], add);
+        assert!(keys.zip(&six).is_none());
+        assert!(keys.zip(&BinTree::leaf(0u8)).is_none());
+        assert_eq!(BinTree::leaf(1u8).zip(&BinTree::leaf('a')), Some(BinTree::leaf((1, 'a'))));
+    }
+
+    // height/leaf_count are cached, and the fold and the drop run on an
+    // explicit stack, so a list-shaped tree far deeper than the call stack
+    // allows is fine.
+    #[test]
+    fn deep_left_leaning_tree_does_not_overflow() {
+        let depth = 100_000u32;
+        let mut t = BinTree::leaf(0u32);
+        for i in 1..=depth {
+            t = BinTree::node(t, BinTree::leaf(i), i);
+        }
+        assert_eq!(t.height(), depth as usize + 1);
+        assert_eq!(t.leaf_count(), depth as usize + 1);
+        drop(t);
+    }
+
+    #[derive(Debug)]
+    struct Counted {
+        v: u32,
+        clones: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.clones.set(self.clones.get() + 1);
+            Counted { v: self.v, clones: self.clones.clone() }
+        }
+    }
+
+    #[test]
+    fn from_vec_clones_are_linear() {
+        for n in [1usize, 2, 5, 64, 1000] {
+            let clones = std::rc::Rc::new(std::cell::Cell::new(0));
+            let input: Vec<Counted> = (0..n as u32)
+                .map(|v| Counted { v, clones: clones.clone() })
+                .collect();
+            let t = BinTree::from_vec(input, |a, b| Counted { v: a.v + b.v, clones: a.clones });
+            assert_eq!(t.leaf_count(), n);
+            // the two values passed to each of the n - 1 aggregations, plus
+            // one per single-child node
+            let unary = t.iter_preorder().count() - (2 * n - 1);
+            assert_eq!(clones.get(), 2 * (n - 1) + unary, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn levels_of_five_leaves() {
+        let t = BinTree::node(
+            BinTree::node(
+                BinTree::node(BinTree::leaf(1u32), BinTree::leaf(2), 3),
+                BinTree::node(BinTree::leaf(4), BinTree::leaf(8), 12),
+                15,
+            ),
+            BinTree::unary(BinTree::unary(BinTree::leaf(5))),
+            20,
+        );
+        assert_eq!(t, five_leaf_tree());
+        assert_eq!(
+            t.levels(),
+            vec![vec![&20], vec![&15, &5], vec![&3, &12, &5], vec![&1, &2, &4, &8, &5]]
+        );
+        assert_eq!(t.nodes_at_depth(1), vec![&15, &5]);
+        assert_eq!(t.nodes_at_depth(3), vec![&1, &2, &4, &8, &5]);
+        assert!(t.nodes_at_depth(4).is_empty());
+
+        // a hand-built lopsided tree can still hold a leaf above the bottom level
+        let lopsided = BinTree::node(BinTree::node(BinTree::leaf(1u32), BinTree::leaf(2), 3), BinTree::leaf(4), 7);
+        assert_eq!(lopsided.levels(), vec![vec![&7], vec![&3, &4], vec![&1, &2]]);
+    }
+
+    #[test]
+    fn levels_of_eight_leaves() {
+        let t = BinTree::from_vec((1..=8u32).collect(), add);
+        let widths: Vec<usize> = t.levels().iter().map(|l| l.len()).collect();
+        assert_eq!(widths, vec![1, 2, 4, 8]);
+        assert_eq!(t.nodes_at_depth(0), vec![&36]);
+        assert_eq!(t.nodes_at_depth(2), vec![&3, &7, &11, &15]);
+        assert_eq!(t.nodes_at_depth(3), (1..=8u32).collect::<Vec<_>>().iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn contains_leaf_hits_and_misses() {
+        let t = five_leaf_tree();
+        assert!(t.contains_leaf(&8));
+        assert!(t.contains_leaf(&5));
+        // 12 and 20 are internal values, not leaves
+        assert!(!t.contains_leaf(&12));
+        assert!(!t.contains_leaf(&20));
+        assert!(!t.contains_leaf(&99));
+    }
+
+    #[test]
+    fn find_searches_all_nodes() {
+        let t = five_leaf_tree();
+        assert_eq!(t.find(|v| *v == 20), Some(&20));
+        assert_eq!(t.find(|v| *v == 8), Some(&8));
+        assert_eq!(t.find(|v| *v > 100), None);
+        // preorder: the first even value is the root
+        assert_eq!(t.find(|v| v % 2 == 0), Some(&20));
+        assert_eq!(t.find(|v| v % 3 == 0), Some(&15));
+    }
+
+    #[test]
+    fn depths_on_five_leaves() {
+        let t = five_leaf_tree();
+        assert_eq!(t.depth_of(&20), Some(0));
+        assert_eq!(t.depth_of(&12), Some(2));
+        assert_eq!(t.depth_of(&4), Some(3));
+        // the first node carrying 5 is the single-child node at depth 1
+        assert_eq!(t.depth_of(&5), Some(1));
+        assert_eq!(t.depth_of(&99), None);
+        assert_eq!(t.leaf_depths(), vec![(3, &1), (3, &2), (3, &4), (3, &8), (3, &5)]);
+    }
+
+    fn render_n(n: u32) -> String {
+        BinTree::from_vec((1..=n).collect(), add).render(|v| v.to_string())
+    }
+
+    #[test]
+    fn render_one_leaf() {
+        assert_eq!(render_n(1), "1 (leaf)\n");
+    }
+
+    #[test]
+    fn render_two_leaves() {
+        assert_eq!(render_n(2), "\
+3
+├── 1 (leaf)
+└── 2 (leaf)
+");
+    }
+
+    #[test]
+    fn render_three_leaves() {
+        assert_eq!(render_n(3), "\
+6
+├── 3
+│   ├── 1 (leaf)
+│   └── 2 (leaf)
+└── 3
+    └── 3 (leaf)
+");
+    }
+
+    #[test]
+    fn render_four_leaves() {
+        assert_eq!(render_n(4), "\
+10
+├── 3
+│   ├── 1 (leaf)
+│   └── 2 (leaf)
+└── 7
+    ├── 3 (leaf)
+    └── 4 (leaf)
+");
+    }
+
+    #[test]
+    fn merkle_path_on_five_leaves() {
+        let t = five_leaf_tree();
+        assert_eq!(t.merkle_path(&1), Some(vec![2, 12, 5]));
+        assert_eq!(t.merkle_path(&8), Some(vec![4, 3, 5]));
+        // single-child ancestors contribute no sibling
+        assert_eq!(t.merkle_path(&5), Some(vec![15]));
+        // internal values are not leaves
+        assert_eq!(t.merkle_path(&12), None);
+        assert_eq!(t.merkle_path(&99), None);
+    }
+
+    #[test]
+    fn odd_leftover_becomes_single_child_node() {
+        let t = BinTree::from_vec(vec![1u32, 2, 3], add);
+        let expected = BinTree::node(
+            BinTree::node(BinTree::leaf(1), BinTree::leaf(2), 3),
+            BinTree::unary(BinTree::leaf(3)),
+            6,
+        );
+        assert_eq!(t, expected);
+        // every leaf now sits on the bottom level
+        assert!(t.leaf_depths().iter().all(|(d, _)| *d == t.height() - 1));
+    }
+
+    #[test]
+    fn insert_leaf_fills_single_child_slot() {
+        let mut t = five_leaf_tree();
+        let untouched: *const BinTree<u32> = match &t {
+            BinTree::Node { left, .. } => &**left,
+            BinTree::Leaf(_) => panic!("expected Node"),
+        };
+        let before = match &t {
+            BinTree::Node { left, .. } => (**left).clone(),
+            BinTree::Leaf(_) => panic!("expected Node"),
+        };
+
+        t.insert_leaf(7, |a, b| a + b);
+        assert_eq!(t.leaf_count(), 6);
+        assert_eq!(collect_leaves(&t), vec![1, 2, 4, 8, 5, 7]);
+
+        // the depth-1 single-child node took the new leaf as its right child
+        let expected = BinTree::node(
+            before.clone(),
+            BinTree::node(BinTree::unary(BinTree::leaf(5)), BinTree::leaf(7), 12),
+            27,
+        );
+        assert_eq!(t, expected);
+        let BinTree::Node { left, .. } = &t else {
+            panic!("expected Node");
+        };
+        assert!(std::ptr::eq(&**left, untouched));
+        assert_eq!(**left, before);
+    }
+
+    #[test]
+    fn insert_leaf_splits_a_leaf_when_no_slot_is_free() {
+        let mut t = BinTree::leaf(1u32);
+        t.insert_leaf(2, |a, b| a + b);
+        assert_eq!(t, BinTree::node(BinTree::leaf(1), BinTree::leaf(2), 3));
+
+        let mut t = BinTree::from_vec(vec![1u32, 2, 4, 8], add);
+        let right: *const BinTree<u32> = match &t {
+            BinTree::Node { right: Some(right), .. } => &**right,
+            _ => panic!("expected two-child Node"),
+        };
+        t.insert_leaf(16, |a, b| a + b);
+        assert_eq!(collect_leaves(&t), vec![1, 16, 2, 4, 8]);
+        assert_eq!(*t.value(), 31);
+        assert_eq!(t.merkle_path(&16), Some(vec![1, 2, 12]));
+        let BinTree::Node { right: Some(after), .. } = &t else {
+            panic!("expected two-child Node");
+        };
+        assert!(std::ptr::eq(&**after, right));
+    }
+
+    #[test]
+    fn remove_leaf_promotes_sibling() {
+        let mut t = five_leaf_tree();
+        assert!(t.remove_leaf(&2, |a, b| a + b));
+        assert_eq!(t.leaf_count(), 4);
+        assert!(!t.contains_leaf(&2));
+        let expected = BinTree::node(
+            BinTree::node(BinTree::leaf(1), BinTree::node(BinTree::leaf(4), BinTree::leaf(8), 12), 13),
+            BinTree::unary(BinTree::unary(BinTree::leaf(5))),
+            18,
+        );
+        assert_eq!(t, expected);
+    }
+
+    #[test]
+    fn remove_leaf_drops_single_child_chain() {
+        let mut t = five_leaf_tree();
+        assert!(t.remove_leaf(&5, |a, b| a + b));
+        assert_eq!(t, BinTree::from_vec(vec![1, 2, 4, 8], add));
+    }
+
+    #[test]
+    fn remove_leaf_misses_and_last_leaf() {
+        let mut t = five_leaf_tree();
+        // internal values are not leaves
+        assert!(!t.remove_leaf(&12, |a, b| a + b));
+        assert!(!t.remove_leaf(&99, |a, b| a + b));
+        assert_eq!(t, five_leaf_tree());
+
+        let mut lone = BinTree::leaf(7u32);
+        assert!(!lone.remove_leaf(&7, |a, b| a + b));
+        assert_eq!(lone, BinTree::leaf(7));
+
+        let mut chain = BinTree::unary(BinTree::leaf(7u32));
+        assert!(!chain.remove_leaf(&7, |a, b| a + b));
+        assert_eq!(chain.leaf_count(), 1);
+    }
+
+    #[test]
+    fn lopsided_tree_has_a_different_shape() {
+        let balanced = BinTree::from_vec(vec![1u32, 2, 3, 4], add);
+        let lopsided = BinTree::node(
+            BinTree::node(BinTree::node(BinTree::leaf(1u32), BinTree::leaf(2), 3), BinTree::leaf(3), 6),
+            BinTree::leaf(4),
+            10,
+        );
+        assert_eq!(balanced.leaf_count(), lopsided.leaf_count());
+        assert!(!balanced.same_shape(&lopsided));
+        assert_ne!(balanced.shape(), lopsided.shape());
+
+        // values of another type do not matter
+        let labels = balanced.map(|v| format!("k{}", v));
+        assert!(balanced.same_shape(&labels));
+        assert_eq!(labels.shape(), balanced.shape());
+
+        // a single-child node is not a leaf
+        assert!(!BinTree::unary(BinTree::leaf(1u32)).same_shape(&BinTree::leaf(1u32)));
+    }
+
+    #[test]
+    fn validate_pinpoints_corrupted_node() {
+        let t = BinTree::from_vec((1u32..=8).collect(), add);
+        assert_eq!(t.validate(|a, b| a + b), Ok(()));
+        assert_eq!(five_leaf_tree().validate(|a, b| a + b), Ok(()));
+
+        // overwrite the second internal node at depth 2, (3, 4) -> 7; its
+        // parent no longer matches either, but the deeper node is reported
+        let mut seen = 0;
+        let corrupted = t.map(|v| {
+            seen += 1;
+            if seen == 6 { 99 } else { *v }
+        });
+        assert_eq!(corrupted.levels()[2], vec![&3, &99, &11, &15]);
+        let err = corrupted.validate(|a, b| a + b).unwrap_err();
+        assert_eq!(err, ValidationError { depth: 2, position: 1 });
+        assert_eq!(err.to_string(), "node at depth 2 position 1 does not match its children");
+
+        // a single-child node must pass its child's value through
+        let bad = BinTree::from_parts(BinTree::leaf(5u32), None, 6);
+        assert_eq!(bad.validate(|a, b| a + b), Err(ValidationError { depth: 0, position: 0 }));
+    }
+
+    #[test]
+    fn shaped_heights() {
+        for n in [1u32, 2, 5, 8, 13] {
+            let input: Vec<u32> = (1..=n).collect();
+            let balanced = BinTree::from_vec_shaped(input.clone(), TreeShape::Balanced, add);
+            let list = BinTree::from_vec_shaped(input.clone(), TreeShape::LeftList, add);
+            assert_eq!(balanced, BinTree::from_vec(input.clone(), add));
+            assert_eq!(balanced.height(), (n as usize).next_power_of_two().trailing_zeros() as usize + 1);
+            assert_eq!(list.height(), n as usize);
+            assert_eq!(collect_leaves(&list), input);
+            assert_eq!(*list.value(), *balanced.value());
+        }
+    }
+
+    #[test]
+    fn left_list_nests_each_new_leaf() {
+        let t = BinTree::from_vec_shaped(vec![1u32, 2, 4], TreeShape::LeftList, add);
+        let expected = BinTree::node(BinTree::node(BinTree::leaf(1), BinTree::leaf(2), 3), BinTree::leaf(4), 7);
+        assert_eq!(t, expected);
+    }
+
+    #[test]
+    fn merkle_path_single_leaf_is_empty() {
+        assert_eq!(BinTree::leaf(7u32).merkle_path(&7), Some(vec![]));
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde_round_trip {
+        use super::*;
+
+        fn trees() -> Vec<BinTree<u32>> {
+            [1u32, 2, 5, 16]
+                .iter()
+                .map(|&n| BinTree::from_vec((0..n).collect(), add))
+                .collect()
+        }
+
+        #[test]
+        fn json_round_trip() {
+            for t in trees() {
+                let json = serde_json::to_string(&t).unwrap();
+                let back: BinTree<u32> = serde_json::from_str(&json).unwrap();
+                assert_eq!(back, t);
+                assert_eq!(back.height(), t.height());
+                assert_eq!(back.leaf_count(), t.leaf_count());
+            }
+        }
+
+        #[test]
+        fn bincode_round_trip() {
+            for t in trees() {
+                let bytes = bincode::serialize(&t).unwrap();
+                let back: BinTree<u32> = bincode::deserialize(&bytes).unwrap();
+                assert_eq!(back, t);
+                assert_eq!(back.height(), t.height());
+                assert_eq!(back.leaf_count(), t.leaf_count());
+            }
+        }
+
+        #[test]
+        fn meta_is_recomputed_not_read() {
+            let t = BinTree::from_vec((0u32..5).collect(), add);
+            let json = serde_json::to_string(&t).unwrap();
+            assert!(!json.contains("meta"));
+            // a claimed height is ignored, not trusted
+            let crafted = r#"{"node":{"left":{"leaf":1},"right":{"leaf":2},"value":3,"meta":{"height":0,"leaf_count":9}}}"#;
+            let back: BinTree<u32> = serde_json::from_str(crafted).unwrap();
+            assert_eq!((back.height(), back.leaf_count()), (2, 2));
+        }
+
+        #[test]
+        fn json_is_tagged() {
+            let t = BinTree::from_vec(vec![1u32, 2], add);
+            assert_eq!(
+                serde_json::to_string(&t).unwrap(),
+                r#"{"node":{"left":{"leaf":1},"right":{"leaf":2},"value":3}}"#
+            );
+        }
+    }
+
     // -------------------------
     // Property-based tests
     // -------------------------
@@ -193,8 +1688,7 @@ mod tests {
         fn prop_leaf_multiset_preserved(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
             let t = BinTree::from_vec(xs.clone(), add);
 
-            let mut got = Vec::new();
-            collect_leaves(&t, &mut got);
+            let got = collect_leaves(&t);
 
             let mut a = xs;
             let mut b = got;
@@ -226,5 +1720,165 @@ mod tests {
             prop_assert_eq!(t.height(), expected);
             prop_assert_eq!(t.leaf_count(), n);
         }
+
+        // Property 5: iterators agree with the counting walks.
+        #[test]
+        fn prop_iterator_counts(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let n = xs.len();
+            let t = BinTree::from_vec(xs, add);
+            prop_assert_eq!(t.leaves().count(), t.leaf_count());
+            // n - 1 two-child nodes, plus however many single-child ones
+            prop_assert!(t.iter_preorder().count() >= 2 * n - 1);
+            prop_assert_eq!(t.iter_postorder().count(), t.iter_preorder().count());
+            prop_assert_eq!(t.iter_postorder().last(), Some(t.value()));
+        }
+
+        // Property 6: every leaf's merkle path has one entry per two-child
+        // ancestor. Distinct values so each leaf is found at its own position.
+        #[test]
+        fn prop_merkle_path_len_is_depth(n in 1u32..256) {
+            let t = BinTree::from_vec((0..n).collect(), |a, b| a.max(b) + n);
+            let mut expected = Vec::new();
+            binary_ancestors(&t, 0, &mut expected);
+            for (leaf, count) in expected {
+                prop_assert_eq!(t.merkle_path(&leaf).map(|p| p.len()), Some(count));
+            }
+            if n.is_power_of_two() {
+                for (depth, leaf) in t.leaf_depths() {
+                    prop_assert_eq!(t.merkle_path(leaf).map(|p| p.len()), Some(depth));
+                }
+            }
+        }
+
+        // Property 7: streaming construction builds the identical tree.
+        #[test]
+        fn prop_streaming_matches_from_vec(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let streamed = BinTree::from_iter_streaming(xs.iter().map(|&x| Ok::<_, ()>(x)), add).unwrap();
+            prop_assert_eq!(streamed, BinTree::from_vec(xs, add));
+        }
+
+        // Property 8: every input is found as a leaf.
+        #[test]
+        fn prop_inputs_are_leaves(xs in proptest::collection::vec(any::<u32>(), 1..256)) {
+            let t = BinTree::from_vec(xs.clone(), add);
+            for x in &xs {
+                prop_assert!(t.contains_leaf(x));
+            }
+        }
+
+        // Property 9: the deepest leaf determines the height, and
+        // `deepest_leaf` finds the first one.
+        #[test]
+        fn prop_max_leaf_depth_is_height(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let t = BinTree::from_vec(xs, add);
+            let depths = t.leaf_depths();
+            let deepest = depths.iter().map(|&(d, _)| d).max().unwrap();
+            prop_assert_eq!(deepest + 1, t.height());
+            let first = depths.iter().find(|&&(d, _)| d == deepest).unwrap().1;
+            prop_assert_eq!(t.deepest_leaf(), first);
+            let indexed = crate::indexed::IndexedTree::from_tree(&t);
+            prop_assert_eq!(&indexed.get(indexed.deepest_leaf()).value, first);
+        }
+
+        // Property 10: inserting one leaf gives the same leaves as building
+        // with it from the start, and keeps every node value consistent.
+        #[test]
+        fn prop_insert_leaf_matches_rebuild(xs in proptest::collection::vec(any::<u32>(), 1..256), x in any::<u32>()) {
+            let mut t = BinTree::from_vec(xs.clone(), add);
+            t.insert_leaf(x, |a, b| add(*a, *b));
+            let mut all = xs;
+            all.push(x);
+            let rebuilt = BinTree::from_vec(all.clone(), add);
+            prop_assert_eq!(t.leaf_count(), all.len());
+
+            let mut got = collect_leaves(&t);
+            let mut want = collect_leaves(&rebuilt);
+            got.sort_unstable();
+            want.sort_unstable();
+            prop_assert_eq!(got, want);
+
+            let recomputed = t.fold(|v| *v, |l, r, _| match r {
+                Some(r) => add(l, r),
+                None => l,
+            });
+            prop_assert_eq!(recomputed, *t.value());
+        }
+
+        // Property 11: removing a leaf drops exactly that leaf and keeps every
+        // node value consistent.
+        #[test]
+        fn prop_remove_leaf(xs in proptest::collection::vec(any::<u32>(), 2..256), pick in any::<prop::sample::Index>()) {
+            let target = xs[pick.index(xs.len())];
+            let mut t = BinTree::from_vec(xs.clone(), add);
+            prop_assert!(t.remove_leaf(&target, |a, b| add(*a, *b)));
+            prop_assert_eq!(t.leaf_count(), xs.len() - 1);
+
+            let mut want = xs;
+            let first = want.iter().position(|x| *x == target).unwrap();
+            want.remove(first);
+            prop_assert_eq!(collect_leaves(&t), want);
+
+            let recomputed = t.fold(|v| *v, |l, r, _| match r {
+                Some(r) => add(l, r),
+                None => l,
+            });
+            prop_assert_eq!(recomputed, *t.value());
+        }
+
+        // Property 12: the shape only depends on the number of leaves.
+        #[test]
+        fn prop_shape_depends_only_on_len(pairs in proptest::collection::vec((any::<u32>(), any::<u64>()), 1..256)) {
+            let (xs, ys): (Vec<u32>, Vec<u64>) = pairs.into_iter().unzip();
+            let a = BinTree::from_vec(xs, add);
+            let b = BinTree::from_vec(ys, |x, y| x ^ y);
+            prop_assert!(a.same_shape(&b));
+            prop_assert!(b.same_shape(&a));
+            prop_assert_eq!(a.shape(), b.shape());
+        }
+
+        // Property 13: sorted construction ignores the input order.
+        #[test]
+        fn prop_sorted_is_order_independent(
+            (xs, shuffled) in proptest::collection::vec(any::<u32>(), 1..256)
+                .prop_flat_map(|xs| (Just(xs.clone()), Just(xs).prop_shuffle()))
+        ) {
+            let a = BinTree::from_vec_sorted(xs, |x| *x, add);
+            let b = BinTree::from_vec_sorted(shuffled, |x| *x, add);
+            prop_assert_eq!(a, b);
+        }
+
+        // Property 14: the cached height and leaf count match a fresh walk
+        // after any sequence of inserts and removes.
+        #[test]
+        fn prop_cached_meta_survives_edits(
+            xs in proptest::collection::vec(any::<u32>(), 1..64),
+            edits in proptest::collection::vec((any::<bool>(), any::<u32>()), 0..64),
+        ) {
+            let walked = |t: &BinTree<u32>| {
+                t.fold(|_| (1, 1), |(lh, ll), r, _| match r {
+                    Some((rh, rl)) => (1 + lh.max(rh), ll + rl),
+                    None => (1 + lh, ll),
+                })
+            };
+            let mut t = BinTree::from_vec(xs, add);
+            prop_assert_eq!((t.height(), t.leaf_count()), walked(&t));
+            for (insert, x) in edits {
+                if insert || t.leaf_count() == 1 {
+                    t.insert_leaf(x, |a, b| add(*a, *b));
+                } else {
+                    let target = collect_leaves(&t)[x as usize % t.leaf_count()];
+                    prop_assert!(t.remove_leaf(&target, |a, b| add(*a, *b)));
+                }
+                prop_assert_eq!((t.height(), t.leaf_count()), walked(&t));
+            }
+        }
+
+        // Property 15: a tree rebuilds from its levels unchanged.
+        #[test]
+        fn prop_from_levels_round_trips(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let t = BinTree::from_vec(xs, add);
+            let levels = t.levels().into_iter().map(|level| level.into_iter().copied().collect()).collect();
+            prop_assert_eq!(BinTree::from_levels(levels), Ok(t));
+        }
     }
 }
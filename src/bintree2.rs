@@ -0,0 +1,253 @@
+//! A binary tree whose leaves and internal nodes hold different types, e.g.
+//! a signer's key at a leaf and an aggregate at a node. `BinTree<T>` is the
+//! `L = N` case and converts to and from `BinTree2<T, T>`.
+//!
+//! `from_vec` carries an odd subtree up a level as it is rather than
+//! wrapping it in a single-child node, as `BinTree::from_vec` does, since
+//! the wrapper would need a node value for what may be a leaf. Leaf order,
+//! height, leaf count and the root value come out the same either way.
+
+use crate::bintree::{BinTree, Meta};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinTree2<L, N> {
+    Leaf(L),
+    /// An internal node; `right` is `None` only in trees converted from a
+    /// `BinTree` with single-child nodes.
+    Node {
+        left: Box<BinTree2<L, N>>,
+        right: Option<Box<BinTree2<L, N>>>,
+        value: N,
+        meta: Meta,
+    },
+}
+
+/// A borrowed node value, whichever kind of node it is.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NodeRef<'a, L, N> {
+    Leaf(&'a L),
+    Node(&'a N),
+}
+
+impl<L, N> Clone for NodeRef<'_, L, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<L, N> Copy for NodeRef<'_, L, N> {}
+
+impl<'a, T> NodeRef<'a, T, T> {
+    /// The value, when both kinds of node hold the same type.
+    pub fn value(self) -> &'a T {
+        match self {
+            NodeRef::Leaf(value) | NodeRef::Node(value) => value,
+        }
+    }
+}
+
+impl<L, N> BinTree2<L, N> {
+    pub fn leaf(value: L) -> Self {
+        BinTree2::Leaf(value)
+    }
+
+    pub fn node(left: Self, right: Self, value: N) -> Self {
+        let meta = Meta {
+            height: 1 + left.height().max(right.height()),
+            leaf_count: left.leaf_count() + right.leaf_count(),
+        };
+        BinTree2::Node {
+            left: Box::new(left),
+            right: Some(Box::new(right)),
+            value,
+            meta,
+        }
+    }
+
+    /// Builds the tree pairwise, level by level, with `agg` making each
+    /// node's value from its two children.
+    pub fn from_vec<F>(leaves: Vec<L>, mut agg: F) -> Self
+    where
+        F: FnMut(NodeRef<'_, L, N>, NodeRef<'_, L, N>) -> N,
+    {
+        assert!(!leaves.is_empty(), "cannot build tree from empty vec");
+        let mut nodes: Vec<Self> = leaves.into_iter().map(Self::leaf).collect();
+        while nodes.len() > 1 {
+            let mut next = Vec::with_capacity(nodes.len().div_ceil(2));
+            let mut level = nodes.into_iter();
+            while let Some(left) = level.next() {
+                match level.next() {
+                    Some(right) => {
+                        let value = agg(left.value(), right.value());
+                        next.push(Self::node(left, right, value));
+                    }
+                    None => next.push(left),
+                }
+            }
+            nodes = next;
+        }
+        nodes.pop().expect("non-empty level")
+    }
+
+    pub fn value(&self) -> NodeRef<'_, L, N> {
+        match self {
+            BinTree2::Leaf(value) => NodeRef::Leaf(value),
+            BinTree2::Node { value, .. } => NodeRef::Node(value),
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, Self::Leaf(_))
+    }
+
+    /// Levels in the tree, leaves included.
+    pub fn height(&self) -> usize {
+        match self {
+            BinTree2::Leaf(_) => 1,
+            BinTree2::Node { meta, .. } => meta.height,
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        match self {
+            BinTree2::Leaf(_) => 1,
+            BinTree2::Node { meta, .. } => meta.leaf_count,
+        }
+    }
+
+    /// Every value, parent before children, left before right.
+    pub fn iter_preorder(&self) -> impl Iterator<Item = NodeRef<'_, L, N>> {
+        self.iter_preorder_nodes().map(Self::value)
+    }
+
+    /// Leaf values, left to right.
+    pub fn leaves(&self) -> impl Iterator<Item = &L> {
+        self.iter_preorder_nodes().filter_map(|node| match node {
+            BinTree2::Leaf(value) => Some(value),
+            BinTree2::Node { .. } => None,
+        })
+    }
+
+    /// Internal node values in preorder.
+    pub fn node_values(&self) -> impl Iterator<Item = &N> {
+        self.iter_preorder_nodes().filter_map(|node| match node {
+            BinTree2::Leaf(_) => None,
+            BinTree2::Node { value, .. } => Some(value),
+        })
+    }
+
+    fn iter_preorder_nodes(&self) -> impl Iterator<Item = &BinTree2<L, N>> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            if let BinTree2::Node { left, right, .. } = node {
+                if let Some(right) = right {
+                    stack.push(right);
+                }
+                stack.push(left);
+            }
+            Some(node)
+        })
+    }
+}
+
+impl<T> From<BinTree<T>> for BinTree2<T, T> {
+    fn from(tree: BinTree<T>) -> Self {
+        match tree.into_parts() {
+            (value, None) => BinTree2::Leaf(value),
+            (value, Some((left, right, meta))) => BinTree2::Node {
+                left: Box::new((*left).into()),
+                right: right.map(|right| Box::new((*right).into())),
+                value,
+                meta,
+            },
+        }
+    }
+}
+
+impl<T> From<BinTree2<T, T>> for BinTree<T> {
+    fn from(tree: BinTree2<T, T>) -> Self {
+        match tree {
+            BinTree2::Leaf(value) => BinTree::Leaf(value),
+            BinTree2::Node { left, right, value, meta } => BinTree::Node {
+                left: Box::new((*left).into()),
+                right: right.map(|right| Box::new((*right).into())),
+                value,
+                meta,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn add(x: u32, y: u32) -> u32 {
+        x.wrapping_add(y)
+    }
+
+    fn add_refs(a: NodeRef<'_, u32, u32>, b: NodeRef<'_, u32, u32>) -> u32 {
+        add(*a.value(), *b.value())
+    }
+
+    #[test]
+    fn leaves_and_nodes_hold_different_types() {
+        // leaves are names, nodes count the leaves below them
+        let names = vec!["a", "b", "c", "d", "e"];
+        let tree: BinTree2<&str, usize> = BinTree2::from_vec(names.clone(), |a, b| {
+            let count = |r: NodeRef<'_, &str, usize>| match r {
+                NodeRef::Leaf(_) => 1,
+                NodeRef::Node(n) => *n,
+            };
+            count(a) + count(b)
+        });
+        assert_eq!(tree.value(), NodeRef::Node(&5));
+        assert_eq!(tree.leaves().copied().collect::<Vec<_>>(), names);
+        assert_eq!(tree.node_values().copied().collect::<Vec<_>>(), vec![5, 4, 2, 2]);
+        assert_eq!((tree.height(), tree.leaf_count()), (4, 5));
+        assert!(BinTree2::<u8, ()>::from_vec(vec![7], |_, _| ()).is_leaf());
+    }
+
+    #[test]
+    fn converts_from_bintree_with_single_child_nodes() {
+        let t = BinTree::from_vec(vec![1u32, 2, 3], add);
+        let t2 = BinTree2::from(t.clone());
+        assert_eq!(t2.iter_preorder().map(NodeRef::value).collect::<Vec<_>>(), t.iter_preorder().collect::<Vec<_>>());
+        assert_eq!(t2.height(), t.height());
+        assert_eq!(BinTree::from(t2), t);
+    }
+
+    proptest! {
+        // Property 1: with L = N, `from_vec` agrees with `BinTree::from_vec`
+        // on the root, the leaves, the height and the leaf count.
+        #[test]
+        fn prop_matches_bintree(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let t = BinTree::from_vec(xs.clone(), add);
+            let t2 = BinTree2::from_vec(xs, add_refs);
+            prop_assert_eq!(t2.value().value(), t.value());
+            prop_assert!(t2.leaves().eq(t.leaves()));
+            prop_assert_eq!(t2.height(), t.height());
+            prop_assert_eq!(t2.leaf_count(), t.leaf_count());
+        }
+
+        // Property 2: converting a `BinTree` there and back is lossless.
+        #[test]
+        fn prop_conversion_round_trips(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let t = BinTree::from_vec(xs, add);
+            let t2 = BinTree2::from(t.clone());
+            prop_assert_eq!(t2.leaf_count(), t.leaf_count());
+            prop_assert_eq!(BinTree::from(t2), t);
+        }
+
+        // Property 3: the cached counts match the iterators.
+        #[test]
+        fn prop_counts_match_iterators(xs in proptest::collection::vec(any::<u32>(), 1..512)) {
+            let t2 = BinTree2::from_vec(xs.clone(), add_refs);
+            prop_assert_eq!(t2.leaves().count(), xs.len());
+            prop_assert_eq!(t2.node_values().count(), xs.len() - 1);
+            prop_assert_eq!(t2.iter_preorder().count(), 2 * xs.len() - 1);
+        }
+    }
+}
@@ -0,0 +1,832 @@
+use ark_usecase::encoding::{POINT_LEN, point_from_bytes};
+use ark_usecase::keys::{KeyFileError, Keypair, parse_secret_keys};
+use ark_usecase::parse::{ParseError, hex_any_lenient, hex_exact};
+use ark_usecase::treefile::{TreeFileError, parse_key_tree};
+use clap::{ArgGroup, Parser, ValueEnum};
+use crypto_rs::secp256k1::Secp256k1Point;
+use nested_musig2::params::Params;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::safepath::{PathError, SafePath};
+
+/// Default upper bound on `--n`, since every signer gets a keypair and a
+/// tree leaf. `--max-n` moves it.
+pub const MAX_SIGNERS: u32 = 4096;
+
+pub const DEFAULT_MESSAGE: &[u8] = b"test tx message";
+
+#[derive(Debug)]
+pub enum ArgError {
+    InvalidCount(String),
+    /// No signers at all; the upper bound is `--max-n`'s, as `TooManySigners`.
+    CountOutOfRange(u32),
+    /// More signers than `--max-n` allows.
+    TooManySigners { n: u32, max: u32 },
+    Message(ParseError),
+    MessageFile { path: String, error: io::Error },
+    KeyFile { path: String, error: io::Error },
+    Keys { path: String, error: KeyFileError },
+    TreeFile { path: String, error: io::Error },
+    Tree { path: String, error: TreeFileError },
+    CountMismatch { n: u32, keys: usize },
+    MerkleRoot(ParseError),
+    /// Not a 33-byte compressed public key, as hex.
+    PublicKey(String),
+    /// `--phase round1` creates the keys, so it needs to know how many.
+    MissingCount,
+    /// The JSON report describes a signature under the root key.
+    JsonSubtree,
+    /// `--mode flat` has no tree, so tree-only flags do not apply.
+    FlatMode(&'static str),
+    /// Flags only the binary key tree supports, with `--arity` above 2.
+    WideTree(&'static str),
+    /// A flag this build left out, with the feature that brings it back.
+    MissingFeature { flag: &'static str, feature: &'static str },
+    Stdin(io::Error),
+    Path(PathError),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgError::InvalidCount(s) => write!(f, "{:?} is not a number of signers", s),
+            ArgError::CountOutOfRange(n) => {
+                write!(f, "number of signers must be at least 1, got {}", n)
+            }
+            ArgError::TooManySigners { n, max } => {
+                write!(f, "refusing to create {} signers, the limit is {}; pass --max-n {} to go ahead", n, max, n)
+            }
+            ArgError::Message(e) => write!(f, "invalid hex message: {}", e),
+            ArgError::MessageFile { path, error } | ArgError::KeyFile { path, error } | ArgError::TreeFile { path, error } => {
+                write!(f, "cannot read {}: {}", path, error)
+            }
+            ArgError::Keys { path, error } => write!(f, "{}: {}", path, error),
+            ArgError::Tree { path, error } => write!(f, "{}: {}", path, error),
+            ArgError::CountMismatch { n, keys } => {
+                write!(f, "--n {} does not match the {} keys in the key file", n, keys)
+            }
+            ArgError::MerkleRoot(e) => write!(f, "invalid taproot merkle root: {}", e),
+            ArgError::PublicKey(e) => write!(f, "invalid public key: {}", e),
+            ArgError::MissingCount => write!(f, "--phase round1 needs --n or --keys"),
+            ArgError::JsonSubtree => write!(f, "--output json cannot be combined with --sign-subtree"),
+            ArgError::FlatMode(flag) => write!(f, "--mode flat cannot be combined with {}", flag),
+            ArgError::WideTree(flag) => write!(f, "--arity above 2 cannot be combined with {}", flag),
+            ArgError::MissingFeature { flag, feature } => {
+                write!(f, "{} needs the `{}` feature, which this build does not have", flag, feature)
+            }
+            ArgError::Stdin(e) => write!(f, "cannot read from stdin: {}", e),
+            ArgError::Path(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+/// Message bytes given on the command line. A newtype so clap treats it as a
+/// single value rather than a list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message(pub Vec<u8>);
+
+/// `--message`: hex, or a file `Args::load` reads once `--workdir` is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageArg {
+    Hex(Message),
+    File(PathBuf),
+}
+
+/// One half of a signing run split across two invocations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Phase {
+    /// Create the keys and nonces and save them to the session file.
+    Round1,
+    /// Sign the message with the saved nonces, which are then discarded.
+    Round2,
+}
+
+/// Protocol parameters for `--params`. The key tree, both signing rounds and
+/// verification all use the one chosen here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ParamsPreset {
+    /// The nested MuSig2 defaults.
+    Default,
+}
+
+impl ParamsPreset {
+    pub fn params(self) -> Params {
+        match self {
+            ParamsPreset::Default => Params::default(),
+        }
+    }
+}
+
+/// How `--mode` signs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SigningMode {
+    /// Nested MuSig2 over the binary key tree.
+    Tree,
+    /// Plain n-of-n MuSig2 over all keys at once, for comparison.
+    Flat,
+}
+
+/// What `--output` prints.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Progress lines for a person, colored unless `NO_COLOR` is set.
+    Human,
+    /// A single JSON object describing the run, and nothing else.
+    Json,
+}
+
+/// Keypairs read from `--keys`.
+#[derive(Debug, Clone)]
+pub struct KeySet(pub Vec<Keypair>);
+
+/// Key tree levels read from `--tree-in`, root first, not yet checked.
+#[derive(Debug, Clone)]
+pub struct TreeLevels(pub Vec<Vec<Secp256k1Point>>);
+
+/// What `Args::load` read from the files named on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Files {
+    pub keys: Option<KeySet>,
+    pub tree: Option<TreeLevels>,
+    /// From `--msg-file` or `--message @path`.
+    pub message: Option<Message>,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[command(about = "Demonstration of converting any n of n musig to binary tree merkelized nested musig")]
+#[command(group(ArgGroup::new("msg").args(["message", "msg_hex", "msg_file"])))]
+pub struct Args {
+    /// Largest `--n` accepted without complaint.
+    #[arg(long, value_name = "N", default_value_t = MAX_SIGNERS)]
+    pub max_n: u32,
+    /// Number of signers. Optional with `--keys`, which implies it, for
+    /// `--phase round2`, which reads the signers from the session, and for
+    /// `--verify-proof` and `--gen-vectors`.
+    #[arg(long, value_parser = parse_count)]
+    #[cfg_attr(not(feature = "session"), arg(required_unless_present_any = ["keys", "verify_proof", "gen_vectors"]))]
+    #[cfg_attr(feature = "session", arg(required_unless_present_any = ["keys", "phase", "verify_proof", "gen_vectors"]))]
+    pub n: Option<u32>,
+    /// Sign with the secret keys in this file, one hex key per line.
+    #[arg(long, value_name = "PATH")]
+    pub keys: Option<PathBuf>,
+    /// Derive the signer keys from this seed instead of the OS RNG. Signing
+    /// nonces stay random.
+    #[arg(long, conflicts_with = "keys")]
+    pub seed: Option<u64>,
+    /// Write the freshly generated secret keys to this file.
+    #[arg(long, conflicts_with = "keys")]
+    pub export_keys: Option<PathBuf>,
+    /// Message to sign: hex, or `@path` to sign a file's raw bytes.
+    #[arg(long, value_parser = parse_message)]
+    pub message: Option<MessageArg>,
+    /// Message to sign, as hex. Any length, including empty.
+    #[arg(long, value_parser = parse_hex_message)]
+    pub msg_hex: Option<Message>,
+    /// File whose raw bytes are the message to sign.
+    #[arg(long, value_name = "PATH")]
+    pub msg_file: Option<PathBuf>,
+    /// Also write the raw signature bytes to this file.
+    #[arg(long)]
+    pub sig_out: Option<PathBuf>,
+    /// Sign under the key tree in this file, one level per line as written
+    /// by `--tree-out`, instead of aggregating the keys locally. Every
+    /// aggregate in it is checked first.
+    #[arg(long, value_name = "PATH", conflicts_with = "timings")]
+    pub tree_in: Option<PathBuf>,
+    /// Write the key tree to this file, for `--tree-in`.
+    #[arg(long, value_name = "PATH", conflicts_with = "timings")]
+    pub tree_out: Option<PathBuf>,
+    /// Print the key tree.
+    #[arg(long)]
+    pub show_tree: bool,
+    /// Check every aggregate key in the tree after building it.
+    #[arg(long)]
+    pub paranoid: bool,
+    /// Script tree merkle root (32 bytes hex) for the taproot output key.
+    /// Without it the output key commits to no scripts.
+    #[arg(long, value_parser = parse_merkle_root)]
+    pub taproot_merkle_root: Option<[u8; 32]>,
+    /// Write an inclusion proof for the leaf with this public key (33 bytes
+    /// hex) to `--proof-out`.
+    #[arg(long, value_name = "PUBKEY_HEX", value_parser = parse_pubkey, requires = "proof_out", conflicts_with = "timings")]
+    pub export_proof: Option<Secp256k1Point>,
+    /// Where `--export-proof` writes the proof.
+    #[arg(long, requires = "export_proof")]
+    pub proof_out: Option<PathBuf>,
+    /// Check the inclusion proof in this file instead of signing.
+    #[arg(long, value_name = "PATH")]
+    pub verify_proof: Option<PathBuf>,
+    /// Root key (33 bytes hex) the proof in `--verify-proof` must lead to.
+    /// Without it the root key stored in the proof is used.
+    #[arg(long, value_name = "PUBKEY_HEX", value_parser = parse_pubkey, requires = "verify_proof")]
+    pub proof_root: Option<Secp256k1Point>,
+    /// Write known-answer test vectors to this file instead of signing once:
+    /// a case per usual signer count, or just `--n`, keys from `--seed`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "seed",
+        conflicts_with_all = [
+            "verify_proof", "export_keys", "sig_out", "params", "timings", "simulate_network", "progress",
+            "sign_subtree", "show_tree", "tree_in", "tree_out", "export_proof", "audit",
+        ]
+    )]
+    #[cfg_attr(feature = "session", arg(conflicts_with = "phase"))]
+    pub gen_vectors: Option<PathBuf>,
+    /// Time each phase of the run, per tree depth, and print a table at the
+    /// end. Signs in-process rather than through separate signers.
+    #[arg(long, conflicts_with = "sign_subtree")]
+    #[cfg_attr(feature = "session", arg(conflicts_with = "phase"))]
+    pub timings: bool,
+    /// Run every signer on its own thread, talking to a coordinator thread
+    /// over channels.
+    #[arg(long, conflicts_with_all = ["sign_subtree", "timings"])]
+    #[cfg_attr(feature = "session", arg(conflicts_with = "phase"))]
+    pub simulate_network: bool,
+    /// Draw a progress bar on stderr for key generation, the key tree and
+    /// both rounds. Signs in-process rather than through separate signers.
+    #[arg(long, conflicts_with_all = ["sign_subtree", "timings", "simulate_network"])]
+    #[cfg_attr(feature = "session", arg(conflicts_with = "phase"))]
+    pub progress: bool,
+    /// Check the signing state against the key tree after each round and
+    /// print what was found. Signs in-process rather than through separate
+    /// signers.
+    #[arg(long, conflicts_with_all = ["sign_subtree", "timings", "simulate_network", "progress"])]
+    #[cfg_attr(feature = "session", arg(conflicts_with = "phase"))]
+    pub audit: bool,
+    /// Output format.
+    #[cfg(feature = "json")]
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    pub output: OutputFormat,
+    /// Sign over the key tree, or flat as a baseline.
+    #[arg(long, value_enum, default_value_t = SigningMode::Tree)]
+    pub mode: SigningMode,
+    /// Most children per key tree node. Wider nodes make a shallower tree
+    /// but reveal more sibling keys per level.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(2..))]
+    pub arity: u32,
+    /// Protocol parameters. Session files always use the defaults.
+    #[arg(long, value_enum, default_value_t = ParamsPreset::Default)]
+    pub params: ParamsPreset,
+    /// Sign with only the signers under this node (preorder index, 0 is the
+    /// root), revealing the sibling keys up to the root.
+    #[arg(long, value_name = "NODE_INDEX")]
+    #[cfg_attr(feature = "session", arg(conflicts_with = "phase"))]
+    pub sign_subtree: Option<usize>,
+    /// Run a single signing round, keeping state in `--session` in between.
+    #[cfg(feature = "session")]
+    #[arg(long, value_enum, requires = "session")]
+    pub phase: Option<Phase>,
+    /// Session file written by `--phase round1` and read by `--phase round2`.
+    /// It holds secret keys and nonces.
+    #[cfg(feature = "session")]
+    #[arg(long, requires = "phase")]
+    pub session: Option<PathBuf>,
+    /// Keep every file read or written inside this directory. Relative paths
+    /// are taken from it, and symlinks below it are refused.
+    #[arg(long, value_name = "DIR")]
+    pub workdir: Option<PathBuf>,
+    /// Filled in by `load`.
+    #[arg(skip)]
+    pub files: Files,
+}
+
+impl Args {
+    /// Defaults for everything but `n`, used when `n` comes from the prompt.
+    pub fn with_count(n: u32) -> Self {
+        Args {
+            max_n: MAX_SIGNERS,
+            n: Some(n),
+            keys: None,
+            seed: None,
+            export_keys: None,
+            message: None,
+            msg_hex: None,
+            msg_file: None,
+            sig_out: None,
+            tree_in: None,
+            tree_out: None,
+            show_tree: false,
+            paranoid: false,
+            taproot_merkle_root: None,
+            export_proof: None,
+            proof_out: None,
+            verify_proof: None,
+            proof_root: None,
+            gen_vectors: None,
+            timings: false,
+            simulate_network: false,
+            progress: false,
+            audit: false,
+            #[cfg(feature = "json")]
+            output: OutputFormat::Human,
+            mode: SigningMode::Tree,
+            arity: 2,
+            params: ParamsPreset::Default,
+            sign_subtree: None,
+            #[cfg(feature = "session")]
+            phase: None,
+            #[cfg(feature = "session")]
+            session: None,
+            workdir: None,
+            files: Files::default(),
+        }
+    }
+
+    /// These arguments with every file path passed through `SafePath` and
+    /// the input files read into `files`. Run before anything else looks at
+    /// the arguments: a value parser cannot see `--workdir`.
+    pub fn load(&self) -> Result<Args, ArgError> {
+        let paths = SafePath::new(self.workdir.as_deref()).map_err(ArgError::Path)?;
+        let resolve = |path: &Option<PathBuf>| {
+            path.as_deref().map(|path| paths.resolve(path)).transpose().map_err(ArgError::Path)
+        };
+        let mut args = self.clone();
+        args.keys = resolve(&self.keys)?;
+        args.export_keys = resolve(&self.export_keys)?;
+        args.msg_file = resolve(&self.msg_file)?;
+        args.sig_out = resolve(&self.sig_out)?;
+        args.tree_in = resolve(&self.tree_in)?;
+        args.tree_out = resolve(&self.tree_out)?;
+        args.proof_out = resolve(&self.proof_out)?;
+        args.verify_proof = resolve(&self.verify_proof)?;
+        args.gen_vectors = resolve(&self.gen_vectors)?;
+        #[cfg(feature = "session")]
+        {
+            args.session = resolve(&self.session)?;
+        }
+        if let Some(MessageArg::File(path)) = &self.message {
+            args.message = Some(MessageArg::File(paths.resolve(path).map_err(ArgError::Path)?));
+        }
+
+        let message_file = match &args.message {
+            Some(MessageArg::File(path)) => Some(path.as_path()),
+            _ => args.msg_file.as_deref(),
+        };
+        args.files = Files {
+            keys: args.keys.as_deref().map(read_key_file).transpose()?,
+            tree: args.tree_in.as_deref().map(read_tree_file).transpose()?,
+            message: message_file.map(read_message_file).transpose()?,
+        };
+        Ok(args)
+    }
+
+    /// Rejects a `--n` or key file above `--max-n`, a `--n` that disagrees
+    /// with the key file, a round 1 with no signer count, and binary tree
+    /// flags in flat mode or with a wider tree. The key file must be loaded.
+    pub fn check(&self) -> Result<(), ArgError> {
+        let key_count = self.files.keys.as_ref().map(|keys| u32::try_from(keys.0.len()).unwrap_or(u32::MAX));
+        if let Some(n) = self.n.into_iter().chain(key_count).find(|&n| n > self.max_n) {
+            return Err(ArgError::TooManySigners { n, max: self.max_n });
+        }
+        #[cfg(feature = "session")]
+        if self.phase == Some(Phase::Round1) && self.n.is_none() && self.keys.is_none() {
+            return Err(ArgError::MissingCount);
+        }
+        if self.json_output() && self.sign_subtree.is_some() {
+            return Err(ArgError::JsonSubtree);
+        }
+        if cfg!(not(feature = "json")) && self.gen_vectors.is_some() {
+            return Err(ArgError::MissingFeature { flag: "--gen-vectors", feature: "json" });
+        }
+        if self.mode == SigningMode::Flat {
+            if let Some(flag) = self.binary_tree_flag().or((self.arity > 2).then_some("--arity")) {
+                return Err(ArgError::FlatMode(flag));
+            }
+        } else if self.arity > 2 {
+            if let Some(flag) = self.binary_tree_flag() {
+                return Err(ArgError::WideTree(flag));
+            }
+        }
+        match (self.n, &self.files.keys) {
+            (Some(n), Some(keys)) if n as usize != keys.0.len() => {
+                Err(ArgError::CountMismatch { n, keys: keys.0.len() })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The first flag set that only the binary key tree run supports.
+    fn binary_tree_flag(&self) -> Option<&'static str> {
+        #[cfg(feature = "session")]
+        if self.phase.is_some() {
+            return Some("--phase");
+        }
+        let tree_only = [
+            (self.sign_subtree.is_some(), "--sign-subtree"),
+            (self.timings, "--timings"),
+            (self.simulate_network, "--simulate-network"),
+            (self.progress, "--progress"),
+            (self.audit, "--audit"),
+            (self.show_tree, "--show-tree"),
+            (self.tree_in.is_some(), "--tree-in"),
+            (self.tree_out.is_some(), "--tree-out"),
+            (self.export_proof.is_some(), "--export-proof"),
+            (self.gen_vectors.is_some(), "--gen-vectors"),
+            (self.json_output(), "--output json"),
+        ];
+        tree_only.into_iter().find(|&(set, _)| set).map(|(_, flag)| flag)
+    }
+
+    pub fn json_output(&self) -> bool {
+        #[cfg(feature = "json")]
+        {
+            self.output == OutputFormat::Json
+        }
+        #[cfg(not(feature = "json"))]
+        {
+            false
+        }
+    }
+
+    /// The signer count, from `--n` or the loaded key file.
+    pub fn count(&self) -> usize {
+        match (&self.files.keys, self.n) {
+            (Some(keys), _) => keys.0.len(),
+            (None, Some(n)) => n as usize,
+            (None, None) => unreachable!("--n is required without --keys outside round 2"),
+        }
+    }
+
+    /// The message to sign; at most one of the message options is set, and
+    /// one naming a file must be loaded.
+    pub fn message(&self) -> &[u8] {
+        let hex = match &self.message {
+            Some(MessageArg::Hex(m)) => Some(m),
+            _ => self.msg_hex.as_ref(),
+        };
+        hex.or(self.files.message.as_ref()).map_or(DEFAULT_MESSAGE, |m| &m.0)
+    }
+}
+
+/// Any positive count; `Args::check` applies `--max-n`, which the parser
+/// cannot see, here and to key files alike.
+pub fn parse_count(s: &str) -> Result<u32, ArgError> {
+    let n: u32 = s.trim().parse().map_err(|_| ArgError::InvalidCount(s.to_string()))?;
+    if n == 0 {
+        return Err(ArgError::CountOutOfRange(n));
+    }
+    Ok(n)
+}
+
+pub fn parse_message(s: &str) -> Result<MessageArg, ArgError> {
+    match s.strip_prefix('@') {
+        Some(path) => Ok(MessageArg::File(PathBuf::from(path))),
+        None => parse_hex_message(s).map(MessageArg::Hex),
+    }
+}
+
+pub fn parse_hex_message(s: &str) -> Result<Message, ArgError> {
+    hex_any_lenient(s).map(Message).map_err(ArgError::Message)
+}
+
+pub fn parse_merkle_root(s: &str) -> Result<[u8; 32], ArgError> {
+    hex_exact(s).map_err(ArgError::MerkleRoot)
+}
+
+pub fn parse_pubkey(s: &str) -> Result<Secp256k1Point, ArgError> {
+    let bytes = hex_exact::<POINT_LEN>(s).map_err(|e| ArgError::PublicKey(e.to_string()))?;
+    point_from_bytes(&bytes).map_err(|e| ArgError::PublicKey(e.to_string()))
+}
+
+pub fn read_key_file(path: &Path) -> Result<KeySet, ArgError> {
+    let text = fs::read_to_string(path).map_err(|error| ArgError::KeyFile { path: path.display().to_string(), error })?;
+    let keys = parse_secret_keys(&text).map_err(|error| ArgError::Keys { path: path.display().to_string(), error })?;
+    if keys.is_empty() {
+        return Err(ArgError::CountOutOfRange(0));
+    }
+    Ok(KeySet(keys))
+}
+
+pub fn read_tree_file(path: &Path) -> Result<TreeLevels, ArgError> {
+    let text = fs::read_to_string(path).map_err(|error| ArgError::TreeFile { path: path.display().to_string(), error })?;
+    let levels = parse_key_tree(&text).map_err(|error| ArgError::Tree { path: path.display().to_string(), error })?;
+    Ok(TreeLevels(levels))
+}
+
+pub fn read_message_file(path: &Path) -> Result<Message, ArgError> {
+    fs::read(path)
+        .map(Message)
+        .map_err(|error| ArgError::MessageFile { path: path.display().to_string(), error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(std::iter::once("ark-usecase").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn full_command_line() {
+        let args = parse(&["--n", "8", "--message", "deadbeef", "--show-tree"]).unwrap();
+        assert_eq!(args.n, Some(8));
+        assert_eq!(args.count(), 8);
+        assert_eq!(args.message(), &[0xde, 0xad, 0xbe, 0xef]);
+        assert!(args.show_tree);
+        assert!(!args.paranoid);
+        assert_eq!(args.seed, None);
+        assert_eq!(parse(&["--n", "2", "--seed", "42"]).unwrap().seed, Some(42));
+    }
+
+    #[test]
+    fn merkle_root_is_32_bytes() {
+        let root = "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21";
+        let args = parse(&["--n", "2", "--taproot-merkle-root", root]).unwrap();
+        assert_eq!(args.taproot_merkle_root.unwrap()[0], 0x5b);
+        assert_eq!(parse(&["--n", "2"]).unwrap().taproot_merkle_root, None);
+        assert_eq!(
+            parse(&["--n", "2", "--taproot-merkle-root", "abcd"]).unwrap_err().kind(),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn params_default_to_the_preset() {
+        assert_eq!(parse(&["--n", "2"]).unwrap().params, ParamsPreset::Default);
+        assert_eq!(parse(&["--n", "2", "--params", "default"]).unwrap().params, ParamsPreset::Default);
+        assert_eq!(parse(&["--n", "2", "--params", "custom"]).unwrap_err().kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn flat_mode_rejects_tree_only_flags() {
+        assert_eq!(parse(&["--n", "2"]).unwrap().mode, SigningMode::Tree);
+        let args = parse(&["--n", "2", "--mode", "flat"]).unwrap();
+        assert_eq!(args.mode, SigningMode::Flat);
+        assert!(args.check().is_ok());
+
+        let err = parse(&["--n", "8", "--mode", "flat", "--sign-subtree", "1"]).unwrap().check().unwrap_err();
+        assert_eq!(err.to_string(), "--mode flat cannot be combined with --sign-subtree");
+        let err = parse(&["--n", "8", "--mode", "flat", "--timings"]).unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::FlatMode("--timings")));
+    }
+
+    #[test]
+    fn arity_flag() {
+        assert_eq!(parse(&["--n", "2"]).unwrap().arity, 2);
+        let args = parse(&["--n", "9", "--arity", "3"]).unwrap();
+        assert_eq!(args.arity, 3);
+        assert!(args.check().is_ok());
+        assert_eq!(parse(&["--n", "9", "--arity", "1"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+
+        let err = parse(&["--n", "9", "--arity", "3", "--show-tree"]).unwrap().check().unwrap_err();
+        assert_eq!(err.to_string(), "--arity above 2 cannot be combined with --show-tree");
+        let err = parse(&["--n", "9", "--arity", "3", "--mode", "flat"]).unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::FlatMode("--arity")));
+    }
+
+    #[test]
+    fn proof_flags() {
+        let pk = format!("02{}", "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        let args = parse(&["--n", "2", "--export-proof", &pk, "--proof-out", "p.bin"]).unwrap();
+        assert!(args.export_proof.is_some());
+        assert_eq!(parse(&["--n", "2", "--export-proof", &pk]).unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+        assert_eq!(parse(&["--n", "2", "--export-proof", "02abcd", "--proof-out", "p.bin"]).unwrap_err().kind(), ErrorKind::ValueValidation);
+
+        // verifying needs no signers
+        let args = parse(&["--verify-proof", "p.bin", "--proof-root", &pk]).unwrap();
+        assert_eq!(args.verify_proof, Some(PathBuf::from("p.bin")));
+        assert!(args.proof_root.is_some());
+    }
+
+    #[test]
+    fn progress_flag() {
+        assert!(parse(&["--n", "4", "--progress"]).unwrap().progress);
+        assert_eq!(parse(&["--n", "4", "--progress", "--timings"]).unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        assert_eq!(parse(&["--n", "4", "--progress", "--simulate-network"]).unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        let err = parse(&["--n", "4", "--progress", "--arity", "3"]).unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::WideTree("--progress")));
+    }
+
+    #[test]
+    fn audit_flag() {
+        assert!(parse(&["--n", "4", "--audit"]).unwrap().audit);
+        assert!(!parse(&["--n", "4"]).unwrap().audit);
+        assert_eq!(parse(&["--n", "4", "--audit", "--progress"]).unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        assert_eq!(parse(&["--n", "4", "--audit", "--sign-subtree", "1"]).unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        let err = parse(&["--n", "4", "--audit", "--mode", "flat"]).unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::FlatMode("--audit")));
+    }
+
+    #[test]
+    fn gen_vectors_flag() {
+        let args = parse(&["--gen-vectors", "vectors.json", "--seed", "1"]).unwrap();
+        assert_eq!(args.gen_vectors, Some(PathBuf::from("vectors.json")));
+        assert_eq!(args.n, None);
+        assert_eq!(parse(&["--gen-vectors", "v.json"]).unwrap_err().kind(), ErrorKind::MissingRequiredArgument);
+        assert_eq!(parse(&["--gen-vectors", "v.json", "--seed", "1", "--timings"]).unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        let err = parse(&["--gen-vectors", "v.json", "--seed", "1", "--mode", "flat"]).unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::FlatMode("--gen-vectors")));
+    }
+
+    #[test]
+    fn tree_file_flags() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-cli-tree-{}", std::process::id()));
+        let p = path.to_str().unwrap();
+        fs::write(&path, "not hex\n").unwrap();
+        let err = parse(&["--n", "2", "--tree-in", p]).unwrap().load().unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, ArgError::Tree { .. }));
+        assert!(err.to_string().contains("line 1"));
+
+        assert_eq!(parse(&["--n", "2", "--tree-out", p, "--timings"]).unwrap_err().kind(), ErrorKind::ArgumentConflict);
+        let err = parse(&["--n", "2", "--mode", "flat", "--tree-out", p]).unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::FlatMode("--tree-out")));
+    }
+
+    #[test]
+    fn message_defaults_when_absent() {
+        let args = parse(&["--n", "3"]).unwrap();
+        assert_eq!(args.message(), DEFAULT_MESSAGE);
+        assert_eq!(Args::with_count(3).message(), DEFAULT_MESSAGE);
+    }
+
+    #[test]
+    fn n_is_required() {
+        let err = parse(&["--show-tree"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn bad_counts_are_value_errors() {
+        for bad in ["0", "abc", "1.5", "", "99999999999"] {
+            let err = parse(&["--n", bad]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ValueValidation, "n = {}", bad);
+        }
+    }
+
+    #[test]
+    fn count_error_messages() {
+        assert_eq!(
+            parse_count("0").unwrap_err().to_string(),
+            "number of signers must be at least 1, got 0"
+        );
+        assert_eq!(parse_count("eight").unwrap_err().to_string(), "\"eight\" is not a number of signers");
+        assert_eq!(parse_count(" 12\n").unwrap(), 12);
+        assert_eq!(parse_count("-1").unwrap_err().to_string(), "\"-1\" is not a number of signers");
+    }
+
+    #[test]
+    fn large_counts_need_max_n() {
+        let args = parse(&["--n", "4097"]).unwrap();
+        let err = args.check().unwrap_err();
+        assert!(matches!(err, ArgError::TooManySigners { n: 4097, max: MAX_SIGNERS }));
+        assert_eq!(err.to_string(), "refusing to create 4097 signers, the limit is 4096; pass --max-n 4097 to go ahead");
+        parse(&["--n", "4097", "--max-n", "5000"]).unwrap().check().unwrap();
+        parse(&["--n", "4096"]).unwrap().check().unwrap();
+
+        // a lower limit applies too, also to a count typed at the prompt
+        assert!(parse(&["--n", "9", "--max-n", "8"]).unwrap().check().is_err());
+        assert!(Args::with_count(MAX_SIGNERS + 1).check().is_err());
+    }
+
+    #[test]
+    fn message_from_file() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-msg-{}", std::process::id()));
+        fs::write(&path, b"raw bytes, not hex").unwrap();
+        let arg = format!("@{}", path.display());
+        let args = parse(&["--n", "2", "--message", &arg]).unwrap().load().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(args.message(), b"raw bytes, not hex");
+    }
+
+    #[test]
+    fn msg_hex_and_msg_file() {
+        let args = parse(&["--n", "2", "--msg-hex", "0x00ff"]).unwrap();
+        assert_eq!(args.message(), &[0x00, 0xff]);
+        let args = parse(&["--n", "2", "--msg-hex", ""]).unwrap();
+        assert_eq!(args.message(), b"");
+
+        let path = std::env::temp_dir().join(format!("ark-usecase-msg-file-{}", std::process::id()));
+        fs::write(&path, b"0xdeadbeef").unwrap();
+        let args = parse(&["--n", "2", "--msg-file", path.to_str().unwrap()]).unwrap().load().unwrap();
+        fs::remove_file(&path).unwrap();
+        // file contents are raw bytes, not hex
+        assert_eq!(args.message(), b"0xdeadbeef");
+    }
+
+    #[test]
+    fn message_options_are_exclusive() {
+        for argv in [
+            ["--msg-hex", "00", "--msg-file", "Cargo.toml"],
+            ["--message", "00", "--msg-hex", "00"],
+            ["--message", "00", "--msg-file", "Cargo.toml"],
+        ] {
+            let err = parse(&[&["--n", "2"][..], &argv[..]].concat()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{:?}", argv);
+        }
+    }
+
+    fn key_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("ark-usecase-{}-{}", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn keys_imply_n() {
+        let path = key_file("keys", &format!("{}\n{}\n", "01".repeat(32), "02".repeat(32)));
+        let p = path.to_str().unwrap();
+        let args = parse(&["--keys", p]).unwrap().load().unwrap();
+        assert_eq!(args.n, None);
+        assert_eq!(args.count(), 2);
+        assert!(args.check().is_ok());
+
+        assert!(parse(&["--keys", p, "--n", "2"]).unwrap().load().unwrap().check().is_ok());
+        let err = parse(&["--keys", p, "--n", "3"]).unwrap().load().unwrap().check().unwrap_err();
+        assert_eq!(err.to_string(), "--n 3 does not match the 2 keys in the key file");
+
+        for other in [["--export-keys", "out"], ["--seed", "1"]] {
+            let err = parse(&[&["--keys", p][..], &other[..]].concat()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{:?}", other);
+        }
+
+        // `--max-n` bounds a key file as it bounds `--n`
+        let err = parse(&["--keys", p, "--max-n", "1"]).unwrap().load().unwrap().check().unwrap_err();
+        assert!(matches!(err, ArgError::TooManySigners { n: 2, max: 1 }));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bad_key_file_names_the_line() {
+        let path = key_file("bad-keys", &format!("{}\nnot hex\n", "01".repeat(32)));
+        let p = path.to_str().unwrap().to_string();
+        let err = read_key_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err.to_string(), format!("{}: line 2: expected 64 hex chars, got 6", p));
+
+        let path = key_file("empty-keys", "\n");
+        let err = read_key_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, ArgError::CountOutOfRange(0)));
+    }
+
+    #[test]
+    fn file_options_stay_in_workdir() {
+        let dir = std::env::temp_dir().join(format!("ark-usecase-cli-workdir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("keys.txt"), format!("{}\n", "01".repeat(32))).unwrap();
+        fs::write(dir.join("msg.bin"), b"from the workdir").unwrap();
+        let d = dir.to_str().unwrap();
+
+        let args = parse(&["--workdir", d, "--keys", "keys.txt", "--message", "@msg.bin", "--sig-out", "sig.bin"])
+            .unwrap()
+            .load()
+            .unwrap();
+        assert_eq!(args.count(), 1);
+        assert_eq!(args.message(), b"from the workdir");
+        assert_eq!(args.sig_out, Some(fs::canonicalize(&dir).unwrap().join("sig.bin")));
+
+        for argv in [
+            ["--sig-out", "../sig.bin"],
+            ["--export-keys", "/tmp/keys.txt"],
+            ["--tree-out", "sub/../../tree.txt"],
+            ["--msg-file", "../msg.bin"],
+        ] {
+            let err = parse(&[&["--n", "2", "--workdir", d][..], &argv[..]].concat()).unwrap().load().unwrap_err();
+            assert!(matches!(err, ArgError::Path(PathError::OutsideWorkdir(_))), "{:?}", argv);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = parse(&["--n", "2", "--workdir", d]).unwrap().load().unwrap_err();
+        assert!(matches!(err, ArgError::Path(PathError::Workdir { .. })));
+    }
+
+    #[cfg(feature = "session")]
+    #[test]
+    fn phase_needs_session() {
+        let args = parse(&["--phase", "round2", "--session", "s.bin", "--msg-hex", "00"]).unwrap();
+        assert_eq!(args.phase, Some(Phase::Round2));
+        assert_eq!(args.n, None);
+        assert!(args.check().is_ok());
+
+        let err = parse(&["--n", "2", "--phase", "round1"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+        let err = parse(&["--n", "2", "--session", "s.bin"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+        let err = parse(&["--n", "2", "--phase", "round3", "--session", "s.bin"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+
+        let err = parse(&["--phase", "round1", "--session", "s.bin"]).unwrap().check().unwrap_err();
+        assert_eq!(err.to_string(), "--phase round1 needs --n or --keys");
+    }
+
+    #[test]
+    fn bad_messages() {
+        assert!(matches!(parse_message("abc"), Err(ArgError::Message(ParseError::OddLength(3)))));
+        // the file is read by `load`, not the parser
+        let missing = parse(&["--n", "2", "--message", "@/nonexistent/ark-usecase-msg"]).unwrap();
+        assert_eq!(missing.message, Some(MessageArg::File(PathBuf::from("/nonexistent/ark-usecase-msg"))));
+        assert!(matches!(missing.load(), Err(ArgError::MessageFile { .. })));
+        let err = parse(&["--n", "2", "--message", "zz"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+}
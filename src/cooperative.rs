@@ -0,0 +1,149 @@
+//! Cooperative close of one subtree: when every signer under a node is
+//! online, they sign for the node's key on their own. Plain n-of-n MuSig2
+//! over the subtree's leaves is cheaper, with no merkle path in the
+//! challenge, but its key is `flat_key` of the leaves, which matches the
+//! node's stored aggregate only where the node joins two leaves. Elsewhere
+//! the nested coefficients differ and the signers sign the subtree as a key
+//! tree instead.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::params::Params;
+use std::collections::{HashMap, HashSet};
+
+use crate::bintree::BinTree;
+use crate::encoding::point_to_bytes;
+use crate::error::Error;
+use crate::flat::{flat_key, flat_sign};
+use crate::indexed::IndexedTree;
+use crate::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+use crate::treemusig::tree_sign_with;
+
+/// A signature under one node's key, and how it was made.
+#[derive(Debug, Clone)]
+pub struct CooperativeClose {
+    /// The node's stored aggregate, which `sig` verifies under.
+    pub node_key: AggregatedKey,
+    /// Whether `flat_key` of the node's leaves is `node_key`, and so `sig`
+    /// is a flat MuSig2 signature rather than a tree signature.
+    pub flat: bool,
+    pub sig: TreeSignature,
+}
+
+pub fn cooperative_sign(
+    tree: &BinTree<Secp256k1Point>,
+    node_value: &Secp256k1Point,
+    msg: &[u8],
+    signers: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+) -> Result<CooperativeClose, Error> {
+    cooperative_sign_with(tree, node_value, msg, signers, &Params::default())
+}
+
+/// Signs `msg` for the first node of `tree`, in preorder, whose key is
+/// `node_value`. `signers` must hold the secret of every leaf under it and
+/// nothing else. The signature is checked under `node_value` before it is
+/// returned.
+pub fn cooperative_sign_with(
+    tree: &BinTree<Secp256k1Point>,
+    node_value: &Secp256k1Point,
+    msg: &[u8],
+    signers: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    params: &Params,
+) -> Result<CooperativeClose, Error> {
+    let indexed = IndexedTree::from_tree(tree);
+    let node = (0..indexed.node_count())
+        .find(|&idx| indexed.get(idx).value == *node_value)
+        .ok_or_else(|| Error::NotANode(node_value.clone()))?;
+    let subtree = indexed.subtree(node);
+    let leaves: Vec<_> = subtree.leaves().cloned().collect();
+    check_signers(&leaves, signers)?;
+
+    let flat = flat_key(&leaves, params)? == *node_value;
+    let sig = if flat {
+        flat_sign(&leaves, signers, msg, params)?
+    } else {
+        tree_sign_with(&subtree, signers, msg, params)?
+    };
+    let close = CooperativeClose { node_key: AggregatedKey::new(node_value.clone()), flat, sig: TreeSignature::new(sig) };
+    if !VerifyCtx::new(params.clone(), &close.node_key).verify(msg, &close.sig) {
+        return Err(Error::Unverified(node_value.clone()));
+    }
+    Ok(close)
+}
+
+/// Fails unless the signers' keys are exactly the keys at `leaves`, naming
+/// the ones missing and the ones from elsewhere, each in encoding order.
+fn check_signers(leaves: &[Secp256k1Point], signers: &HashMap<Secp256k1Point, Secp256k1Scalar>) -> Result<(), Error> {
+    let under: HashSet<_> = leaves.iter().map(point_to_bytes).collect();
+    let mut missing: Vec<_> = leaves.iter().filter(|pk| !signers.contains_key(pk)).cloned().collect();
+    let mut extra: Vec<_> = signers.keys().filter(|pk| !under.contains(&point_to_bytes(pk))).cloned().collect();
+    if missing.is_empty() && extra.is_empty() {
+        return Ok(());
+    }
+    missing.sort_by_cached_key(point_to_bytes);
+    missing.dedup();
+    extra.sort_by_cached_key(point_to_bytes);
+    Err(Error::SignerMismatch { missing, extra })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::build_key_tree;
+
+    fn eight() -> (BinTree<Secp256k1Point>, Vec<Keypair>) {
+        let keys: Vec<_> = (0..8).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        (tree, keys)
+    }
+
+    /// The key of node `idx` (preorder) and the secrets of the leaves under it.
+    fn node_signers(tree: &BinTree<Secp256k1Point>, idx: usize, keys: &[Keypair]) -> (Secp256k1Point, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+        let subtree = IndexedTree::from_tree(tree).subtree(idx);
+        let secrets = keys.iter().filter(|kp| subtree.contains_leaf(&kp.pk)).map(|kp| (kp.pk.clone(), kp.sk.clone())).collect();
+        (subtree.value().clone(), secrets)
+    }
+
+    #[test]
+    fn closes_at_depth_one_and_two() {
+        let (tree, keys) = eight();
+        let params = Params::default();
+        // preorder: 0 root, 1 left half of four leaves, 2 its left pair
+        for (idx, leaves, flat) in [(1, 4, false), (2, 2, true)] {
+            let (node_key, signers) = node_signers(&tree, idx, &keys);
+            assert_eq!(signers.len(), leaves);
+            let close = cooperative_sign(&tree, &node_key, b"coop", &signers).unwrap();
+            assert_eq!(close.flat, flat, "node {}", idx);
+            assert_eq!(close.node_key.as_point(), &node_key);
+
+            let ctx = VerifyCtx::new(params.clone(), &close.node_key);
+            assert!(ctx.verify(b"coop", &close.sig), "node {}", idx);
+            assert!(!ctx.verify(b"coo", &close.sig), "node {}", idx);
+            // a signature for the node is not one for the root
+            let root = VerifyCtx::new(params.clone(), &AggregatedKey::new(tree.value().clone()));
+            assert!(!root.verify(b"coop", &close.sig), "node {}", idx);
+        }
+    }
+
+    #[test]
+    fn signers_must_be_exactly_the_leaves() {
+        let (tree, keys) = eight();
+        let (node_key, signers) = node_signers(&tree, 1, &keys);
+
+        let mut short = signers.clone();
+        let gone = keys[0].pk.clone();
+        short.remove(&gone);
+        let err = cooperative_sign(&tree, &node_key, b"coop", &short).unwrap_err();
+        assert!(matches!(&err, Error::SignerMismatch { missing, extra } if *missing == [gone.clone()] && extra.is_empty()));
+
+        let mut more = signers;
+        let outsider = keys[7].clone();
+        more.insert(outsider.pk.clone(), outsider.sk);
+        let err = cooperative_sign(&tree, &node_key, b"coop", &more).unwrap_err();
+        assert!(matches!(&err, Error::SignerMismatch { missing, extra } if missing.is_empty() && *extra == [outsider.pk.clone()]));
+        assert!(err.to_string().contains("1 not under it"));
+
+        let stranger = Keypair::generate().pk;
+        assert!(matches!(cooperative_sign(&tree, &stranger, b"coop", &HashMap::new()), Err(Error::NotANode(pk)) if pk == stranger));
+    }
+}
@@ -0,0 +1,176 @@
+//! Drives a signing run between separate `Signer`s. The coordinator only
+//! ever sees public data: the key tree, round 1 outputs and partial
+//! signatures.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{params::Params, round1::Round1Out};
+
+use crate::bintree::BinTree;
+use crate::error::Error;
+use crate::indexed::IndexedTree;
+use crate::progress::StageProgress;
+use crate::treemusig::{MAX_TREE_HEIGHT, NONCES, NodeState, Signature, StateMap, aggregate_round1, aggregate_round2, check_height, leaf_round2_inputs, node_state_mut, root_signature};
+
+pub struct Coordinator {
+    tree: IndexedTree<Secp256k1Point>,
+    /// Arena index of each leaf, by leaf position.
+    leaves: Vec<usize>,
+    nodes: StateMap,
+    params: Params,
+}
+
+impl Coordinator {
+    pub fn new(tree: &BinTree<Secp256k1Point>) -> Self {
+        Coordinator::with_params(tree, Params::default())
+    }
+
+    /// A coordinator for a tree built under `params`. Every `Signer` must
+    /// use the same `params`.
+    pub fn with_params(tree: &BinTree<Secp256k1Point>, params: Params) -> Self {
+        let tree = IndexedTree::from_tree(tree);
+        let leaves: Vec<usize> = tree.leaf_indices().collect();
+        let nodes = leaves.iter().map(|&idx| (idx, NodeState::default())).collect();
+        Coordinator { tree, leaves, nodes, params }
+    }
+
+    pub fn signer_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// The key of the leaf at `position`, i.e. who should sign there.
+    pub fn leaf_key(&self, position: usize) -> Option<&Secp256k1Point> {
+        self.leaves.get(position).map(|&idx| &self.tree.get(idx).value)
+    }
+
+    fn leaf(&self, position: usize) -> Result<usize, Error> {
+        self.leaves.get(position).copied().ok_or(Error::UnknownSigner(position))
+    }
+
+    pub fn add_round1(&mut self, position: usize, out: Round1Out) -> Result<(), Error> {
+        let idx = self.leaf(position)?;
+        if out.0.len() != NONCES {
+            return Err(Error::MalformedRound1(position));
+        }
+        let state = node_state_mut(&self.tree, &mut self.nodes, idx)?;
+        if state.out.is_some() {
+            return Err(Error::RoundRepeated { pubkey: self.tree.get(idx).value.clone(), round: 1 });
+        }
+        state.out = Some(out);
+        Ok(())
+    }
+
+    /// Once every signer's round 1 output is in, aggregates them up the tree.
+    pub fn aggregate_round1(&mut self) -> Result<(), Error> {
+        check_height(&self.tree, MAX_TREE_HEIGHT)?;
+        aggregate_round1(&self.tree, &mut self.nodes, &self.params, None, StageProgress::silent(), |_| false)
+    }
+
+    /// What the signer at `position` needs for round 2: the internal round 1
+    /// outputs of its two-child ancestors and its sibling path, both root
+    /// level first.
+    pub fn round2_inputs(&self, position: usize) -> Result<(Vec<Round1Out>, Vec<Vec<Secp256k1Point>>), Error> {
+        leaf_round2_inputs(&self.tree, &self.nodes, self.leaf(position)?)
+    }
+
+    pub fn add_round2(&mut self, position: usize, prime: (Secp256k1Point, Secp256k1Scalar)) -> Result<(), Error> {
+        let idx = self.leaf(position)?;
+        let state = node_state_mut(&self.tree, &mut self.nodes, idx)?;
+        if state.out_prime.is_some() {
+            return Err(Error::RoundRepeated { pubkey: self.tree.get(idx).value.clone(), round: 2 });
+        }
+        state.state_prime = Some(prime.0);
+        state.out_prime = Some(prime.1);
+        Ok(())
+    }
+
+    /// Once every signer's partial signature is in, combines them into the
+    /// signature for the root key.
+    pub fn aggregate_round2(&mut self) -> Result<Signature, Error> {
+        aggregate_round2(&self.tree, &mut self.nodes, None, StageProgress::silent(), |_| false)?;
+        root_signature(&self.tree, &self.nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::signer::Signer;
+    use crate::treemusig::{build_key_tree, tree_verify};
+
+    fn signers(n: usize) -> (BinTree<Secp256k1Point>, Vec<Signer>) {
+        let mut keys: Vec<Keypair> = (0..n).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        // Each signer is handed only its own keypair, placed at its leaf.
+        let signers = tree
+            .leaves()
+            .enumerate()
+            .map(|(position, pk)| {
+                let i = keys.iter().position(|kp| kp.pk == *pk).unwrap();
+                Signer::new(keys.swap_remove(i), position)
+            })
+            .collect();
+        (tree, signers)
+    }
+
+    fn run(tree: &BinTree<Secp256k1Point>, signers: &mut [Signer], msg: &[u8]) -> Signature {
+        let mut coordinator = Coordinator::new(tree);
+        for signer in signers.iter_mut() {
+            coordinator.add_round1(signer.position(), signer.round1().unwrap()).unwrap();
+        }
+        coordinator.aggregate_round1().unwrap();
+        for signer in signers.iter_mut() {
+            let (outs, path) = coordinator.round2_inputs(signer.position()).unwrap();
+            let prime = signer.round2(&outs, msg, &path).unwrap();
+            coordinator.add_round2(signer.position(), prime).unwrap();
+        }
+        // The coordinator never held a secret key or nonce state.
+        assert!(coordinator.nodes.values().all(|s| s.secret_key.is_none() && s.state.is_none()));
+        coordinator.aggregate_round2().unwrap()
+    }
+
+    #[test]
+    fn separate_signers_produce_a_valid_signature() {
+        for n in [1, 2, 3, 5, 8] {
+            let (tree, mut signers) = signers(n);
+            assert_eq!(Coordinator::new(&tree).signer_count(), n);
+            let sig = run(&tree, &mut signers, b"separate signers");
+            assert!(tree_verify(tree.value(), b"separate signers", &sig), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn round2_inputs_match_the_tree() {
+        let (tree, _) = signers(5);
+        let coordinator = Coordinator::new(&tree);
+        for (position, pk) in tree.leaves().enumerate() {
+            assert!(coordinator.leaf_key(position) == Some(pk));
+        }
+        assert!(coordinator.leaf_key(5).is_none());
+        assert!(matches!(coordinator.round2_inputs(5), Err(Error::UnknownSigner(5))));
+        // before round 1 is aggregated there are no internal outputs yet
+        assert!(matches!(
+            coordinator.round2_inputs(0),
+            Err(Error::MissingNodeState(_))
+        ));
+    }
+
+    #[test]
+    fn signer_round2_needs_round1() {
+        let (_, mut signers) = signers(2);
+        let err = signers[0].round2(&[], b"msg", &[]).unwrap_err();
+        assert!(matches!(err, Error::IncompleteNodeState { field: "state", .. }));
+    }
+
+    #[test]
+    fn repeated_rounds_are_rejected() {
+        let (tree, mut signers) = signers(2);
+        let out = signers[0].round1().unwrap();
+        assert!(matches!(signers[0].round1(), Err(Error::RoundRepeated { round: 1, .. })));
+
+        let mut coordinator = Coordinator::new(&tree);
+        coordinator.add_round1(0, out.clone()).unwrap();
+        assert!(matches!(coordinator.add_round1(0, out), Err(Error::RoundRepeated { round: 1, .. })));
+        assert!(matches!(coordinator.add_round1(1, Round1Out(vec![])), Err(Error::MalformedRound1(1))));
+    }
+}
@@ -0,0 +1,198 @@
+//! Byte encodings of curve types. Everything that turns a point or scalar
+//! into bytes goes through here, so the crypto_rs serialization calls live in
+//! one place.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::round1::{Round1Out, Round1State};
+use std::fmt;
+
+use crate::parse::{ParseError, to_hex};
+use crate::treemusig::Signature;
+
+pub const POINT_LEN: usize = 33;
+pub const SCALAR_LEN: usize = 32;
+/// A signature is its point followed by its scalar.
+pub const SIGNATURE_LEN: usize = POINT_LEN + SCALAR_LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    WrongLength { expected: usize, got: usize },
+    InvalidPoint,
+    InvalidScalar,
+    /// Decodes, but to a value whose own encoding is different bytes.
+    NonCanonical,
+    Hex(ParseError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::WrongLength { expected, got } => {
+                write!(f, "expected {} bytes, got {}", expected, got)
+            }
+            DecodeError::InvalidPoint => write!(f, "not a valid compressed secp256k1 point"),
+            DecodeError::InvalidScalar => write!(f, "not a valid secp256k1 scalar"),
+            DecodeError::NonCanonical => write!(f, "not the canonical encoding of its value"),
+            DecodeError::Hex(e) => write!(f, "invalid hex: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Hex(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for DecodeError {
+    fn from(e: ParseError) -> Self {
+        DecodeError::Hex(e)
+    }
+}
+
+fn check_len(bytes: &[u8], expected: usize) -> Result<(), DecodeError> {
+    if bytes.len() != expected {
+        return Err(DecodeError::WrongLength { expected, got: bytes.len() });
+    }
+    Ok(())
+}
+
+/// 33-byte compressed SEC1 encoding.
+pub fn point_to_bytes(point: &Secp256k1Point) -> Vec<u8> {
+    point.to_bytes()
+}
+
+/// Only the canonical encoding is accepted, so every point has exactly one.
+pub fn point_from_bytes(bytes: &[u8]) -> Result<Secp256k1Point, DecodeError> {
+    check_len(bytes, POINT_LEN)?;
+    let point = Secp256k1Point::from_bytes(bytes).map_err(|_| DecodeError::InvalidPoint)?;
+    if point_to_bytes(&point) != bytes {
+        return Err(DecodeError::NonCanonical);
+    }
+    Ok(point)
+}
+
+pub fn point_hex(point: &Secp256k1Point) -> String {
+    to_hex(&point_to_bytes(point))
+}
+
+/// The first 8 bytes of `point_hex`, enough to tell a tree's keys apart in
+/// a message.
+pub fn point_fingerprint(point: &Secp256k1Point) -> String {
+    point_hex(point)[..16].to_string()
+}
+
+/// 32 bytes, big-endian.
+pub fn scalar_to_bytes(scalar: &Secp256k1Scalar) -> Vec<u8> {
+    scalar.to_bytes().to_vec()
+}
+
+/// Like `point_from_bytes`, only the canonical encoding is accepted.
+pub fn scalar_from_bytes(bytes: &[u8]) -> Result<Secp256k1Scalar, DecodeError> {
+    check_len(bytes, SCALAR_LEN)?;
+    let scalar = Secp256k1Scalar::from_bytes(bytes).map_err(|_| DecodeError::InvalidScalar)?;
+    if scalar_to_bytes(&scalar) != bytes {
+        return Err(DecodeError::NonCanonical);
+    }
+    Ok(scalar)
+}
+
+pub fn signature_to_bytes(sig: &Signature) -> Vec<u8> {
+    let mut bytes = point_to_bytes(&sig.0);
+    bytes.extend(scalar_to_bytes(&sig.1));
+    bytes
+}
+
+pub fn signature_from_bytes(bytes: &[u8]) -> Result<Signature, DecodeError> {
+    check_len(bytes, SIGNATURE_LEN)?;
+    let (point, scalar) = bytes.split_at(POINT_LEN);
+    Ok((point_from_bytes(point)?, scalar_from_bytes(scalar)?))
+}
+
+pub fn signature_hex(sig: &Signature) -> String {
+    to_hex(&signature_to_bytes(sig))
+}
+
+/// A round 1 output is the signer's public nonce points, one encoding each.
+pub fn round1_out_to_bytes(out: &Round1Out) -> Vec<Vec<u8>> {
+    out.0.iter().map(point_to_bytes).collect()
+}
+
+pub fn round1_out_from_bytes(points: &[Vec<u8>]) -> Result<Round1Out, DecodeError> {
+    let points = points.iter().map(|p| point_from_bytes(p)).collect::<Result<_, _>>()?;
+    Ok(Round1Out(points))
+}
+
+/// The matching secret nonces. Whoever holds these can sign exactly once.
+pub fn round1_state_to_bytes(state: &Round1State) -> Vec<Vec<u8>> {
+    state.0.iter().map(scalar_to_bytes).collect()
+}
+
+pub fn round1_state_from_bytes(scalars: &[Vec<u8>]) -> Result<Round1State, DecodeError> {
+    let scalars = scalars.iter().map(|s| scalar_from_bytes(s)).collect::<Result<_, _>>()?;
+    Ok(Round1State(scalars))
+}
+
+/// `i * G`: distinct public keys for tests that run without key
+/// generation, which `verify-only` leaves out. `i` must not be zero.
+#[cfg(test)]
+pub(crate) fn nth_point(i: u64) -> Secp256k1Point {
+    let mut bytes = [0u8; SCALAR_LEN];
+    bytes[SCALAR_LEN - 8..].copy_from_slice(&i.to_be_bytes());
+    Secp256k1Point::generator() * &scalar_from_bytes(&bytes).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "signing")]
+    use crate::parse::hex_any;
+    #[cfg(feature = "signing")]
+    use crate::treemusig::{setup, sign, tree_verify};
+    #[cfg(feature = "signing")]
+    use nested_musig2::params::Params;
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signature_round_trip_still_verifies() {
+        let (tree, mut state_map) = setup(4);
+        let msg = b"round trip";
+        let sig = sign(&tree, &mut state_map, msg, &Params::default()).unwrap();
+        let root = &tree.get(tree.root()).value;
+
+        let sig_hex = signature_hex(&sig);
+        let root_hex = point_hex(root);
+        assert_eq!(sig_hex.len(), 2 * SIGNATURE_LEN);
+        assert_eq!(root_hex.len(), 2 * POINT_LEN);
+
+        let parsed_sig = signature_from_bytes(&hex_any(&sig_hex).unwrap()).unwrap();
+        let parsed_root = point_from_bytes(&hex_any(&root_hex).unwrap()).unwrap();
+        assert!(parsed_root == *root);
+        assert!(tree_verify(&parsed_root, msg, &parsed_sig));
+    }
+
+    #[test]
+    fn rejects_wrong_lengths_and_bad_points() {
+        assert_eq!(
+            signature_from_bytes(&[0u8; 64]).unwrap_err(),
+            DecodeError::WrongLength { expected: 65, got: 64 }
+        );
+        assert_eq!(point_from_bytes(&[0u8; 32]).unwrap_err(), DecodeError::WrongLength { expected: 33, got: 32 });
+        // 0x05 is not a compressed-point prefix
+        assert_eq!(point_from_bytes(&[5u8; 33]).unwrap_err(), DecodeError::InvalidPoint);
+    }
+
+    #[test]
+    fn round1_round_trip() {
+        let (out, state) = nested_musig2::round1::sign_round1(2).unwrap();
+        let out_bytes = round1_out_to_bytes(&out);
+        let state_bytes = round1_state_to_bytes(&state);
+        assert_eq!(out_bytes.len(), 2);
+        assert_eq!(round1_out_to_bytes(&round1_out_from_bytes(&out_bytes).unwrap()), out_bytes);
+        assert_eq!(round1_state_to_bytes(&round1_state_from_bytes(&state_bytes).unwrap()), state_bytes);
+        assert_eq!(round1_out_from_bytes(&[vec![5u8; 33]]).unwrap_err(), DecodeError::InvalidPoint);
+    }
+}
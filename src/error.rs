@@ -0,0 +1,163 @@
+use crypto_rs::secp256k1::Secp256k1Point;
+use std::fmt;
+
+#[cfg(feature = "signing")]
+use crate::audit::{AuditReport, Phase};
+use crate::bintree::{LevelsError, ValidationError};
+use crate::encoding::{point_fingerprint, point_hex};
+
+#[derive(Debug)]
+pub enum Error {
+    EmptyInput,
+    /// The key appears more than once among those a tree is built from.
+    DuplicateKey(Secp256k1Point),
+    /// No state entry exists for this node's key.
+    MissingNodeState(Secp256k1Point),
+    /// The entry exists but a field the current round needs was never set.
+    IncompleteNodeState {
+        pubkey: Secp256k1Point,
+        field: &'static str,
+    },
+    /// A round was run a second time on a node that already completed it.
+    RoundRepeated {
+        pubkey: Secp256k1Point,
+        round: u8,
+    },
+    Round1Failed(String),
+    Round2Failed(String),
+    AggregationFailed(String),
+    /// Round 2 failed at this node of the key tree (an `IndexedTree`
+    /// index): a leaf's own signature, or combining a node's children.
+    Round2FailedAt { node: usize, error: Box<Error> },
+    /// No leaf at this position in the key tree.
+    UnknownSigner(usize),
+    /// No node at this index in the key tree.
+    UnknownNode(usize),
+    /// The key is not at any leaf of the key tree.
+    NotALeaf(Secp256k1Point),
+    /// The signer at this leaf position sent a round 1 output of the wrong
+    /// shape.
+    MalformedRound1(usize),
+    /// The key tree has more levels than allowed: `MAX_TREE_HEIGHT` when
+    /// signing, or the cap in `KeyTreeOptions` when building or importing.
+    /// `deepest` is the leaf at the bottom of the longest path.
+    TreeTooDeep { height: usize, max: usize, deepest: Secp256k1Point },
+    /// A stored aggregate key does not match its children.
+    InvalidKeyTree(ValidationError),
+    /// An imported key tree's levels do not fit together.
+    TreeLevels(LevelsError),
+    /// The BIP341 tweak hash is not below the curve order.
+    InvalidTweak,
+    /// A k-ary key tree was asked for fewer than two children per node.
+    InvalidArity(usize),
+    /// The signing state no longer matches the key tree after `phase`.
+    #[cfg(feature = "signing")]
+    StateAudit { phase: Phase, report: AuditReport },
+    /// No node of the key tree has this key.
+    NotANode(Secp256k1Point),
+    /// The signers for a node are not exactly the leaves under it: these
+    /// leaves have no signer, and these signers are not under the node.
+    SignerMismatch { missing: Vec<Secp256k1Point>, extra: Vec<Secp256k1Point> },
+    /// A signature just made does not verify under this key.
+    Unverified(Secp256k1Point),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyInput => write!(f, "cannot build a key tree from zero keys"),
+            Error::DuplicateKey(pk) => write!(f, "{} appears more than once among the signers' keys", point_hex(pk)),
+            Error::MissingNodeState(pk) => write!(f, "no signing state for key {} in the key tree", point_fingerprint(pk)),
+            Error::IncompleteNodeState { pubkey, field } => {
+                write!(f, "node state for key {} is missing `{}`", point_fingerprint(pubkey), field)
+            }
+            Error::RoundRepeated { pubkey, round } => {
+                write!(f, "round {} already ran for key {} in the key tree", round, point_fingerprint(pubkey))
+            }
+            Error::Round1Failed(e) => write!(f, "round 1 failed: {}", e),
+            Error::Round2Failed(e) => write!(f, "round 2 failed: {}", e),
+            Error::AggregationFailed(e) => write!(f, "aggregation failed: {}", e),
+            Error::Round2FailedAt { node, error } => write!(f, "round 2 failed at node {} of the key tree: {}", node, error),
+            Error::UnknownSigner(position) => write!(f, "no signer at leaf position {}", position),
+            Error::UnknownNode(idx) => write!(f, "no node at index {} in the key tree", idx),
+            Error::NotALeaf(pk) => write!(f, "{} is not a leaf of the key tree", point_hex(pk)),
+            Error::MalformedRound1(position) => {
+                write!(f, "signer at leaf position {} sent a malformed round 1 output", position)
+            }
+            Error::TreeTooDeep { height, max, deepest } => {
+                write!(f, "key tree has {} levels but at most {} are supported", height, max)?;
+                let balanced = u32::try_from(*max).ok().and_then(|max| 1u64.checked_shl(max.checked_sub(1)?));
+                if let Some(signers) = balanced {
+                    write!(f, ", i.e. up to {} signers in a balanced tree", signers)?;
+                }
+                write!(f, "; the deepest leaf is {}", point_fingerprint(deepest))
+            }
+            Error::InvalidKeyTree(e) => write!(f, "invalid key tree: {}", e),
+            Error::TreeLevels(e) => write!(f, "malformed key tree: {}", e),
+            Error::InvalidTweak => write!(f, "taproot tweak is out of range"),
+            Error::InvalidArity(arity) => write!(f, "a key tree node needs room for at least two children, not {}", arity),
+            #[cfg(feature = "signing")]
+            Error::StateAudit { phase, report } => write!(f, "state audit after {:?} failed:\n{}", phase, report),
+            Error::NotANode(pk) => write!(f, "{} is not a node of the key tree", point_hex(pk)),
+            Error::SignerMismatch { missing, extra } => {
+                let list = |keys: &[Secp256k1Point]| keys.iter().map(point_fingerprint).collect::<Vec<_>>().join(", ");
+                write!(
+                    f,
+                    "signers do not match the leaves under the node: {} missing ({}), {} not under it ({})",
+                    missing.len(),
+                    list(missing),
+                    extra.len(),
+                    list(extra)
+                )
+            }
+            Error::Unverified(pk) => write!(f, "signature does not verify under {}", point_hex(pk)),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Round2FailedAt { error, .. } => Some(error.as_ref()),
+            Error::InvalidKeyTree(e) => Some(e),
+            Error::TreeLevels(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        Error::InvalidKeyTree(e)
+    }
+}
+
+impl From<LevelsError> for Error {
+    fn from(e: LevelsError) -> Self {
+        Error::TreeLevels(e)
+    }
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::network::NetworkError;
+
+    #[test]
+    fn source_chain_leads_to_the_failing_node() {
+        let pk = Keypair::generate().pk;
+        let err = NetworkError::Coordinator(Error::Round2FailedAt {
+            node: 3,
+            error: Box::new(Error::RoundRepeated { pubkey: pk.clone(), round: 2 }),
+        });
+
+        let chain: Vec<&dyn std::error::Error> = std::iter::successors(Some(&err as &dyn std::error::Error), |e| e.source()).collect();
+        assert_eq!(chain.len(), 3);
+        assert!(matches!(chain[1].downcast_ref::<Error>(), Some(Error::Round2FailedAt { node: 3, .. })));
+        let innermost = chain[2].downcast_ref::<Error>().unwrap();
+        assert!(matches!(innermost, Error::RoundRepeated { round: 2, .. }));
+        assert!(innermost.to_string().contains(&point_fingerprint(&pk)));
+        assert!(err.to_string().contains(&point_fingerprint(&pk)));
+    }
+}
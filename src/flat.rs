@@ -0,0 +1,96 @@
+//! Plain n-of-n MuSig2: one aggregate key over every signer and a single
+//! round of aggregation, with no tree. It signs with the same key material
+//! as `treemusig`, as a baseline for what the tree construction costs.
+//!
+//! A flat signature is for `flat_key`, not for the tree's root key, and is
+//! checked with the same `ver` as a tree signature.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{keyagg::key_agg, params::Params, round1::{sign_agg, sign_round1}, round2::{sign_agg_prime, sign_prime}};
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::secret::{SecretNonces, SecretScalar};
+use crate::treemusig::{NONCES, Signature};
+
+/// The aggregate of every key in `pubkeys`, all at one level.
+pub fn flat_key(pubkeys: &[Secp256k1Point], params: &Params) -> Result<Secp256k1Point, Error> {
+    if pubkeys.is_empty() {
+        return Err(Error::EmptyInput);
+    }
+    key_agg(params, pubkeys).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))
+}
+
+/// Signs `msg` for `flat_key(pubkeys)`; `secret_keys` must hold the secret
+/// for every key. A key listed twice signs twice, as a tree leaf would.
+pub fn flat_sign(
+    pubkeys: &[Secp256k1Point],
+    secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    msg: &[u8],
+    params: &Params,
+) -> Result<Signature, Error> {
+    if pubkeys.is_empty() {
+        return Err(Error::EmptyInput);
+    }
+    let mut outs = Vec::with_capacity(pubkeys.len());
+    let mut signers = Vec::with_capacity(pubkeys.len());
+    for (i, pk) in pubkeys.iter().enumerate() {
+        let sk = secret_keys.get(pk).ok_or_else(|| Error::MissingNodeState(pk.clone()))?;
+        let (out, state) = sign_round1(NONCES).map_err(|e| Error::Round1Failed(format!("{:?}", e)))?;
+        outs.push(out);
+        signers.push((i, SecretScalar::new(sk.clone()), SecretNonces::new(state)));
+    }
+    let out = sign_agg(&outs).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))?;
+    let outs_by_depth = [out];
+
+    // Each signer's one merkle level is everyone else's key.
+    let sign = |(i, sk, nonces): (usize, SecretScalar, SecretNonces)| {
+        let others: Vec<_> = pubkeys.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, pk)| pk.clone()).collect();
+        sign_prime(params, nonces.into_inner(), &outs_by_depth, sk.expose(), msg, &vec![others])
+            .map_err(|e| Error::Round2Failed(format!("{:?}", e)))
+    };
+    #[cfg(feature = "parallel")]
+    let parts: Vec<_> = {
+        use rayon::prelude::*;
+        signers.into_par_iter().map(sign).collect::<Result<_, _>>()?
+    };
+    #[cfg(not(feature = "parallel"))]
+    let parts: Vec<_> = signers.into_iter().map(sign).collect::<Result<_, _>>()?;
+
+    sign_agg_prime(&parts).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::tree_verify_with;
+
+    fn keys(n: usize) -> (Vec<Secp256k1Point>, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+        let keys: Vec<_> = (0..n).map(|_| Keypair::generate()).collect();
+        let pubkeys = keys.iter().map(|kp| kp.pk.clone()).collect();
+        (pubkeys, keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect())
+    }
+
+    #[test]
+    fn flat_signatures_verify_under_the_flat_key() {
+        let params = Params::default();
+        for n in [1, 2, 3, 8] {
+            let (pubkeys, secret_keys) = keys(n);
+            let key = flat_key(&pubkeys, &params).unwrap();
+            let sig = flat_sign(&pubkeys, &secret_keys, b"flat", &params).unwrap();
+            assert!(tree_verify_with(&key, b"flat", &sig, &params), "n = {}", n);
+            assert!(!tree_verify_with(&key, b"flaT", &sig, &params), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn missing_secret_and_empty_input_are_errors() {
+        let params = Params::default();
+        let (pubkeys, mut secret_keys) = keys(3);
+        secret_keys.remove(&pubkeys[1]);
+        assert!(matches!(flat_sign(&pubkeys, &secret_keys, b"flat", &params), Err(Error::MissingNodeState(_))));
+        assert!(matches!(flat_key(&[], &params), Err(Error::EmptyInput)));
+        assert!(matches!(flat_sign(&[], &secret_keys, b"flat", &params), Err(Error::EmptyInput)));
+    }
+}
@@ -0,0 +1,134 @@
+//! The build/sign/verify flow driven by arbitrary bytes, shared by the
+//! cargo-fuzz target in `fuzz/` and the proptest harness below, which runs
+//! the same flow on stable.
+//!
+//! Input layout, with missing bytes read as zero:
+//!
+//! | bytes      | meaning                                                   |
+//! |------------|-----------------------------------------------------------|
+//! | 0          | leaf count, `1 + b % 64`                                  |
+//! | 1..9       | seed for the keypairs                                     |
+//! | 9          | flags: bit 0 withholds the first leaf's secret key        |
+//! | 10..10+n   | for each leaf, which of the `n` keypairs sits there       |
+//! | rest       | the message                                               |
+
+use nested_musig2::params::Params;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::collections::HashMap;
+
+use crate::coordinator::Coordinator;
+use crate::error::Error;
+use crate::keys::Keypair;
+use crate::signer::signers_for_tree;
+use crate::treemusig::{KeyTreeOptions, build_key_tree_opts, tree_sign, tree_verify};
+
+pub const MAX_LEAVES: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct FuzzCase {
+    pub keys: Vec<Keypair>,
+    /// Index into `keys` for each leaf, so keys can repeat or go unused.
+    pub leaves: Vec<usize>,
+    pub withhold_first: bool,
+    pub msg: Vec<u8>,
+}
+
+impl FuzzCase {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+        let n = 1 + byte(0) as usize % MAX_LEAVES;
+        let mut seed = [0u8; 8];
+        for (i, b) in seed.iter_mut().enumerate() {
+            *b = byte(1 + i);
+        }
+        let mut rng = ChaCha20Rng::seed_from_u64(u64::from_be_bytes(seed));
+        let keys = (0..n).map(|_| Keypair::from_rng(&mut rng)).collect();
+        let leaves = (0..n).map(|i| byte(10 + i) as usize % n).collect();
+        FuzzCase {
+            keys,
+            leaves,
+            withhold_first: byte(9) & 1 == 1,
+            msg: data.get(10 + n..).unwrap_or_default().to_vec(),
+        }
+    }
+
+    /// Builds the tree and signs both in-process and through separate
+    /// signers. `Ok(false)` means a signature failed to verify, which is
+    /// always a bug; a missing secret must surface as an `Err`.
+    pub fn run(&self) -> Result<bool, Error> {
+        let leaf_keys: Vec<&Keypair> = self.leaves.iter().map(|&i| &self.keys[i]).collect();
+        let tree = build_key_tree_opts(leaf_keys.iter().map(|kp| kp.pk.clone()).collect(), KeyTreeOptions { allow_duplicates: true, ..KeyTreeOptions::default() }, &Params::default())?;
+        let mut secret_keys: HashMap<_, _> = leaf_keys.iter().map(|kp| (kp.pk.clone(), kp.sk.clone())).collect();
+        if self.withhold_first {
+            secret_keys.remove(&leaf_keys[0].pk);
+        }
+        let sig = tree_sign(&tree, &secret_keys, &self.msg)?;
+        if !tree_verify(tree.value(), &self.msg, &sig) {
+            return Ok(false);
+        }
+
+        let mut signers = signers_for_tree(&tree, leaf_keys.into_iter().cloned().collect(), &Params::default())?;
+        let mut coordinator = Coordinator::new(&tree);
+        for signer in &mut signers {
+            coordinator.add_round1(signer.position(), signer.round1()?)?;
+        }
+        coordinator.aggregate_round1()?;
+        for signer in &mut signers {
+            let (outs_by_depth, merkle_path) = coordinator.round2_inputs(signer.position())?;
+            coordinator.add_round2(signer.position(), signer.round2(&outs_by_depth, &self.msg, &merkle_path)?)?;
+        }
+        let sig = coordinator.aggregate_round2()?;
+        Ok(tree_verify(tree.value(), &self.msg, &sig))
+    }
+}
+
+/// The fuzz target body: panics only when a signature fails to verify.
+pub fn check(data: &[u8]) {
+    let case = FuzzCase::from_bytes(data);
+    if let Ok(verified) = case.run() {
+        assert!(verified, "signature failed to verify for {} leaves", case.leaves.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn corpus_regressions() {
+        // Three leaves: the odd split that used to panic in round 2.
+        let odd = include_bytes!("../fuzz/corpus/sign_flow/odd-n");
+        let case = FuzzCase::from_bytes(odd);
+        assert_eq!(case.leaves.len(), 3);
+        assert!(case.run().unwrap());
+    }
+
+    #[test]
+    fn withheld_secret_is_a_typed_error() {
+        // four leaves, seed 0, first secret withheld, leaves 0..4
+        let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 2, 3];
+        assert!(matches!(FuzzCase::from_bytes(&data).run(), Err(Error::MissingNodeState(_))));
+        data[9] = 0;
+        assert!(FuzzCase::from_bytes(&data).run().unwrap());
+    }
+
+    #[test]
+    fn short_inputs_are_zero_padded() {
+        let case = FuzzCase::from_bytes(&[]);
+        assert_eq!(case.leaves, vec![0]);
+        assert!(case.msg.is_empty());
+        check(&[]);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        // Property 1: any input signs and verifies or fails with an `Error`.
+        #[test]
+        fn prop_flow_never_panics(data in proptest::collection::vec(any::<u8>(), 0..128)) {
+            check(&data);
+        }
+    }
+}
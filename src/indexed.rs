@@ -0,0 +1,323 @@
+//! Arena-backed tree with parent links, for walking from a node back up to
+//! the root. Nodes are stored in preorder, so the root is index 0 and every
+//! child has a larger index than its parent.
+
+use crate::bintree::BinTree;
+
+/// Which side of its parent a node sits on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeEntry<T> {
+    pub value: T,
+    pub parent: Option<usize>,
+    pub left: Option<usize>,
+    /// `None` for leaves and for single-child nodes.
+    pub right: Option<usize>,
+}
+
+impl<T> NodeEntry<T> {
+    pub fn is_leaf(&self) -> bool {
+        self.left.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedTree<T> {
+    nodes: Vec<NodeEntry<T>>,
+}
+
+impl<T: Clone> IndexedTree<T> {
+    pub fn from_tree(tree: &BinTree<T>) -> Self {
+        let mut nodes: Vec<NodeEntry<T>> = Vec::new();
+        // (node, parent index, is the parent's right child)
+        let mut stack = vec![(tree, None, false)];
+        while let Some((node, parent, is_right)) = stack.pop() {
+            let idx = nodes.len();
+            nodes.push(NodeEntry {
+                value: node.value().clone(),
+                parent,
+                left: None,
+                right: None,
+            });
+            if let Some(parent) = parent {
+                let entry: &mut NodeEntry<T> = &mut nodes[parent];
+                if is_right {
+                    entry.right = Some(idx);
+                } else {
+                    entry.left = Some(idx);
+                }
+            }
+            if let BinTree::Node { left, right, value: _, .. } = node {
+                if let Some(right) = right {
+                    stack.push((right, Some(idx), true));
+                }
+                stack.push((left, Some(idx), false));
+            }
+        }
+        Self { nodes }
+    }
+
+    pub fn to_tree(&self) -> BinTree<T> {
+        self.subtree(self.root())
+    }
+
+    /// The subtree rooted at `idx` as a standalone tree.
+    pub fn subtree(&self, root: usize) -> BinTree<T> {
+        // A subtree is a contiguous run of the preorder, ending at its
+        // rightmost leaf.
+        let mut last = root;
+        while let Some(child) = self.nodes[last].right.or(self.nodes[last].left) {
+            last = child;
+        }
+        // Children come after their parent, so building back to front always
+        // finds both subtrees ready.
+        let mut built: Vec<Option<BinTree<T>>> = vec![None; last + 1 - root];
+        for idx in (root..=last).rev() {
+            let entry = &self.nodes[idx];
+            let mut take = |child: usize| built[child - root].take().expect("child built before parent");
+            let tree = match (entry.left, entry.right) {
+                (None, _) => BinTree::leaf(entry.value.clone()),
+                (Some(left), None) => BinTree::from_parts(take(left), None, entry.value.clone()),
+                (Some(left), Some(right)) => {
+                    let left = take(left);
+                    BinTree::node(left, take(right), entry.value.clone())
+                }
+            };
+            built[idx - root] = Some(tree);
+        }
+        built[0].take().expect("tree has a root")
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Panics if `idx` is out of range, like slice indexing.
+    pub fn get(&self, idx: usize) -> &NodeEntry<T> {
+        &self.nodes[idx]
+    }
+
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        self.nodes[idx].parent
+    }
+
+    /// The other child of `idx`'s parent; `None` for the root and for the
+    /// child of a single-child node.
+    pub fn sibling(&self, idx: usize) -> Option<usize> {
+        let parent = &self.nodes[self.parent(idx)?];
+        if parent.left == Some(idx) { parent.right } else { parent.left }
+    }
+
+    /// Which side of its parent `idx` is on; `None` for the root.
+    pub fn side(&self, idx: usize) -> Option<Side> {
+        let parent = &self.nodes[self.parent(idx)?];
+        Some(if parent.right == Some(idx) { Side::Right } else { Side::Left })
+    }
+
+    /// Depth of every node by index; the root is at depth 0.
+    pub fn depths(&self) -> Vec<usize> {
+        let mut depths = vec![0; self.nodes.len()];
+        // parents come before their children
+        for idx in 1..self.nodes.len() {
+            depths[idx] = self.parent(idx).map_or(0, |parent| depths[parent] + 1);
+        }
+        depths
+    }
+
+    /// Levels in the tree, leaves included, like `BinTree::height`.
+    pub fn height(&self) -> usize {
+        self.depths().into_iter().max().map_or(0, |depth| depth + 1)
+    }
+
+    /// Index of the leftmost of the leaves furthest from the root.
+    pub fn deepest_leaf(&self) -> usize {
+        let depths = self.depths();
+        // preorder visits leaves left to right, so the first maximum wins
+        self.leaf_indices().fold(self.root(), |deepest, idx| if depths[idx] > depths[deepest] { idx } else { deepest })
+    }
+
+    /// `idx`, its parent, and so on up to and including the root.
+    pub fn path_to_root(&self, idx: usize) -> Vec<usize> {
+        std::iter::successors(Some(idx), |&i| self.parent(i)).collect()
+    }
+
+    /// Indices of the leaves, left to right.
+    pub fn leaf_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&idx| self.nodes[idx].is_leaf())
+    }
+
+    /// Index of the first leaf, in preorder, equal to `target`.
+    pub fn find_leaf(&self, target: &T) -> Option<usize>
+    where
+        T: PartialEq,
+    {
+        self.nodes.iter().position(|entry| entry.is_leaf() && entry.value == *target)
+    }
+
+    /// Same result as `BinTree::merkle_path`, read off the parent links.
+    pub fn merkle_path(&self, target: &T) -> Option<Vec<T>>
+    where
+        T: PartialEq,
+    {
+        Some(self.merkle_path_at(self.find_leaf(target)?))
+    }
+
+    /// The sibling values from `idx` up to the root, nearest first. Unlike
+    /// `merkle_path`, tells apart leaves that hold equal values.
+    pub fn merkle_path_at(&self, idx: usize) -> Vec<T> {
+        self.sided_path_at(idx).into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// `merkle_path_at` with the side each sibling sits on, which is what
+    /// recombining the path with a non-commutative function needs.
+    pub fn sided_path_at(&self, idx: usize) -> Vec<(Side, T)> {
+        self.path_to_root(idx)
+            .into_iter()
+            .filter_map(|idx| self.sibling(idx))
+            .map(|sibling| (self.side(sibling).expect("a sibling has a parent"), self.nodes[sibling].value.clone()))
+            .collect()
+    }
+}
+
+impl<T: Clone> From<&BinTree<T>> for IndexedTree<T> {
+    fn from(tree: &BinTree<T>) -> Self {
+        Self::from_tree(tree)
+    }
+}
+
+impl<T: Clone> From<&IndexedTree<T>> for BinTree<T> {
+    fn from(tree: &IndexedTree<T>) -> Self {
+        tree.to_tree()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bintree::TreeShape;
+    use proptest::prelude::*;
+
+    fn add(x: u32, y: u32) -> u32 {
+        x.saturating_add(y)
+    }
+
+    #[test]
+    fn layout_is_preorder_with_links() {
+        // 6 -> (3 -> (1, 2), single-child 3 -> 3)
+        let t = BinTree::from_vec(vec![1u32, 2, 3], add);
+        let idx = IndexedTree::from_tree(&t);
+        let values: Vec<u32> = (0..idx.node_count()).map(|i| idx.get(i).value).collect();
+        assert_eq!(values, t.iter_preorder().copied().collect::<Vec<_>>());
+
+        assert_eq!(idx.root(), 0);
+        assert_eq!(idx.parent(0), None);
+        assert_eq!(idx.parent(2), Some(1));
+        assert_eq!(idx.sibling(2), Some(3));
+        assert_eq!(idx.sibling(1), Some(4));
+        // the leaf under the single-child node has no sibling
+        assert_eq!(idx.sibling(5), None);
+        assert_eq!(idx.path_to_root(5), vec![5, 4, 0]);
+        assert_eq!(idx.path_to_root(0), vec![0]);
+    }
+
+    #[test]
+    fn single_leaf() {
+        let t = BinTree::leaf(7u32);
+        let idx = IndexedTree::from(&t);
+        assert_eq!(idx.node_count(), 1);
+        assert!(idx.get(0).is_leaf());
+        assert_eq!(idx.merkle_path(&7), Some(vec![]));
+        assert_eq!(BinTree::from(&idx), t);
+    }
+
+    #[test]
+    fn merkle_path_misses_internal_values() {
+        let t = BinTree::from_vec(vec![1u32, 2, 4, 8, 5], add);
+        let idx = IndexedTree::from_tree(&t);
+        assert_eq!(idx.merkle_path(&12), None);
+        assert_eq!(idx.merkle_path(&99), None);
+        assert_eq!(idx.merkle_path(&1), t.merkle_path(&1));
+        assert_eq!(idx.merkle_path(&5), Some(vec![15]));
+    }
+
+    #[test]
+    fn subtrees_and_sides() {
+        let t = BinTree::from_vec(vec![1u32, 2, 4, 8, 5], add);
+        let idx = IndexedTree::from_tree(&t);
+        assert_eq!(idx.subtree(1), BinTree::from_vec(vec![1u32, 2, 4, 8], add));
+        assert_eq!(idx.subtree(2), BinTree::from_vec(vec![1u32, 2], add));
+        assert_eq!(idx.subtree(3), BinTree::leaf(1));
+        assert_eq!(idx.subtree(0), t);
+        for (i, depth) in idx.depths().into_iter().enumerate() {
+            assert_eq!(depth, idx.path_to_root(i).len() - 1);
+        }
+        assert_eq!(idx.height(), t.height());
+
+        assert_eq!(idx.side(0), None);
+        assert_eq!(idx.side(2), Some(Side::Left));
+        assert_eq!(idx.side(5), Some(Side::Right));
+        // leaf 2: sibling 1 on the left, then 12 on the right, then 5's
+        // branch on the right
+        assert_eq!(idx.sided_path_at(4), vec![(Side::Left, 1), (Side::Right, 12), (Side::Right, 5)]);
+    }
+
+    #[test]
+    fn equal_leaves_keep_their_own_paths() {
+        let t = BinTree::from_vec(vec![1u32, 2, 1, 4], add);
+        let idx = IndexedTree::from_tree(&t);
+        let leaves: Vec<usize> = idx.leaf_indices().collect();
+        assert_eq!(leaves, vec![2, 3, 5, 6]);
+        assert_eq!(idx.merkle_path_at(leaves[0]), vec![2, 5]);
+        assert_eq!(idx.merkle_path_at(leaves[2]), vec![4, 3]);
+        // lookup by value only ever finds the first one
+        assert_eq!(idx.merkle_path(&1), Some(idx.merkle_path_at(leaves[0])));
+    }
+
+    proptest! {
+        // Property 1: BinTree -> IndexedTree -> BinTree is the identity.
+        #[test]
+        fn prop_round_trip(xs in proptest::collection::vec(any::<u32>(), 1..256)) {
+            let t = BinTree::from_vec(xs, add);
+            let idx = IndexedTree::from_tree(&t);
+            prop_assert_eq!(idx.node_count(), t.iter_preorder().count());
+            prop_assert_eq!(idx.to_tree(), t);
+        }
+
+        // Property 3: the subtree at any index is the contiguous run of the
+        // preorder that starts there, and its leaves are the arena leaves in
+        // that run.
+        #[test]
+        fn prop_subtree_is_preorder_run(xs in proptest::collection::vec(any::<u32>(), 1..64)) {
+            let t = BinTree::from_vec(xs, add);
+            let idx = IndexedTree::from_tree(&t);
+            for i in 0..idx.node_count() {
+                let sub: Vec<u32> = idx.subtree(i).iter_preorder().copied().collect();
+                let run: Vec<u32> = (i..i + sub.len()).map(|j| idx.get(j).value).collect();
+                prop_assert_eq!(&sub, &run);
+                let leaves = (i..i + sub.len()).filter(|&j| idx.get(j).is_leaf()).count();
+                prop_assert_eq!(idx.subtree(i).leaf_count(), leaves);
+            }
+        }
+
+        // Property 2: merkle paths agree with the recursive version, for
+        // balanced and list-shaped trees alike.
+        #[test]
+        fn prop_merkle_path_matches(xs in proptest::collection::vec(any::<u32>(), 1..128), list in any::<bool>()) {
+            let shape = if list { TreeShape::LeftList } else { TreeShape::Balanced };
+            let t = BinTree::from_vec_shaped(xs.clone(), shape, add);
+            let idx = IndexedTree::from_tree(&t);
+            for x in &xs {
+                prop_assert_eq!(idx.merkle_path(x), t.merkle_path(x));
+            }
+        }
+    }
+}
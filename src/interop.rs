@@ -0,0 +1,67 @@
+//! Checks tree signatures with a BIP340 verifier that shares no code with
+//! `nested_musig2`, so passing `tree_verify` is not the only evidence that
+//! a signature is valid.
+//!
+//! A signature is put in BIP340 form as `x(R) || s`, and the root key as
+//! `x(X)`. BIP340 reads both x-only values as the points with even y.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use k256::schnorr::{Signature as SchnorrSignature, VerifyingKey};
+
+use crate::encoding::scalar_to_bytes;
+use crate::taproot::x_only;
+use crate::treemusig::Signature;
+
+/// The 64-byte BIP340 encoding of `sig`.
+pub fn bip340_signature(sig: &Signature) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&x_only(&sig.0));
+    bytes[32..].copy_from_slice(&scalar_to_bytes(&sig.1));
+    bytes
+}
+
+/// Verifies `sig` on `msg` for `root_pk` with `k256`'s BIP340 verifier.
+/// `msg` is passed as is, like `tree_verify` does, rather than hashed.
+pub fn verify_external(root_pk: &Secp256k1Point, msg: &[u8], sig: &Signature) -> bool {
+    let Ok(key) = VerifyingKey::from_bytes(&x_only(root_pk)) else {
+        return false;
+    };
+    let Ok(sig) = SchnorrSignature::try_from(&bip340_signature(sig)[..]) else {
+        return false;
+    };
+    key.verify_raw(msg, &sig).is_ok()
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::{build_key_tree, tree_sign, tree_verify};
+    use std::collections::HashMap;
+
+    #[test]
+    fn external_verifier_accepts_tree_signatures() {
+        for n in [1, 2, 5, 8] {
+            let keys: Vec<_> = (0..n).map(|_| Keypair::generate()).collect();
+            let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+            let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+            let msg = [0x42u8; 32];
+            let sig = tree_sign(&tree, &secret_keys, &msg).unwrap();
+            assert!(tree_verify(tree.value(), &msg, &sig));
+            assert!(verify_external(tree.value(), &msg, &sig), "n = {}", n);
+
+            let mut flipped = msg;
+            flipped[0] ^= 1;
+            assert!(!verify_external(tree.value(), &flipped, &sig), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn encoding_is_x_only_r_then_s() {
+        let kp = Keypair::generate();
+        let sig = (kp.pk.clone(), kp.sk.clone());
+        let bytes = bip340_signature(&sig);
+        assert_eq!(bytes[..32], x_only(&kp.pk));
+        assert_eq!(bytes[32..], scalar_to_bytes(&kp.sk)[..]);
+    }
+}
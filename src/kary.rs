@@ -0,0 +1,196 @@
+//! Nested MuSig2 over a `MultiTree` of keys, where a node aggregates up to
+//! `arity` children at once. Each level of a signer's merkle path holds all
+//! of its siblings there rather than one, and each node's round 1 outputs
+//! are combined in a single `sign_agg`. `treemusig` remains the binary
+//! case; both produce signatures checked by the same `ver`.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{keyagg::key_agg, params::Params, round1::{Round1Out, sign_agg, sign_agg_ext, sign_round1}, round2::{sign_agg_prime, sign_prime}};
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::multitree::MultiTree;
+use crate::secret::{SecretNonces, SecretScalar};
+use crate::treemusig::{KeyTreeOptions, MAX_TREE_HEIGHT, NONCES, Round2Inputs, Signature, check_duplicates, from_build_error};
+
+/// Groups `pubkeys` `arity` at a time into a key tree whose root is the
+/// signing key. With `arity` 2 this is `build_key_tree`'s tree, and like
+/// it each key may appear only once.
+pub fn build_kary_key_tree(pubkeys: Vec<Secp256k1Point>, arity: usize) -> Result<MultiTree<Secp256k1Point>, Error> {
+    build_kary_key_tree_with(pubkeys, arity, &Params::default())
+}
+
+pub fn build_kary_key_tree_with(pubkeys: Vec<Secp256k1Point>, arity: usize, params: &Params) -> Result<MultiTree<Secp256k1Point>, Error> {
+    check_duplicates(&pubkeys, KeyTreeOptions::default())?;
+    MultiTree::try_from_vec(pubkeys, arity, |keys| {
+        key_agg(params, keys).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))
+    })
+    .map_err(from_build_error)
+}
+
+pub fn kary_tree_sign(tree: &MultiTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>, msg: &[u8]) -> Result<Signature, Error> {
+    kary_tree_sign_with(tree, secret_keys, msg, &Params::default())
+}
+
+/// Both rounds for every leaf of `tree`, which must have been built with
+/// `params`. Leaves draw nonces and sign independently (in parallel with the
+/// `parallel` feature); the aggregation in between walks the tree.
+pub fn kary_tree_sign_with(
+    tree: &MultiTree<Secp256k1Point>,
+    secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    msg: &[u8],
+    params: &Params,
+) -> Result<Signature, Error> {
+    let height = tree.height();
+    if height > MAX_TREE_HEIGHT {
+        return Err(Error::TreeTooDeep { height, max: MAX_TREE_HEIGHT, deepest: tree.deepest_leaf().clone() });
+    }
+    let secrets = tree
+        .leaves()
+        .map(|pk| secret_keys.get(pk).map(|sk| SecretScalar::new(sk.clone())).ok_or_else(|| Error::MissingNodeState(pk.clone())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let draw = |_| sign_round1(NONCES).map_err(|e| Error::Round1Failed(format!("{:?}", e)));
+    #[cfg(feature = "parallel")]
+    let drawn: Vec<_> = {
+        use rayon::prelude::*;
+        (0..secrets.len()).into_par_iter().map(draw).collect::<Result<_, _>>()?
+    };
+    #[cfg(not(feature = "parallel"))]
+    let drawn: Vec<_> = (0..secrets.len()).map(draw).collect::<Result<_, _>>()?;
+    let (outs, nonces): (Vec<_>, Vec<_>) = drawn.into_iter().map(|(out, state)| (out, SecretNonces::new(state))).unzip();
+
+    let round1 = aggregate_round1(tree, &mut outs.into_iter(), params)?;
+    let mut inputs = Vec::with_capacity(secrets.len());
+    collect_round2_inputs(tree, &round1, &mut Vec::new(), &mut Vec::new(), &mut inputs);
+
+    let sign = |((sk, nonces), (outs_by_depth, merkle_path)): ((SecretScalar, SecretNonces), Round2Inputs)| {
+        sign_prime(params, nonces.into_inner(), &outs_by_depth, sk.expose(), msg, &merkle_path)
+            .map_err(|e| Error::Round2Failed(format!("{:?}", e)))
+    };
+    let jobs: Vec<_> = secrets.into_iter().zip(nonces).zip(inputs).collect();
+    #[cfg(feature = "parallel")]
+    let parts: Vec<_> = {
+        use rayon::prelude::*;
+        jobs.into_par_iter().map(sign).collect::<Result<_, _>>()?
+    };
+    #[cfg(not(feature = "parallel"))]
+    let parts: Vec<_> = jobs.into_iter().map(sign).collect::<Result<_, _>>()?;
+
+    aggregate_round2(tree, &mut parts.into_iter())
+}
+
+/// Round 1 outputs in the key tree's shape. `out_internal` is set only on
+/// nodes with more than one child; a single-child node passes its child's
+/// `out` up unchanged.
+struct Round1Node {
+    out: Round1Out,
+    out_internal: Option<Round1Out>,
+    children: Vec<Round1Node>,
+}
+
+fn aggregate_round1(node: &MultiTree<Secp256k1Point>, leaf_outs: &mut impl Iterator<Item = Round1Out>, params: &Params) -> Result<Round1Node, Error> {
+    let MultiTree::Node { children, value } = node else {
+        let out = leaf_outs.next().expect("one round 1 output per leaf");
+        return Ok(Round1Node { out, out_internal: None, children: Vec::new() });
+    };
+    let children = children.iter().map(|child| aggregate_round1(child, leaf_outs, params)).collect::<Result<Vec<_>, _>>()?;
+    if let [only] = children.as_slice() {
+        let out = only.out.clone();
+        return Ok(Round1Node { out, out_internal: None, children });
+    }
+    let child_outs: Vec<Round1Out> = children.iter().map(|child| child.out.clone()).collect();
+    let out_internal = sign_agg(&child_outs).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))?;
+    let out = sign_agg_ext(params, &out_internal, value).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))?;
+    Ok(Round1Node { out, out_internal: Some(out_internal), children })
+}
+
+/// Pushes every leaf's round 2 inputs, left to right. `outs_by_depth` and
+/// `merkle_path` hold what the ancestors of `node` contribute.
+fn collect_round2_inputs(
+    node: &MultiTree<Secp256k1Point>,
+    round1: &Round1Node,
+    outs_by_depth: &mut Vec<Round1Out>,
+    merkle_path: &mut Vec<Vec<Secp256k1Point>>,
+    inputs: &mut Vec<Round2Inputs>,
+) {
+    let MultiTree::Node { children, .. } = node else {
+        inputs.push((outs_by_depth.clone(), merkle_path.clone()));
+        return;
+    };
+    let Some(out_internal) = &round1.out_internal else {
+        collect_round2_inputs(&children[0], &round1.children[0], outs_by_depth, merkle_path, inputs);
+        return;
+    };
+    outs_by_depth.push(out_internal.clone());
+    for (i, (child, child_round1)) in children.iter().zip(&round1.children).enumerate() {
+        let siblings = children.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, sibling)| sibling.value().clone()).collect();
+        merkle_path.push(siblings);
+        collect_round2_inputs(child, child_round1, outs_by_depth, merkle_path, inputs);
+        merkle_path.pop();
+    }
+    outs_by_depth.pop();
+}
+
+/// Combines the partial signatures, given left to right, up to the root.
+fn aggregate_round2(node: &MultiTree<Secp256k1Point>, leaf_parts: &mut impl Iterator<Item = Signature>) -> Result<Signature, Error> {
+    let MultiTree::Node { children, .. } = node else {
+        return Ok(leaf_parts.next().expect("one partial signature per leaf"));
+    };
+    let parts = children.iter().map(|child| aggregate_round2(child, leaf_parts)).collect::<Result<Vec<_>, _>>()?;
+    if let [only] = parts.as_slice() {
+        return Ok(only.clone());
+    }
+    sign_agg_prime(&parts).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::{build_key_tree, tree_verify};
+
+    fn keys(n: usize) -> (Vec<Secp256k1Point>, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+        let keys: Vec<_> = (0..n).map(|_| Keypair::generate()).collect();
+        let pubkeys = keys.iter().map(|kp| kp.pk.clone()).collect();
+        (pubkeys, keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect())
+    }
+
+    #[test]
+    fn wide_trees_sign_and_verify() {
+        for (n, arity) in [(1, 3), (3, 3), (9, 3), (10, 3), (7, 4), (5, 8)] {
+            let (pubkeys, secret_keys) = keys(n);
+            let tree = build_kary_key_tree(pubkeys, arity).unwrap();
+            let sig = kary_tree_sign(&tree, &secret_keys, b"wide").unwrap();
+            assert!(tree_verify(tree.value(), b"wide", &sig), "n = {}, arity = {}", n, arity);
+            assert!(!tree_verify(tree.value(), b"widE", &sig), "n = {}, arity = {}", n, arity);
+        }
+    }
+
+    #[test]
+    fn arity_two_matches_the_binary_tree() {
+        let (pubkeys, secret_keys) = keys(6);
+        let binary = build_key_tree(pubkeys.clone()).unwrap();
+        let tree = build_kary_key_tree(pubkeys, 2).unwrap();
+        assert_eq!(tree, MultiTree::from(binary.clone()));
+        let sig = kary_tree_sign(&tree, &secret_keys, b"binary").unwrap();
+        assert!(tree_verify(binary.value(), b"binary", &sig));
+    }
+
+    #[test]
+    fn missing_secret_is_an_error() {
+        let (pubkeys, mut secret_keys) = keys(4);
+        secret_keys.remove(&pubkeys[2]);
+        let tree = build_kary_key_tree(pubkeys, 3).unwrap();
+        assert!(matches!(kary_tree_sign(&tree, &secret_keys, b"wide"), Err(Error::MissingNodeState(_))));
+        assert!(matches!(build_kary_key_tree(Vec::new(), 3), Err(Error::EmptyInput)));
+    }
+
+    #[test]
+    fn arity_below_two_is_an_error() {
+        for arity in [0, 1] {
+            let (pubkeys, _) = keys(3);
+            assert!(matches!(build_kary_key_tree(pubkeys, arity), Err(Error::InvalidArity(a)) if a == arity));
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Key files: one hex-encoded 32-byte secret key per line. Blank lines are
+//! skipped; line numbers in errors are 1-based and count them.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use rand_core::{CryptoRng, OsRng, RngCore};
+use std::fmt;
+
+use crate::encoding::{DecodeError, SCALAR_LEN, scalar_from_bytes, scalar_to_bytes};
+use crate::parse::{ParseError, hex_exact_lenient, to_hex};
+
+#[derive(Debug, Clone)]
+pub struct Keypair {
+    pub pk: Secp256k1Point,
+    pub sk: Secp256k1Scalar,
+}
+
+impl Keypair {
+    pub fn from_secret(sk: Secp256k1Scalar) -> Self {
+        Keypair { pk: public_key(&sk), sk }
+    }
+
+    /// A keypair from the OS RNG. That is `getrandom`, which also has a
+    /// backend for wasm in the browser.
+    pub fn generate() -> Self {
+        Keypair::from_rng(&mut OsRng)
+    }
+
+    /// A keypair drawn from `rng`, so a seeded RNG gives reproducible keys.
+    /// Only long-term keys come from here: signing nonces are always drawn
+    /// fresh by `sign_round1`, and must never be derived from a seed.
+    pub fn from_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        loop {
+            let mut bytes = [0u8; SCALAR_LEN];
+            rng.fill_bytes(&mut bytes);
+            // Zero or >= the group order: draw again (odds ~2^-128).
+            if bytes == [0u8; SCALAR_LEN] {
+                continue;
+            }
+            if let Ok(sk) = scalar_from_bytes(&bytes) {
+                return Keypair::from_secret(sk);
+            }
+        }
+    }
+}
+
+/// `sk * G`.
+pub fn public_key(sk: &Secp256k1Scalar) -> Secp256k1Point {
+    Secp256k1Point::generator() * sk
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyLineError {
+    Hex(ParseError),
+    Scalar(DecodeError),
+    Zero,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyFileError {
+    pub line: usize,
+    pub error: KeyLineError,
+}
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.error {
+            KeyLineError::Hex(e) => write!(f, "{}", e),
+            KeyLineError::Scalar(e) => write!(f, "{}", e),
+            KeyLineError::Zero => write!(f, "secret key is zero"),
+        }
+    }
+}
+
+impl std::error::Error for KeyFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.error {
+            KeyLineError::Hex(e) => Some(e),
+            KeyLineError::Scalar(e) => Some(e),
+            KeyLineError::Zero => None,
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<Keypair, KeyLineError> {
+    let bytes = hex_exact_lenient::<SCALAR_LEN>(line).map_err(KeyLineError::Hex)?;
+    if bytes == [0u8; SCALAR_LEN] {
+        return Err(KeyLineError::Zero);
+    }
+    let sk = scalar_from_bytes(&bytes).map_err(KeyLineError::Scalar)?;
+    Ok(Keypair::from_secret(sk))
+}
+
+/// Stops at the first bad line.
+pub fn parse_secret_keys(text: &str) -> Result<Vec<Keypair>, KeyFileError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_line(line).map_err(|error| KeyFileError { line: i + 1, error }))
+        .collect()
+}
+
+pub fn format_secret_keys<'a>(keys: impl IntoIterator<Item = &'a Keypair>) -> String {
+    keys.into_iter()
+        .map(|kp| format!("{}\n", to_hex(&scalar_to_bytes(&kp.sk))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::treemusig::build_sorted_key_tree;
+
+    #[test]
+    fn derived_pubkeys_match_keygen() {
+        for _ in 0..4 {
+            let kp = Keypair::generate();
+            assert!(public_key(&kp.sk) == kp.pk);
+        }
+    }
+
+    #[test]
+    fn seeded_keys_are_reproducible() {
+        use rand_chacha::ChaCha20Rng;
+        use rand_core::SeedableRng;
+
+        let keys = |seed: u64| {
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            let keys: Vec<_> = (0..4).map(|_| Keypair::from_rng(&mut rng)).collect();
+            format_secret_keys(&keys)
+        };
+        assert_eq!(keys(7), keys(7));
+        assert_ne!(keys(7), keys(8));
+    }
+
+    #[test]
+    fn saved_keys_reproduce_root_key() {
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let text = format_secret_keys(&keys);
+        assert_eq!(text.lines().count(), 5);
+
+        let root = |keys: Vec<Keypair>| {
+            let tree = build_sorted_key_tree(keys.into_iter().map(|kp| kp.pk).collect()).unwrap();
+            tree.value().clone()
+        };
+        let first = root(parse_secret_keys(&text).unwrap());
+        let second = root(parse_secret_keys(&text).unwrap());
+        assert!(first == second);
+        assert!(first == root(keys));
+    }
+
+    #[test]
+    fn bad_lines_report_their_number() {
+        let good = to_hex(&[1u8; 32]);
+        let text = format!("{}\n\n{}\n{}\n", good, good, &good[..62]);
+        assert_eq!(
+            parse_secret_keys(&text).unwrap_err(),
+            KeyFileError {
+                line: 4,
+                error: KeyLineError::Hex(ParseError::WrongLength { expected: 64, got: 62 })
+            }
+        );
+
+        let text = format!("{}\n{}zz\n", good, &good[..62]);
+        let err = parse_secret_keys(&text).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid hex char 'z' at position 62");
+
+        let text = format!("{}\n{}\n", good, "00".repeat(32));
+        assert_eq!(parse_secret_keys(&text).unwrap_err().to_string(), "line 2: secret key is zero");
+
+        // the group order n and above are not scalars
+        let text = "f".repeat(64);
+        assert!(matches!(
+            parse_secret_keys(&text),
+            Err(KeyFileError { line: 1, error: KeyLineError::Scalar(DecodeError::InvalidScalar) })
+        ));
+    }
+
+    #[test]
+    fn blank_and_prefixed_lines() {
+        let text = format!("\n  0x{}  \n\n", to_hex(&[7u8; 32]));
+        assert_eq!(parse_secret_keys(&text).unwrap().len(), 1);
+        assert!(parse_secret_keys("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(all(feature = "verify-only", feature = "signing"))]
+compile_error!("the \"verify-only\" feature excludes signing; build with --no-default-features --features verify-only");
+
+#[cfg(feature = "signing")]
+pub mod audit;
+pub mod bintree;
+pub mod bintree2;
+#[cfg(feature = "signing")]
+pub mod cooperative;
+#[cfg(feature = "signing")]
+pub mod coordinator;
+pub mod encoding;
+pub mod error;
+#[cfg(feature = "signing")]
+pub mod flat;
+#[cfg(feature = "signing")]
+#[doc(hidden)]
+pub mod fuzzing;
+pub mod indexed;
+#[cfg(feature = "interop")]
+pub mod interop;
+#[cfg(feature = "signing")]
+pub mod kary;
+#[cfg(feature = "signing")]
+pub mod keys;
+pub mod multitree;
+#[cfg(feature = "signing")]
+pub mod network;
+pub mod parse;
+pub mod progress;
+pub mod proof;
+#[cfg(feature = "async")]
+pub mod remote;
+#[cfg(feature = "json")]
+pub mod report;
+#[cfg(feature = "signing")]
+pub(crate) mod secret;
+#[cfg(feature = "session")]
+pub mod session;
+pub mod signature;
+#[cfg(feature = "signing")]
+pub mod signer;
+pub mod subtree;
+pub mod taproot;
+#[cfg(feature = "signing")]
+pub mod timings;
+pub mod treefile;
+pub mod treemusig;
+#[cfg(feature = "json")]
+pub mod vectors;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
+
+pub use error::Error;
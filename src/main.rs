@@ -1,140 +1,881 @@
-mod bintree;
-
-use colored::*;
-use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
-use nested_musig2::{keyagg::key_agg, keygen::keygen, params::Params, round1::{Round1Out, Round1State, sign_agg, sign_agg_ext, sign_round1}, round2::{sign_agg_prime, sign_prime, ver}};
-use std::{collections::HashMap, io};
-
-use crate::bintree::BinTree;
-
-struct NodeState {
-    secret_key: Option<Secp256k1Scalar>,
-    state: Option<Round1State>,
-    out: Option<Round1Out>,
-    out_internal: Option<Round1Out>,
-    out_prime: Option<Secp256k1Scalar>,
-    state_prime: Option<Secp256k1Point>,
-}
-
-fn round1(node: &BinTree<Secp256k1Point>, state_map: &mut HashMap<Secp256k1Point, NodeState>) {
-    match node {
-        BinTree::Leaf(pk) => {
-            let (out, _state) = sign_round1(2).unwrap();
-            if let Some(state) = state_map.get_mut(&pk) {
-                state.out = Some(out);
-                state.state = Some(_state);
+mod cli;
+mod output;
+mod safepath;
+
+use ark_usecase::Error;
+use ark_usecase::audit::{AuditReport, Phase as AuditPhase};
+use ark_usecase::bintree::{BinTree, LeafOrigins};
+use ark_usecase::coordinator::Coordinator;
+use ark_usecase::encoding::{point_hex, point_to_bytes, signature_hex, signature_to_bytes};
+use ark_usecase::flat::{flat_key, flat_sign};
+use ark_usecase::indexed::IndexedTree;
+use ark_usecase::kary::{build_kary_key_tree_with, kary_tree_sign_with};
+use ark_usecase::keys::{Keypair, format_secret_keys};
+use ark_usecase::network::{NetworkError, simulate_network};
+use ark_usecase::parse::to_hex;
+use ark_usecase::proof::{InclusionProof, ProofError};
+#[cfg(feature = "json")]
+use ark_usecase::report::SigningReport;
+#[cfg(feature = "session")]
+use ark_usecase::session::{Session, SessionError};
+use ark_usecase::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+use ark_usecase::signer::signers_for_tree;
+use ark_usecase::subtree::{subtree_sign_with, subtree_verify_with};
+use ark_usecase::taproot::taproot_tweak;
+use ark_usecase::treefile::format_key_tree;
+use ark_usecase::treemusig::{SessionBuilder, Signature, audited_tree_sign_with, import_key_tree_with, timed_tree_sign, validate_key_tree_with};
+#[cfg(feature = "json")]
+use ark_usecase::vectors::{TestVector, VECTOR_COUNTS, VectorFile};
+use clap::Parser;
+use crypto_rs::secp256k1::Secp256k1Point;
+use nested_musig2::params::Params;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::{collections::HashMap, env, fmt, fs, io, io::Write, path::{Path, PathBuf}, process::ExitCode, sync::Arc};
+
+#[cfg(feature = "session")]
+use crate::cli::Phase;
+use crate::cli::{ArgError, Args, SigningMode, parse_count};
+use crate::output::{Output, OutputMode, ProgressBar};
+
+/// The signature did not verify.
+const EXIT_UNVERIFIED: u8 = 1;
+/// The arguments, or the files they name, cannot be used; clap exits with
+/// the same code for arguments it rejects itself.
+const EXIT_USAGE: u8 = 2;
+/// Signing or anything after it failed.
+const EXIT_PROTOCOL: u8 = 3;
+
+fn main() -> ExitCode {
+    // Without arguments, fall back to asking for n interactively.
+    let parsed = (env::args_os().len() > 1).then(|| Args::try_parse().unwrap_or_else(|e| e.exit()));
+    let mode = match &parsed {
+        Some(args) if args.json_output() => OutputMode::Quiet,
+        _ => OutputMode::from_env(),
+    };
+    let mut out = Output::stdout(mode);
+    out.heading("Demonstration of converting any n of n musig to binary tree merkelized nested musig");
+
+    let args = match parsed.map_or_else(|| prompt_args(&mut out), Ok) {
+        Ok(args) => args,
+        Err(e) => {
+            out.error(&format!("error: {}", e));
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    match run(&mut out, &args) {
+        Ok(outcome) => ExitCode::from(outcome.exit_code()),
+        Err(e) => {
+            out.error(&format!("error: {}", e));
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// What a run ended with, for `main` to turn into an exit code and for
+/// tests to look at without reading the printed lines.
+#[derive(Debug)]
+struct RunOutcome {
+    /// The key the signature or proof is checked under.
+    root_key: Option<Secp256k1Point>,
+    signature: Option<Signature>,
+    /// `None` when the run had nothing to verify, as for `--phase round1`.
+    verified: Option<bool>,
+}
+
+impl RunOutcome {
+    fn signed(root_key: &Secp256k1Point, signature: &Signature, verified: bool) -> Self {
+        RunOutcome { root_key: Some(root_key.clone()), signature: Some(signature.clone()), verified: Some(verified) }
+    }
+
+    fn exit_code(&self) -> u8 {
+        if self.verified == Some(false) { EXIT_UNVERIFIED } else { 0 }
+    }
+}
+
+#[derive(Debug)]
+enum RunError {
+    Usage(ArgError),
+    Signing(Error),
+    Network(NetworkError),
+    Read { path: PathBuf, error: io::Error },
+    Write { path: PathBuf, error: io::Error },
+    Proof { path: PathBuf, error: ProofError },
+    #[cfg(feature = "session")]
+    Session { path: PathBuf, error: SessionError },
+    /// `--audit` found the signing state out of step with the key tree.
+    Audit { phase: AuditPhase, report: AuditReport },
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Usage(e) => write!(f, "{}", e),
+            RunError::Signing(e) => write!(f, "{}", e),
+            RunError::Network(e) => write!(f, "{}", e),
+            RunError::Read { path, error } => write!(f, "cannot read {}: {}", path.display(), error),
+            RunError::Write { path, error } => write!(f, "cannot write {}: {}", path.display(), error),
+            RunError::Proof { path, error } => write!(f, "{}: {}", path.display(), error),
+            #[cfg(feature = "session")]
+            RunError::Session { path, error } => write!(f, "session {}: {}", path.display(), error),
+            RunError::Audit { phase, report } => {
+                write!(f, "state audit after {:?} found {} issues", phase, report.issues.len())
             }
-        },
-        BinTree::Node { left, right, value } => {
-            round1(left, state_map);
-            round1(right, state_map);
-            let left_out = state_map.get(left.value()).unwrap().out.clone().unwrap();
-            let right_out = state_map.get(right.value()).unwrap().out.clone().unwrap();
-            let out_internal = sign_agg(&[left_out, right_out]).unwrap();
-
-            let out = sign_agg_ext(&Params::default(), &out_internal, value).unwrap();
-            let state = NodeState {
-                secret_key: None,
-                state: None,
-                out: Some(out),
-                out_internal: Some(out_internal),
-                out_prime: None,
-                state_prime: None,
-            };
-            state_map.insert(value.clone(), state);
         }
     }
 }
 
-fn round2(node: &BinTree<Secp256k1Point>, state_map: &mut HashMap<Secp256k1Point, NodeState>, msg: &[u8], outs_by_depth: &[Round1Out], merkle_path: Vec<Vec<Secp256k1Point>>) {
-    let params = Params::default();
-    match node {
-        BinTree::Leaf(pk) => {
-            let state = state_map.get_mut(pk).unwrap(); 
-            let state1 = state.state.clone().unwrap();
-            let sk = state.secret_key.clone().unwrap();
-            let (state_prime, out_prime) = sign_prime(&params, state1, outs_by_depth, &sk, msg, &merkle_path).unwrap();
-            state.out_prime = Some(out_prime);
-            state.state_prime = Some(state_prime);
-        },
-        BinTree::Node { left, right, value } => {
-            let state = state_map.get(value).unwrap(); 
-            let out_d = state.out_internal.clone().unwrap();
+impl RunError {
+    /// Bad arguments and unreadable input files are the caller's to fix;
+    /// everything else failed while signing or saving the result.
+    fn exit_code(&self) -> u8 {
+        match self {
+            RunError::Usage(_) | RunError::Read { .. } | RunError::Proof { .. } => EXIT_USAGE,
+            _ => EXIT_PROTOCOL,
+        }
+    }
+}
 
-            let mut ext_outs = outs_by_depth.to_vec();
-            ext_outs.push(out_d);
+impl From<ArgError> for RunError {
+    fn from(e: ArgError) -> Self {
+        RunError::Usage(e)
+    }
+}
 
-            // insert corresponding pubkeys of siblings at level `lambda`
-            let mut l_path = merkle_path.clone();
-            let mut r_path = merkle_path.clone();
-            r_path.push(vec![left.value().clone()]);
-            l_path.push(vec![right.value().clone()]);
-            round2(left, state_map, msg, &ext_outs, l_path);
-            round2(right, state_map, msg, &ext_outs, r_path);
+impl From<Error> for RunError {
+    fn from(e: Error) -> Self {
+        RunError::Signing(e)
+    }
+}
 
-            let l_state = state_map.get(left.value()).unwrap().state_prime.clone().unwrap();
-            let l_out = state_map.get(left.value()).unwrap().out_prime.clone().unwrap();
+impl From<NetworkError> for RunError {
+    fn from(e: NetworkError) -> Self {
+        RunError::Network(e)
+    }
+}
 
-            let r_state = state_map.get(right.value()).unwrap().state_prime.clone().unwrap();
-            let r_out = state_map.get(right.value()).unwrap().out_prime.clone().unwrap();
+fn prompt_args(out: &mut Output<io::Stdout>) -> Result<Args, ArgError> {
+    out.prompt("n");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).map_err(ArgError::Stdin)?;
+    Ok(Args::with_count(parse_count(&input)?))
+}
 
-            let parts = &[(l_state, l_out), (r_state, r_out)];
-            let (state_prime, out_prime) = sign_agg_prime(parts).unwrap();
+fn run<W: Write>(out: &mut Output<W>, args: &Args) -> Result<RunOutcome, RunError> {
+    let args = &args.load()?;
+    args.check()?;
+    if let Some(path) = &args.verify_proof {
+        return verify_proof(out, args, path);
+    }
+    #[cfg(feature = "json")]
+    if let Some(path) = &args.gen_vectors {
+        return gen_vectors(out, args, path);
+    }
+    #[cfg(feature = "session")]
+    if let (Some(phase), Some(path)) = (args.phase, &args.session) {
+        return run_phase(out, args, phase, path);
+    }
+    if args.timings {
+        return run_timed(out, args);
+    }
+    if args.mode == SigningMode::Flat {
+        return run_flat(out, args);
+    }
+    if args.arity > 2 {
+        return run_kary(out, args);
+    }
+    let (origins, keys) = key_tree(out, args)?;
+    if let (Some(leaf), Some(path)) = (&args.export_proof, &args.proof_out) {
+        let proof = InclusionProof::for_leaf(&origins.tree, leaf)?;
+        fs::write(path, proof.to_bytes()).map_err(|error| RunError::Write { path: path.clone(), error })?;
+        let signer = origins.leaf_index_of(leaf).expect("a key with a proof is a leaf");
+        out.info(&format!("Wrote inclusion proof for {} (signer #{}) to {}", point_hex(leaf), signer, path.display()));
+    }
+    let btree = origins.tree;
+    let msg = args.message();
+    out.info(&format!("Signing {} message bytes: {}", msg.len(), message_preview(msg)));
+    if let Some(node) = args.sign_subtree {
+        return run_subtree(out, args, &btree, &keys, node);
+    }
+    let sig = if args.simulate_network {
+        out.info(&format!("Simulating {} signers on their own threads", keys.len()));
+        simulate_network(&btree, keys, msg, &args.params.params())?
+    } else if args.progress {
+        sign_in_session(args, &btree, keys, msg)?
+    } else if args.audit {
+        sign_audited(out, &btree, keys, msg, &args.params.params())?
+    } else {
+        sign_with_signers(&btree, keys, msg, &args.params.params())?
+    };
+    report(out, args, &btree, &sig)
+}
 
-            let state = state_map.get_mut(value).unwrap(); 
-            state.out_prime = Some(out_prime);
-            state.state_prime = Some(state_prime);
+/// Signs in-process through `timed_tree_sign` and prints where the time
+/// went.
+fn run_timed<W: Write>(out: &mut Output<W>, args: &Args) -> Result<RunOutcome, RunError> {
+    let keys = keypairs(out, args)?;
+    let pubkeys = keys.iter().map(|kp| kp.pk.clone()).collect();
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let msg = args.message();
+    let (btree, sig, verified, timings) = timed_tree_sign(pubkeys, &secret_keys, msg, &args.params.params())?;
+    describe_tree(out, args, &btree, None)?;
+    out.info(&format!("Signing {} message bytes: {}", msg.len(), message_preview(msg)));
+    let outcome = report_verified(out, args, &btree, &sig, verified)?;
+    for line in timings.to_string().lines() {
+        out.info(line);
+    }
+    Ok(outcome)
+}
+
+/// Checks an inclusion proof written by `--export-proof`, against
+/// `--proof-root` when given.
+fn verify_proof<W: Write>(out: &mut Output<W>, args: &Args, path: &Path) -> Result<RunOutcome, RunError> {
+    let bytes = fs::read(path).map_err(|error| RunError::Read { path: path.to_path_buf(), error })?;
+    let proof = InclusionProof::from_bytes(&bytes).map_err(|error| RunError::Proof { path: path.to_path_buf(), error })?;
+    let root = args.proof_root.as_ref().unwrap_or(&proof.root);
+    let verified = proof.verify_for_root(root, &args.params.params());
+    if verified {
+        out.success("SUCCESS");
+    } else {
+        out.failure("FAIL");
+    }
+    out.info(&format!("Leaf key: {}", point_hex(&proof.leaf)));
+    out.info(&format!("Root key: {}", point_hex(root)));
+    Ok(RunOutcome { root_key: Some(root.clone()), signature: None, verified: Some(verified) })
+}
+
+/// Signs a known-answer case for every usual signer count, or only `--n`,
+/// with keys from `--seed`, and writes them all to `path`.
+#[cfg(feature = "json")]
+fn gen_vectors<W: Write>(out: &mut Output<W>, args: &Args, path: &Path) -> Result<RunOutcome, RunError> {
+    let seed = args.seed.expect("clap requires --seed with --gen-vectors");
+    let counts = match args.n {
+        Some(n) => vec![n as usize],
+        None => VECTOR_COUNTS.to_vec(),
+    };
+    let msg = args.message();
+    out.info(&format!("Signing {} message bytes: {}", msg.len(), message_preview(msg)));
+    let cases = counts.into_iter().map(|n| TestVector::generate(seed, n, msg)).collect::<Result<Vec<_>, _>>()?;
+    let mut verified = true;
+    for case in &cases {
+        let ok = case.verifies().expect("generated from valid bytes");
+        out.info(&format!("n = {}: root key {}{}", case.n, case.root_key, if ok { "" } else { ", signature FAILS" }));
+        verified &= ok;
+    }
+    if verified {
+        out.success("SUCCESS");
+    } else {
+        out.failure("FAIL");
+    }
+    fs::write(path, VectorFile::new(cases).to_json()).map_err(|error| RunError::Write { path: path.to_path_buf(), error })?;
+    out.info(&format!("Wrote test vectors to {}", path.display()));
+    Ok(RunOutcome { root_key: None, signature: None, verified: Some(verified) })
+}
+
+/// Signs with plain n-of-n MuSig2 over the same keys, with no tree.
+fn run_flat<W: Write>(out: &mut Output<W>, args: &Args) -> Result<RunOutcome, RunError> {
+    let keys = keypairs(out, args)?;
+    let params = args.params.params();
+    let pubkeys: Vec<_> = keys.iter().map(|kp| kp.pk.clone()).collect();
+    let key = flat_key(&pubkeys, &params)?;
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let msg = args.message();
+    out.info(&format!("Signing {} message bytes: {}", msg.len(), message_preview(msg)));
+    out.info(&format!("Flat {0}-of-{0} MuSig2, no key tree", pubkeys.len()));
+    let sig = flat_sign(&pubkeys, &secret_keys, msg, &params)?;
+    report_key(out, args, &key, &sig)
+}
+
+/// Signs over a key tree with up to `--arity` children per node, keys
+/// sorted as for the binary tree.
+fn run_kary<W: Write>(out: &mut Output<W>, args: &Args) -> Result<RunOutcome, RunError> {
+    let keys = keypairs(out, args)?;
+    let params = args.params.params();
+    let mut pubkeys: Vec<_> = keys.iter().map(|kp| kp.pk.clone()).collect();
+    pubkeys.sort_by_cached_key(point_to_bytes);
+    let tree = build_kary_key_tree_with(pubkeys, args.arity as usize, &params)?;
+    out.info(&format!("Key tree with up to {} children per node, {} levels", args.arity, tree.height()));
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let msg = args.message();
+    out.info(&format!("Signing {} message bytes: {}", msg.len(), message_preview(msg)));
+    let sig = kary_tree_sign_with(&tree, &secret_keys, msg, &params)?;
+    report_key(out, args, tree.value(), &sig)
+}
+
+/// Signs with the leaves under `node` only; everyone else's secret is left
+/// out.
+fn run_subtree<W: Write>(out: &mut Output<W>, args: &Args, btree: &BinTree<Secp256k1Point>, keys: &[Keypair], node: usize) -> Result<RunOutcome, RunError> {
+    let indexed = IndexedTree::from_tree(btree);
+    if node >= indexed.node_count() {
+        return Err(Error::UnknownNode(node).into());
+    }
+    let subtree = indexed.subtree(node);
+    let secret_keys: HashMap<_, _> = keys
+        .iter()
+        .filter(|kp| subtree.contains_leaf(&kp.pk))
+        .map(|kp| (kp.pk.clone(), kp.sk.clone()))
+        .collect();
+    out.info(&format!("Signing with the {} signers under node {}", subtree.leaf_count(), node));
+    let params = args.params.params();
+    let proof = subtree_sign_with(btree, node, &secret_keys, args.message(), &params)?;
+
+    let verified = subtree_verify_with(btree.value(), args.message(), &proof, &params);
+    if verified {
+        out.success("SUCCESS");
+    } else {
+        out.failure("FAIL");
+    }
+    out.info(&format!("Root key: {}", point_hex(btree.value())));
+    out.info(&format!("Subtree key: {}", point_hex(&proof.subtree_key)));
+    for (side, sibling) in &proof.path {
+        out.info(&format!("Sibling ({:?}): {}", side, point_hex(sibling)));
+    }
+    out.info(&format!("Signature: {}", signature_hex(&proof.sig)));
+    if let Some(path) = &args.sig_out {
+        fs::write(path, signature_to_bytes(&proof.sig)).map_err(|error| RunError::Write { path: path.clone(), error })?;
+        out.info(&format!("Wrote signature to {}", path.display()));
+    }
+    Ok(RunOutcome::signed(btree.value(), &proof.sig, verified))
+}
+
+/// Round 1 saves everything round 2 needs; round 2 signs and saves the
+/// session back spent, so it cannot be loaded again.
+#[cfg(feature = "session")]
+fn run_phase<W: Write>(out: &mut Output<W>, args: &Args, phase: Phase, path: &Path) -> Result<RunOutcome, RunError> {
+    match phase {
+        Phase::Round1 => {
+            let (LeafOrigins { tree: btree, .. }, keys) = key_tree(out, args)?;
+            let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+            let mut session = Session::new(&btree, &secret_keys)?;
+            session.round1()?;
+            session.save(path).map_err(|error| RunError::Session { path: path.to_path_buf(), error })?;
+            out.info(&format!("Root key: {}", point_hex(session.root_key())));
+            out.info(&format!("Saved round 1 session to {}", path.display()));
+            Ok(RunOutcome { root_key: Some(session.root_key().clone()), signature: None, verified: None })
+        }
+        Phase::Round2 => {
+            let mut session = Session::load(path).map_err(|error| RunError::Session { path: path.to_path_buf(), error })?;
+            let msg = args.message();
+            out.info(&format!("Signing {} message bytes: {}", msg.len(), message_preview(msg)));
+            let sig = session.round2(msg)?;
+            session.save(path).map_err(|error| RunError::Session { path: path.to_path_buf(), error })?;
+            report(out, args, &session.key_tree(), &sig)
+        }
+    }
+}
+
+/// Creates or loads the keypairs and builds their key tree, or checks the
+/// one from `--tree-in`, printing what was asked for along the way. Signers
+/// are numbered by their position in the keypair list, whatever order the
+/// tree puts them in.
+fn key_tree<W: Write>(out: &mut Output<W>, args: &Args) -> Result<(LeafOrigins<Secp256k1Point>, Vec<Keypair>), RunError> {
+    let keys = keypairs(out, args)?;
+    let origins = match &args.files.tree {
+        Some(levels) => {
+            let tree = import_key_tree_with(levels.0.clone(), &args.params.params())?;
+            out.info(&format!("Imported a key tree with {} leaves", tree.leaf_count()));
+            let positions = tree
+                .leaves()
+                .map(|pk| keys.iter().position(|kp| kp.pk == *pk).ok_or_else(|| Error::MissingNodeState(pk.clone())))
+                .collect::<Result<_, _>>()?;
+            LeafOrigins { tree, positions }
+        }
+        None => session_builder(args).key_tree(keys.iter().map(|kp| kp.pk.clone()).collect())?,
+    };
+    if let Some(path) = &args.tree_out {
+        fs::write(path, format_key_tree(&origins.tree)).map_err(|error| RunError::Write { path: path.clone(), error })?;
+        out.info(&format!("Wrote key tree to {}", path.display()));
+    }
+    describe_tree(out, args, &origins.tree, Some(&origins))?;
+    Ok((origins, keys))
+}
+
+fn keypairs<W: Write>(out: &mut Output<W>, args: &Args) -> Result<Vec<Keypair>, RunError> {
+    let keys: Vec<Keypair> = match &args.files.keys {
+        Some(loaded) => loaded.0.clone(),
+        None => match args.seed {
+            Some(seed) => {
+                let mut rng = ChaCha20Rng::seed_from_u64(seed);
+                session_builder(args).generate_keys(args.count(), || Keypair::from_rng(&mut rng))
+            }
+            None => session_builder(args).generate_keys(args.count(), Keypair::generate),
         },
+    };
+    if let Some(path) = &args.export_keys {
+        fs::write(path, format_secret_keys(&keys)).map_err(|error| RunError::Write { path: path.clone(), error })?;
+        out.info(&format!("Wrote {} secret keys to {}", keys.len(), path.display()));
+    }
+    if args.keys.is_some() {
+        out.info(&format!("Loaded {} keypairs", keys.len()));
+    } else {
+        out.info(&format!("Created {} keypairs", keys.len()));
+    }
+    Ok(keys)
+}
+
+/// Checks and prints the key tree as the flags ask, numbering the leaves
+/// when their `origins` are known.
+fn describe_tree<W: Write>(
+    out: &mut Output<W>,
+    args: &Args,
+    btree: &BinTree<Secp256k1Point>,
+    origins: Option<&LeafOrigins<Secp256k1Point>>,
+) -> Result<(), RunError> {
+    if args.paranoid {
+        validate_key_tree_with(btree, &args.params.params())?;
+        out.info("Key tree aggregates check out");
+    }
+    if cfg!(debug_assertions) {
+        let widths: Vec<String> = btree.levels().iter().map(|level| level.len().to_string()).collect();
+        out.info(&format!("Key tree nodes per depth: [{}]", widths.join(", ")));
+    }
+    if args.show_tree {
+        let rendered = btree.render(|pk| {
+            let short = point_hex(pk)[..8].to_string();
+            match origins.and_then(|origins| origins.leaf_index_of(pk)) {
+                Some(signer) => format!("{} #{}", short, signer),
+                None => short,
+            }
+        });
+        for line in rendered.lines() {
+            out.info(line);
+        }
     }
+    Ok(())
 }
 
-fn main() {
-    println!(
-        "{}",
-        "Demonstration of converting any n of n musig to binary tree merkelized nested musig"
-            .green()
-    );
-    println!("Enter {}", "n".yellow());
+fn report<W: Write>(out: &mut Output<W>, args: &Args, tree: &BinTree<Secp256k1Point>, sig: &Signature) -> Result<RunOutcome, RunError> {
+    let verified = verify_root(args, tree.value(), sig);
+    report_verified(out, args, tree, sig, verified)
+}
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let n: u32 = input.trim().parse().unwrap();
-
-    let keys: Vec<_> = (0..n).map(|_| keygen()).collect();
-    let mut state_map: HashMap<Secp256k1Point, NodeState> = HashMap::new();
-    let mut pubkeys = Vec::new();
-
-    for kp in keys {
-        pubkeys.push(kp.pk.clone());
-        state_map.insert(
-            kp.pk,
-            NodeState {
-                secret_key: Some(kp.sk),
-                state: None,
-                out: None,
-                out_internal: None,
-                out_prime: None,
-                state_prime: None,
-            },
-        );
-    }
-
-    println!("Created n keypairs");
-
-    let btree = BinTree::from_vec(pubkeys, |k1, k2| {
-        key_agg(&Params::default(), &[k1, k2]).unwrap()
-    });
-
-    round1(&btree, &mut state_map);
-    let msg = b"test tx message";
-    round2(&btree, &mut state_map, msg , &[], vec![]);
-    let root_pk = btree.value();
-    let state = state_map.get(root_pk).unwrap();
-    let sig = (state.state_prime.clone().unwrap(), state.out_prime.clone().unwrap());
-
-    if ver(&Params::default(), root_pk, msg, &sig) {
-        println!("{}", "SUCCESS".green());
+/// `report` for a signature whose check has already run.
+fn report_verified<W: Write>(out: &mut Output<W>, args: &Args, tree: &BinTree<Secp256k1Point>, sig: &Signature, verified: bool) -> Result<RunOutcome, RunError> {
+    let outcome = print_signed(out, args, tree.value(), sig, verified)?;
+    #[cfg(feature = "json")]
+    if args.json_output() {
+        let verified = outcome.verified == Some(true);
+        out.document(&SigningReport::new(tree, args.message(), sig, verified).to_json());
+    }
+    Ok(outcome)
+}
+
+/// Verifies `sig` under `root` and prints both.
+fn report_key<W: Write>(out: &mut Output<W>, args: &Args, root: &Secp256k1Point, sig: &Signature) -> Result<RunOutcome, RunError> {
+    let verified = verify_root(args, root, sig);
+    print_signed(out, args, root, sig, verified)
+}
+
+/// Whether `sig` signs the message under `root` with the chosen params.
+fn verify_root(args: &Args, root: &Secp256k1Point, sig: &Signature) -> bool {
+    let ctx = VerifyCtx::new(args.params.params(), &AggregatedKey::new(root.clone()));
+    ctx.verify(args.message(), &TreeSignature::new(sig.clone()))
+}
+
+fn print_signed<W: Write>(out: &mut Output<W>, args: &Args, root: &Secp256k1Point, sig: &Signature, verified: bool) -> Result<RunOutcome, RunError> {
+    if verified {
+        out.success("SUCCESS");
     } else {
-        println!("{}", "FAIL".red());
+        out.failure("FAIL");
+    }
+    // Root key (33-byte compressed) and signature (point then 32-byte
+    // big-endian scalar) are enough to verify elsewhere.
+    out.info(&format!("Root key: {}", point_hex(root)));
+    let (tweaked, parity) = taproot_tweak(root, args.taproot_merkle_root)?;
+    out.info(&format!("Taproot output key: {} ({:?} y)", to_hex(&tweaked.x_only()), parity));
+    out.info(&format!("Signature: {}", signature_hex(sig)));
+    if let Some(path) = &args.sig_out {
+        fs::write(path, signature_to_bytes(sig)).map_err(|error| RunError::Write { path: path.clone(), error })?;
+        out.info(&format!("Wrote signature to {}", path.display()));
+    }
+    Ok(RunOutcome::signed(root, sig, verified))
+}
+
+/// Keys, key tree and in-process session under `--params`, reporting to a
+/// progress bar with `--progress`.
+fn session_builder(args: &Args) -> SessionBuilder {
+    let builder = SessionBuilder::new().params(args.params.params());
+    if args.progress { builder.with_progress(Arc::new(ProgressBar::default())) } else { builder }
+}
+
+/// Signs in-process, printing the state audit after setup and each round.
+/// The first audit that finds anything fails the run.
+fn sign_audited<W: Write>(out: &mut Output<W>, tree: &BinTree<Secp256k1Point>, keys: Vec<Keypair>, msg: &[u8], params: &Params) -> Result<Signature, RunError> {
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let (sig, reports) = audited_tree_sign_with(tree, &secret_keys, msg, params)?;
+    for (phase, report) in reports {
+        if !report.is_clean() {
+            out.failure(&format!("State audit after {:?}:", phase));
+            for line in report.to_string().lines() {
+                out.info(line);
+            }
+            return Err(RunError::Audit { phase, report });
+        }
+        out.info(&format!("State audit after {:?}: clean", phase));
+    }
+    Ok(sig)
+}
+
+/// Runs both rounds in one `SigningSession`, which reports its progress
+/// where `Signer`s cannot.
+fn sign_in_session(args: &Args, tree: &BinTree<Secp256k1Point>, keys: Vec<Keypair>, msg: &[u8]) -> Result<Signature, Error> {
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let session = session_builder(args).session(tree, &secret_keys)?;
+    Ok(session.round1()?.round2(msg)?.signature().clone())
+}
+
+/// Hands each keypair to its own `Signer`, at its leaf's position, and runs
+/// both rounds through one `Coordinator`.
+fn sign_with_signers(tree: &BinTree<Secp256k1Point>, keys: Vec<Keypair>, msg: &[u8], params: &Params) -> Result<Signature, Error> {
+    let mut signers = signers_for_tree(tree, keys, params)?;
+    let mut coordinator = Coordinator::with_params(tree, params.clone());
+    for signer in &mut signers {
+        coordinator.add_round1(signer.position(), signer.round1()?)?;
+    }
+    coordinator.aggregate_round1()?;
+    for signer in &mut signers {
+        let (outs_by_depth, merkle_path) = coordinator.round2_inputs(signer.position())?;
+        coordinator.add_round2(signer.position(), signer.round2(&outs_by_depth, msg, &merkle_path)?)?;
+    }
+    coordinator.aggregate_round2()
+}
+
+/// The message is signed as given, not hashed first; long ones are cut
+/// short for display.
+fn message_preview(msg: &[u8]) -> String {
+    const SHOWN: usize = 64;
+    if msg.is_empty() {
+        "(empty)".to_string()
+    } else if msg.len() <= SHOWN {
+        to_hex(msg)
+    } else {
+        format!("{}...", to_hex(&msg[..SHOWN]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_usecase::treemusig::tree_verify_with;
+
+    fn run_with(argv: &[&str]) -> String {
+        let args = Args::try_parse_from(std::iter::once("ark-usecase").chain(argv.iter().copied())).unwrap();
+        let mut buf = Vec::new();
+        run(&mut Output::new(OutputMode::Plain, &mut buf), &args).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn audit_flag_prints_each_phase() {
+        let printed = run_with(&["--n", "5", "--audit", "--msg-hex", "deadbeef"]);
+        for phase in ["Setup", "Round1", "Round2"] {
+            assert!(printed.contains(&format!("State audit after {}: clean", phase)), "{}", printed);
+        }
+        assert!(printed.contains("SUCCESS"));
+    }
+
+    #[test]
+    fn signs_and_verifies_from_args() {
+        let printed = run_with(&["--n", "5", "--message", "deadbeef", "--paranoid"]);
+        assert!(printed.contains("Created 5 keypairs"));
+        assert!(printed.contains("Key tree aggregates check out"));
+        assert!(printed.contains("SUCCESS"));
+    }
+
+    #[test]
+    fn one_signer_is_supported() {
+        let printed = run_with(&["--n", "1"]);
+        assert!(printed.contains("Created 1 keypairs"));
+        assert!(printed.contains("SUCCESS"));
+        assert!(run_with(&["--n", "1", "--timings"]).contains("SUCCESS"));
+    }
+
+    #[test]
+    fn echoes_the_signed_message() {
+        let printed = run_with(&["--n", "2", "--msg-hex", "deadbeef"]);
+        assert!(printed.contains("Signing 4 message bytes: deadbeef"));
+        let printed = run_with(&["--n", "2", "--msg-hex", ""]);
+        assert!(printed.contains("Signing 0 message bytes: (empty)"));
+        assert!(printed.contains("SUCCESS"));
+
+        let long = "ab".repeat(100);
+        let printed = run_with(&["--n", "2", "--msg-hex", &long]);
+        assert!(printed.contains(&format!("Signing 100 message bytes: {}...", "ab".repeat(64))));
+        assert!(printed.contains("SUCCESS"));
+    }
+
+    #[test]
+    fn writes_signature_file() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-sig-{}", std::process::id()));
+        let printed = run_with(&["--n", "3", "--sig-out", path.to_str().unwrap()]);
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let sig_line = printed.lines().find_map(|l| l.strip_prefix("Signature: ")).unwrap();
+        assert_eq!(sig_line, to_hex(&bytes));
+        let sig = ark_usecase::encoding::signature_from_bytes(&bytes).unwrap();
+        let root_line = printed.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap();
+        let root = ark_usecase::encoding::point_from_bytes(&ark_usecase::parse::hex_any(root_line).unwrap()).unwrap();
+        assert!(tree_verify_with(&root, cli::DEFAULT_MESSAGE, &sig, &Params::default()));
+    }
+
+    #[test]
+    fn exported_keys_reproduce_root_key() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-export-{}", std::process::id()));
+        let p = path.to_str().unwrap();
+        let root_of = |printed: String| {
+            printed.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap().to_string()
+        };
+
+        let generated = root_of(run_with(&["--n", "5", "--export-keys", p]));
+        let first = run_with(&["--keys", p]);
+        let second = run_with(&["--keys", p, "--n", "5"]);
+        fs::remove_file(&path).unwrap();
+
+        assert!(first.contains("Loaded 5 keypairs"));
+        assert_eq!(root_of(first), generated);
+        assert_eq!(root_of(second), generated);
+    }
+
+    #[test]
+    fn same_seed_same_root_key() {
+        let root_of = |argv: &[&str]| {
+            let printed = run_with(argv);
+            printed.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap().to_string()
+        };
+        let a = root_of(&["--n", "5", "--seed", "42"]);
+        assert_eq!(root_of(&["--n", "5", "--seed", "42"]), a);
+        assert_ne!(root_of(&["--n", "5", "--seed", "43"]), a);
+    }
+
+    #[cfg(feature = "session")]
+    #[test]
+    fn rounds_in_separate_invocations() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-phases-{}", std::process::id()));
+        let p = path.to_str().unwrap();
+        let root_of = |printed: &str| printed.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap().to_string();
+
+        let single = run_with(&["--n", "5", "--seed", "7", "--msg-hex", "deadbeef"]);
+        let first = run_with(&["--n", "5", "--seed", "7", "--phase", "round1", "--session", p]);
+        assert!(first.contains("Saved round 1 session"));
+        assert!(!first.contains("Signature: "));
+        let second = run_with(&["--phase", "round2", "--session", p, "--msg-hex", "deadbeef"]);
+        assert!(second.contains("SUCCESS"));
+        assert_eq!(root_of(&first), root_of(&single));
+        assert_eq!(root_of(&second), root_of(&single));
+
+        // The session is spent, so it cannot sign again.
+        let args = Args::try_parse_from(["ark-usecase", "--phase", "round2", "--session", p]).unwrap();
+        let err = run(&mut Output::new(OutputMode::Plain, Vec::new()), &args).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, RunError::Session { error: SessionError::Spent, .. }));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_output_is_one_object() {
+        let args = Args::try_parse_from(["ark-usecase", "--n", "4", "--msg-hex", "deadbeef", "--output", "json"]).unwrap();
+        let mut buf = Vec::new();
+        run(&mut Output::new(OutputMode::Quiet, &mut buf), &args).unwrap();
+        let printed = String::from_utf8(buf).unwrap();
+        assert_eq!(printed.lines().count(), 1);
+
+        let report: serde_json::Value = serde_json::from_str(&printed).unwrap();
+        assert_eq!(report["n"], 4);
+        assert_eq!(report["leaf_count"], 4);
+        assert_eq!(report["height"], 3);
+        assert_eq!(report["message"], "deadbeef");
+        assert_eq!(report["verified"], true);
+        let levels = report["levels"].as_array().unwrap();
+        assert_eq!(levels.iter().map(|l| l.as_array().unwrap().len()).collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert_eq!(levels[0][0], report["root_pubkey"]);
+        assert_eq!(report["root_pubkey"].as_str().unwrap().len(), 66);
+        assert_eq!(report["signature"].as_str().unwrap().len(), 130);
+
+        let args = Args::try_parse_from(["ark-usecase", "--n", "4", "--output", "json", "--sign-subtree", "1"]).unwrap();
+        assert!(matches!(args.check(), Err(ArgError::JsonSubtree)));
+    }
+
+    #[test]
+    fn timings_table_has_a_row_per_level() {
+        let printed = run_with(&["--n", "4", "--timings"]);
+        assert!(printed.contains("SUCCESS"));
+        assert_eq!(printed.lines().filter(|l| l.starts_with("key aggregation")).count(), 1);
+        assert_eq!(printed.lines().filter(|l| l.starts_with("round 1")).count(), 3);
+        assert_eq!(printed.lines().filter(|l| l.starts_with("round 2")).count(), 3);
+    }
+
+    #[test]
+    fn prints_the_taproot_output_key() {
+        let output_key = |printed: &str| printed.lines().find_map(|l| l.strip_prefix("Taproot output key: ")).unwrap().to_string();
+        let key_path = output_key(&run_with(&["--n", "2", "--seed", "7"]));
+        let root = "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21";
+        let scripted = output_key(&run_with(&["--n", "2", "--seed", "7", "--taproot-merkle-root", root]));
+        assert_ne!(key_path, scripted);
+    }
+
+    #[test]
+    fn signs_with_a_subtree() {
+        let printed = run_with(&["--n", "8", "--sign-subtree", "2"]);
+        assert!(printed.contains("Signing with the 2 signers under node 2"));
+        assert!(printed.contains("SUCCESS"));
+        assert_eq!(printed.matches("Sibling (").count(), 2);
+
+        let args = Args::try_parse_from(["ark-usecase", "--n", "8", "--sign-subtree", "15"]).unwrap();
+        let err = run(&mut Output::new(OutputMode::Plain, Vec::new()), &args).unwrap_err();
+        assert_eq!(err.to_string(), "no node at index 15 in the key tree");
+    }
+
+    #[test]
+    fn simulated_network_signs() {
+        let printed = run_with(&["--n", "8", "--simulate-network"]);
+        assert!(printed.contains("Simulating 8 signers on their own threads"));
+        assert!(printed.contains("SUCCESS"));
+        assert!(Args::try_parse_from(["ark-usecase", "--n", "8", "--simulate-network", "--timings"]).is_err());
+    }
+
+    #[test]
+    fn flat_mode_signs_the_same_keys() {
+        let tree_run = run_with(&["--n", "5", "--seed", "7"]);
+        let flat_run = run_with(&["--n", "5", "--seed", "7", "--mode", "flat"]);
+        assert!(flat_run.contains("Flat 5-of-5 MuSig2, no key tree"));
+        assert!(flat_run.contains("SUCCESS"));
+        assert!(tree_run.contains("SUCCESS"));
+    }
+
+    #[test]
+    fn exported_proof_verifies() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-proof-{}", std::process::id()));
+        let p = path.to_str().unwrap();
+        let signed = run_with(&["--n", "5", "--seed", "7"]);
+        let root = signed.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap().to_string();
+        let leaf = point_hex(&Keypair::from_rng(&mut ChaCha20Rng::seed_from_u64(7)).pk);
+
+        let printed = run_with(&["--n", "5", "--seed", "7", "--export-proof", &leaf, "--proof-out", p]);
+        assert!(printed.contains(&format!("Wrote inclusion proof for {}", leaf)));
+        let verified = run_with(&["--verify-proof", p, "--proof-root", &root]);
+        assert!(verified.contains("SUCCESS"));
+        assert!(verified.contains(&format!("Leaf key: {}", leaf)));
+
+        let other = run_with(&["--n", "5", "--seed", "8"]);
+        let other_root = other.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap();
+        let rejected = run_with(&["--verify-proof", p, "--proof-root", other_root]);
+        fs::remove_file(&path).unwrap();
+        assert!(rejected.contains("FAIL"));
+    }
+
+    #[test]
+    fn exported_tree_signs_in_another_run() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-tree-{}", std::process::id()));
+        let p = path.to_str().unwrap();
+        let exported = run_with(&["--n", "6", "--seed", "11", "--tree-out", p]);
+        let root = exported.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap();
+
+        let imported = run_with(&["--n", "6", "--seed", "11", "--tree-in", p]);
+        assert!(imported.contains("Imported a key tree with 6 leaves"));
+        assert!(imported.contains("SUCCESS"));
+        assert_eq!(imported.lines().find_map(|l| l.strip_prefix("Root key: ")), Some(root));
+
+        // signers whose keys are not in the tree cannot use it
+        let args = Args::try_parse_from(["ark-usecase", "--n", "6", "--seed", "12", "--tree-in", p]).unwrap();
+        let err = run(&mut Output::new(OutputMode::Plain, &mut Vec::new()), &args).unwrap_err();
+        assert!(matches!(err, RunError::Signing(Error::MissingNodeState(_))));
+
+        // a tampered aggregate is caught before signing
+        let mut text = fs::read_to_string(&path).unwrap();
+        let first_leaf = text.lines().last().unwrap().split(' ').next().unwrap().to_string();
+        let root_line = text.lines().next().unwrap().to_string();
+        text = text.replacen(&root_line, &first_leaf, 1);
+        fs::write(&path, text).unwrap();
+        let args = Args::try_parse_from(["ark-usecase", "--n", "6", "--seed", "11", "--tree-in", p]).unwrap();
+        let err = run(&mut Output::new(OutputMode::Plain, &mut Vec::new()), &args).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(err, RunError::Signing(Error::InvalidKeyTree(_))));
+    }
+
+    #[test]
+    fn progress_run_signs() {
+        let printed = run_with(&["--n", "16", "--progress", "--seed", "3"]);
+        assert!(printed.contains("Created 16 keypairs"));
+        assert!(printed.contains("SUCCESS"));
+        // the bar goes to stderr only
+        assert!(!printed.contains("round 1"));
+    }
+
+    #[test]
+    fn verified_run_exits_zero() {
+        let args = Args::try_parse_from(["ark-usecase", "--n", "4", "--seed", "5"]).unwrap();
+        let outcome = run(&mut Output::new(OutputMode::Plain, Vec::new()), &args).unwrap();
+        let (root, sig) = (outcome.root_key.as_ref().unwrap(), outcome.signature.as_ref().unwrap());
+        assert!(tree_verify_with(root, cli::DEFAULT_MESSAGE, sig, &Params::default()));
+        assert_eq!(outcome.exit_code(), 0);
+    }
+
+    #[test]
+    fn tampered_message_exits_one() {
+        let args = Args::try_parse_from(["ark-usecase", "--n", "4", "--msg-hex", "deadbeef"]).unwrap();
+        let signed = run(&mut Output::new(OutputMode::Plain, Vec::new()), &args).unwrap();
+        let tampered = Args::try_parse_from(["ark-usecase", "--n", "4", "--msg-hex", "deadbeee"]).unwrap();
+        let mut buf = Vec::new();
+        let outcome = report_key(&mut Output::new(OutputMode::Plain, &mut buf), &tampered, signed.root_key.as_ref().unwrap(), signed.signature.as_ref().unwrap()).unwrap();
+        assert_eq!(outcome.verified, Some(false));
+        assert_eq!(outcome.exit_code(), EXIT_UNVERIFIED);
+        assert!(String::from_utf8(buf).unwrap().contains("FAIL"));
+    }
+
+    #[test]
+    fn bad_input_exits_two_and_failed_signing_three() {
+        let run_err = |argv: &[&str]| {
+            let args = Args::try_parse_from(std::iter::once("ark-usecase").chain(argv.iter().copied())).unwrap();
+            run(&mut Output::new(OutputMode::Plain, Vec::new()), &args).unwrap_err()
+        };
+        let err = run_err(&["--n", "5000"]);
+        assert!(matches!(err, RunError::Usage(ArgError::TooManySigners { .. })));
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+
+        let missing = std::env::temp_dir().join(format!("ark-usecase-no-proof-{}", std::process::id()));
+        assert_eq!(run_err(&["--verify-proof", missing.to_str().unwrap()]).exit_code(), EXIT_USAGE);
+
+        let err = run_err(&["--n", "8", "--sign-subtree", "15"]);
+        assert!(matches!(err, RunError::Signing(Error::UnknownNode(15))));
+        assert_eq!(err.exit_code(), EXIT_PROTOCOL);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn generated_vectors_replay() {
+        let path = std::env::temp_dir().join(format!("ark-usecase-vectors-{}", std::process::id()));
+        let p = path.to_str().unwrap();
+        let printed = run_with(&["--gen-vectors", p, "--seed", "3", "--n", "5", "--msg-hex", "deadbeef"]);
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(printed.contains("SUCCESS"));
+
+        let file = VectorFile::from_json(&text).unwrap();
+        assert_eq!(file.cases.len(), 1);
+        let case = &file.cases[0];
+        assert_eq!((case.seed, case.n, case.message.as_str()), (3, 5, "deadbeef"));
+        case.replay().unwrap();
+        // the same keys as a plain run with that seed
+        let signed = run_with(&["--n", "5", "--seed", "3"]);
+        let root = signed.lines().find_map(|l| l.strip_prefix("Root key: ")).unwrap();
+        assert_eq!(case.root_key.to_string(), root);
+    }
+
+    #[test]
+    fn wide_tree_signs() {
+        let printed = run_with(&["--n", "9", "--arity", "3"]);
+        assert!(printed.contains("Key tree with up to 3 children per node, 3 levels"));
+        assert!(printed.contains("SUCCESS"));
+    }
+
+    #[test]
+    fn show_tree_prints_every_node() {
+        let printed = run_with(&["--n", "3", "--show-tree"]);
+        assert_eq!(printed.matches("(leaf)").count(), 3);
+        for signer in 0..3 {
+            assert!(printed.contains(&format!(" #{} (leaf)", signer)), "signer {}", signer);
+        }
+        assert!(printed.contains("SUCCESS"));
     }
 }
@@ -0,0 +1,200 @@
+//! A tree whose internal nodes have up to `arity` children, built level by
+//! level like `BinTree::from_vec`. Wider nodes make shallower trees at the
+//! cost of more siblings per level. With `arity` 2 it is the same tree as
+//! `BinTree::from_vec` builds.
+
+use std::convert::Infallible;
+
+use crate::bintree::{BinTree, BuildError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultiTree<T> {
+    Leaf(T),
+    /// An internal node. One with a single child is the odd subtree left
+    /// over at the end of a level and carries its child's value unchanged.
+    Node { children: Vec<MultiTree<T>>, value: T },
+}
+
+impl<T: Clone> MultiTree<T> {
+    pub fn leaf(value: T) -> Self {
+        MultiTree::Leaf(value)
+    }
+
+    pub fn value(&self) -> &T {
+        match self {
+            MultiTree::Leaf(value) | MultiTree::Node { value, .. } => value,
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, Self::Leaf(_))
+    }
+
+    /// Levels in the tree, leaves included.
+    pub fn height(&self) -> usize {
+        match self {
+            MultiTree::Leaf(_) => 1,
+            MultiTree::Node { children, .. } => 1 + children.iter().map(Self::height).max().unwrap_or(0),
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaves().count()
+    }
+
+    /// The leftmost of the leaves furthest from the root.
+    pub fn deepest_leaf(&self) -> &T {
+        let mut stack = vec![(0, self)];
+        let mut deepest = (0, self.value());
+        while let Some((depth, node)) = stack.pop() {
+            match node {
+                MultiTree::Leaf(value) if depth > deepest.0 => deepest = (depth, value),
+                MultiTree::Leaf(_) => {}
+                MultiTree::Node { children, .. } => stack.extend(children.iter().rev().map(|child| (depth + 1, child))),
+            }
+        }
+        deepest.1
+    }
+
+    /// Leaf values, left to right.
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            while let Some(node) = stack.pop() {
+                match node {
+                    MultiTree::Leaf(value) => return Some(value),
+                    MultiTree::Node { children, .. } => stack.extend(children.iter().rev()),
+                }
+            }
+            None
+        })
+    }
+
+    /// Groups `leaves` `arity` at a time, level by level, with `agg` making
+    /// each node's value from its children's. Panics on empty input or an
+    /// `arity` below 2.
+    pub fn from_vec<F>(leaves: Vec<T>, arity: usize, mut agg: F) -> Self
+    where
+        F: FnMut(&[T]) -> T,
+    {
+        assert!(!leaves.is_empty(), "cannot build tree from empty vec");
+        assert!(arity >= 2, "a tree node needs room for at least two children");
+        match Self::try_from_vec(leaves, arity, |values| Ok::<T, Infallible>(agg(values))) {
+            Ok(tree) => tree,
+            Err(BuildError::Aggregation(never) | BuildError::Source { error: never, .. }) => match never {},
+            Err(BuildError::Empty | BuildError::Arity(_)) => unreachable!("checked above"),
+        }
+    }
+
+    /// Like `from_vec`, but stops at the first failing aggregation and
+    /// reports an empty input or an `arity` below 2 as an error instead of
+    /// panicking.
+    pub fn try_from_vec<E, F>(leaves: Vec<T>, arity: usize, mut agg: F) -> Result<Self, BuildError<E>>
+    where
+        F: FnMut(&[T]) -> Result<T, E>,
+    {
+        if arity < 2 {
+            return Err(BuildError::Arity(arity));
+        }
+        if leaves.is_empty() {
+            return Err(BuildError::Empty);
+        }
+        let mut nodes: Vec<Self> = leaves.into_iter().map(Self::leaf).collect();
+        while nodes.len() > 1 {
+            let mut next = Vec::with_capacity(nodes.len().div_ceil(arity));
+            let mut level = nodes.into_iter().peekable();
+            while level.peek().is_some() {
+                let children: Vec<Self> = level.by_ref().take(arity).collect();
+                let value = match children.as_slice() {
+                    [only] => only.value().clone(),
+                    _ => {
+                        let values: Vec<T> = children.iter().map(|child| child.value().clone()).collect();
+                        agg(&values).map_err(BuildError::Aggregation)?
+                    }
+                };
+                next.push(MultiTree::Node { children, value });
+            }
+            nodes = next;
+        }
+        Ok(nodes.pop().expect("non-empty level"))
+    }
+}
+
+impl<T> From<BinTree<T>> for MultiTree<T> {
+    fn from(tree: BinTree<T>) -> Self {
+        match tree.into_parts() {
+            (value, None) => MultiTree::Leaf(value),
+            (value, Some((left, right, _))) => {
+                let children = std::iter::once(*left).chain(right.map(|right| *right)).map(Self::from).collect();
+                MultiTree::Node { children, value }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn sum(values: &[u32]) -> u32 {
+        values.iter().fold(0, |acc, v| acc.wrapping_add(*v))
+    }
+
+    #[test]
+    fn nine_leaves_in_threes() {
+        let t = MultiTree::from_vec((1..=9).collect(), 3, sum);
+        assert_eq!(*t.value(), 45);
+        assert_eq!(t.height(), 3);
+        let MultiTree::Node { children, .. } = &t else {
+            panic!("expected Node");
+        };
+        let sums: Vec<u32> = children.iter().map(|child| *child.value()).collect();
+        assert_eq!(sums, vec![6, 15, 24]);
+    }
+
+    #[test]
+    fn leftover_single_child_passes_its_value_up() {
+        // 4 leaves in threes: [1 2 3] [4], then the root over both
+        let t = MultiTree::from_vec(vec![1u32, 2, 3, 4], 3, sum);
+        let MultiTree::Node { children, value } = &t else {
+            panic!("expected Node");
+        };
+        assert_eq!(*value, 10);
+        assert_eq!(children[1], MultiTree::Node { children: vec![MultiTree::leaf(4)], value: 4 });
+        assert!(MultiTree::try_from_vec(Vec::<u32>::new(), 3, |v| Ok::<_, ()>(sum(v))).is_err());
+    }
+
+    #[test]
+    fn arity_below_two_is_an_error() {
+        for arity in [0, 1] {
+            let r = MultiTree::try_from_vec(vec![1u32, 2, 3], arity, |v| Ok::<_, ()>(sum(v)));
+            assert_eq!(r, Err(BuildError::Arity(arity)));
+        }
+    }
+
+    proptest! {
+        // Property 1: arity 2 builds the same tree as `BinTree::from_vec`.
+        #[test]
+        fn prop_arity_two_is_binary(xs in proptest::collection::vec(any::<u32>(), 1..256)) {
+            let binary = BinTree::from_vec(xs.clone(), |a, b| a.wrapping_add(b));
+            prop_assert_eq!(MultiTree::from_vec(xs, 2, sum), MultiTree::from(binary));
+        }
+
+        // Property 2: every leaf is kept in order and the height is the
+        // number of levels it takes to group n leaves down to one.
+        #[test]
+        fn prop_leaves_and_height(xs in proptest::collection::vec(any::<u32>(), 1..256), arity in 2usize..8) {
+            let t = MultiTree::from_vec(xs.clone(), arity, sum);
+            prop_assert!(t.leaves().eq(xs.iter()));
+            prop_assert_eq!(t.leaf_count(), xs.len());
+            let mut levels = 1;
+            let mut width = xs.len();
+            while width > 1 {
+                width = width.div_ceil(arity);
+                levels += 1;
+            }
+            prop_assert_eq!(t.height(), levels);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! The signing protocol with every participant on its own thread. Each
+//! leaf's `Signer` holds only its own keypair and nonces and talks to the
+//! coordinator over channels, as it would over a network, so the rounds
+//! have to line up through messages rather than shared state.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{params::Params, round1::Round1Out};
+use std::{fmt, sync::mpsc, thread};
+
+use crate::bintree::BinTree;
+use crate::coordinator::Coordinator;
+use crate::error::Error;
+use crate::keys::Keypair;
+use crate::signer::{Signer, signers_for_tree};
+use crate::treemusig::Signature;
+
+/// Participant to coordinator.
+enum Upstream {
+    Round1(Round1Out),
+    Round2((Secp256k1Point, Secp256k1Scalar)),
+    Failed(Error),
+}
+
+/// Coordinator to one participant: what it needs for round 2.
+struct Round2Inputs {
+    outs_by_depth: Vec<Round1Out>,
+    merkle_path: Vec<Vec<Secp256k1Point>>,
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    /// The participant at this leaf position failed or sent something the
+    /// coordinator rejected.
+    Participant { position: usize, error: Error },
+    /// The participant at this position sent a message for the wrong round.
+    OutOfTurn { position: usize, round: u8 },
+    /// Failed on the coordinator's side, e.g. while aggregating.
+    Coordinator(Error),
+    /// A participant hung up before the run finished.
+    Disconnected,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Participant { position, error } => write!(f, "participant {} failed: {}", position, error),
+            NetworkError::Coordinator(e) => write!(f, "coordinator failed: {}", e),
+            NetworkError::OutOfTurn { position, round } => {
+                write!(f, "participant {} sent a message out of turn in round {}", position, round)
+            }
+            NetworkError::Disconnected => write!(f, "a participant disconnected before the run finished"),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetworkError::Participant { error, .. } | NetworkError::Coordinator(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for NetworkError {
+    fn from(e: Error) -> Self {
+        NetworkError::Coordinator(e)
+    }
+}
+
+/// Signs `msg` with one thread per leaf of `tree` plus this thread as the
+/// coordinator. `keys` holds the keypair for every leaf.
+pub fn simulate_network(tree: &BinTree<Secp256k1Point>, keys: Vec<Keypair>, msg: &[u8], params: &Params) -> Result<Signature, NetworkError> {
+    simulate_with(tree, keys, msg, params, |_, out| out)
+}
+
+/// `simulate_network`, with `tamper` applied to each participant's round 1
+/// output before it is sent.
+pub(crate) fn simulate_with(
+    tree: &BinTree<Secp256k1Point>,
+    keys: Vec<Keypair>,
+    msg: &[u8],
+    params: &Params,
+    tamper: impl Fn(usize, Round1Out) -> Round1Out + Sync,
+) -> Result<Signature, NetworkError> {
+    let signers = signers_for_tree(tree, keys, params)?;
+    let mut coordinator = Coordinator::with_params(tree, params.clone());
+    let (up_tx, up_rx) = mpsc::channel::<(usize, Upstream)>();
+
+    thread::scope(|scope| {
+        let mut downstream = Vec::with_capacity(signers.len());
+        for signer in signers {
+            let (down_tx, down_rx) = mpsc::channel::<Round2Inputs>();
+            downstream.push(down_tx);
+            let up_tx = up_tx.clone();
+            let tamper = &tamper;
+            scope.spawn(move || participant(signer, msg, up_tx, down_rx, tamper));
+        }
+        // Only the participants hold senders now, so `recv` fails once they
+        // have all exited.
+        drop(up_tx);
+        let n = downstream.len();
+
+        for _ in 0..n {
+            let (position, message) = up_rx.recv().map_err(|_| NetworkError::Disconnected)?;
+            match message {
+                Upstream::Round1(out) => coordinator
+                    .add_round1(position, out)
+                    .map_err(|error| NetworkError::Participant { position, error })?,
+                Upstream::Failed(error) => return Err(NetworkError::Participant { position, error }),
+                Upstream::Round2(_) => return Err(NetworkError::OutOfTurn { position, round: 1 }),
+            }
+        }
+        coordinator.aggregate_round1()?;
+
+        for (position, down_tx) in downstream.iter().enumerate() {
+            let (outs_by_depth, merkle_path) = coordinator.round2_inputs(position)?;
+            down_tx
+                .send(Round2Inputs { outs_by_depth, merkle_path })
+                .map_err(|_| NetworkError::Disconnected)?;
+        }
+        for _ in 0..n {
+            let (position, message) = up_rx.recv().map_err(|_| NetworkError::Disconnected)?;
+            match message {
+                Upstream::Round2(prime) => coordinator
+                    .add_round2(position, prime)
+                    .map_err(|error| NetworkError::Participant { position, error })?,
+                Upstream::Failed(error) => return Err(NetworkError::Participant { position, error }),
+                Upstream::Round1(_) => return Err(NetworkError::OutOfTurn { position, round: 2 }),
+            }
+        }
+        Ok(coordinator.aggregate_round2()?)
+    })
+}
+
+/// One participant's side of the run. Returns early, dropping its channel
+/// ends, when the coordinator gives up on the run.
+fn participant(
+    mut signer: Signer,
+    msg: &[u8],
+    up_tx: mpsc::Sender<(usize, Upstream)>,
+    down_rx: mpsc::Receiver<Round2Inputs>,
+    tamper: &(impl Fn(usize, Round1Out) -> Round1Out + Sync),
+) {
+    let position = signer.position();
+    let message = match signer.round1() {
+        Ok(out) => Upstream::Round1(tamper(position, out)),
+        Err(e) => Upstream::Failed(e),
+    };
+    if up_tx.send((position, message)).is_err() {
+        return;
+    }
+    let Ok(inputs) = down_rx.recv() else {
+        return;
+    };
+    let message = match signer.round2(&inputs.outs_by_depth, msg, &inputs.merkle_path) {
+        Ok(prime) => Upstream::Round2(prime),
+        Err(e) => Upstream::Failed(e),
+    };
+    let _ = up_tx.send((position, message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::treemusig::{build_key_tree, tree_verify};
+
+    fn keys(n: usize) -> (BinTree<Secp256k1Point>, Vec<Keypair>) {
+        let keys: Vec<_> = (0..n).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        (tree, keys)
+    }
+
+    #[test]
+    fn threaded_run_verifies() {
+        for n in [1, 3, 8] {
+            let (tree, keys) = keys(n);
+            let sig = simulate_network(&tree, keys, b"over the wire", &Params::default()).unwrap();
+            assert!(tree_verify(tree.value(), b"over the wire", &sig), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn garbage_is_pinned_on_its_sender() {
+        let (tree, keys) = keys(8);
+        let err = simulate_with(&tree, keys, b"msg", &Params::default(), |position, out| {
+            if position == 5 { Round1Out(out.0[..1].to_vec()) } else { out }
+        })
+        .unwrap_err();
+        assert!(matches!(err, NetworkError::Participant { position: 5, error: Error::MalformedRound1(5) }));
+        assert_eq!(err.to_string(), "participant 5 failed: signer at leaf position 5 sent a malformed round 1 output");
+    }
+}
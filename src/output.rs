@@ -0,0 +1,237 @@
+use ark_usecase::progress::{ProgressSink, Stage};
+use colored::*;
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    Plain,
+    Colored,
+    Json,
+    /// Nothing but `document`s; for `--output json`.
+    Quiet,
+}
+
+impl OutputMode {
+    /// Picks the mode once at startup: `ARK_OUTPUT=plain|colored|json` wins,
+    /// otherwise colored unless `NO_COLOR` is set.
+    pub fn from_env() -> Self {
+        match env::var("ARK_OUTPUT").ok().as_deref().map(str::trim) {
+            Some("plain") => Self::Plain,
+            Some("json") => Self::Json,
+            Some("colored") => Self::Colored,
+            _ if env::var_os("NO_COLOR").is_some() => Self::Plain,
+            _ => Self::Colored,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Heading,
+    Prompt,
+    Info,
+    Success,
+    Failure,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Heading => "heading",
+            Kind::Prompt => "prompt",
+            Kind::Info => "info",
+            Kind::Success => "success",
+            Kind::Failure => "failure",
+        }
+    }
+}
+
+/// All user-facing printing goes through here. Every line is rendered into a
+/// single buffer and written with one `write_all`, so a reader never sees a
+/// partial ANSI sequence.
+pub struct Output<W: Write> {
+    mode: OutputMode,
+    sink: W,
+}
+
+impl Output<io::Stdout> {
+    pub fn stdout(mode: OutputMode) -> Self {
+        // Decide once instead of letting `colored` probe the terminal per call.
+        colored::control::set_override(mode == OutputMode::Colored);
+        Self::new(mode, io::stdout())
+    }
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(mode: OutputMode, sink: W) -> Self {
+        Self { mode, sink }
+    }
+
+    pub fn heading(&mut self, msg: &str) {
+        let colored = msg.green().to_string();
+        self.line(Kind::Heading, msg, colored);
+    }
+
+    /// `Enter <name>` with the name highlighted.
+    pub fn prompt(&mut self, name: &str) {
+        let plain = format!("Enter {}", name);
+        let colored = format!("Enter {}", name.yellow());
+        self.line(Kind::Prompt, &plain, colored);
+    }
+
+    pub fn info(&mut self, msg: &str) {
+        self.line(Kind::Info, msg, msg.to_string());
+    }
+
+    pub fn success(&mut self, msg: &str) {
+        let colored = msg.green().to_string();
+        self.line(Kind::Success, msg, colored);
+    }
+
+    pub fn failure(&mut self, msg: &str) {
+        let colored = msg.red().to_string();
+        self.line(Kind::Failure, msg, colored);
+    }
+
+    /// A failure the user must see even in `Quiet` mode, where it goes to
+    /// stderr so stdout stays parseable.
+    pub fn error(&mut self, msg: &str) {
+        if self.mode == OutputMode::Quiet {
+            eprintln!("{}", msg);
+        } else {
+            self.failure(msg);
+        }
+    }
+
+    /// Written as is in every mode, including `Quiet`.
+    pub fn document(&mut self, text: &str) {
+        self.write(format!("{}\n", text));
+    }
+
+    fn line(&mut self, kind: Kind, plain: &str, colored: String) {
+        let mut buf = match self.mode {
+            OutputMode::Quiet => return,
+            OutputMode::Plain => plain.to_string(),
+            OutputMode::Colored => colored,
+            OutputMode::Json => format!(
+                "{{\"kind\":\"{}\",\"message\":\"{}\"}}",
+                kind.name(),
+                json_escape(plain)
+            ),
+        };
+        buf.push('\n');
+        self.write(buf);
+    }
+
+    fn write(&mut self, buf: String) {
+        self.sink
+            .write_all(buf.as_bytes())
+            .and_then(|_| self.sink.flush())
+            .expect("failed to write output");
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `--progress`: a bar per stage on stderr, redrawn in place whenever it
+/// grows, so stdout keeps only the run's own output.
+#[derive(Default)]
+pub struct ProgressBar {
+    counts: Mutex<HashMap<Stage, usize>>,
+}
+
+impl ProgressSink for ProgressBar {
+    fn advance(&self, stage: Stage, done: usize, total: usize) {
+        const WIDTH: usize = 30;
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.entry(stage).or_default();
+        let filled = |count: usize| (count * WIDTH / total.max(1)).min(WIDTH);
+        let before = filled(*count);
+        let first = *count == 0;
+        *count += done;
+        let finished = *count >= total;
+        if !first && !finished && filled(*count) == before {
+            return;
+        }
+        let bar = format!("\r{:<10} [{:<width$}] {}/{}", stage.name(), "#".repeat(filled(*count)), count, total, width = WIDTH);
+        let mut err = io::stderr().lock();
+        let _ = err.write_all(bar.as_bytes());
+        if finished {
+            let _ = writeln!(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emit_all(mode: OutputMode) -> String {
+        let mut sink = Vec::new();
+        let mut out = Output::new(mode, &mut sink);
+        out.heading("heading");
+        out.prompt("n");
+        out.info("info");
+        out.success("SUCCESS");
+        out.failure("FAIL");
+        String::from_utf8(sink).unwrap()
+    }
+
+    #[test]
+    fn plain_mode_has_no_ansi() {
+        let s = emit_all(OutputMode::Plain);
+        assert!(!s.contains('\x1b'));
+        assert_eq!(s, "heading\nEnter n\ninfo\nSUCCESS\nFAIL\n");
+    }
+
+    #[test]
+    fn json_mode_has_no_ansi_and_one_object_per_line() {
+        let s = emit_all(OutputMode::Json);
+        assert!(!s.contains('\x1b'));
+        let lines: Vec<_> = s.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], r#"{"kind":"prompt","message":"Enter n"}"#);
+        assert_eq!(lines[3], r#"{"kind":"success","message":"SUCCESS"}"#);
+    }
+
+    #[test]
+    fn colored_mode_emits_ansi() {
+        colored::control::set_override(true);
+        let s = emit_all(OutputMode::Colored);
+        assert!(s.contains('\x1b'));
+        assert_eq!(s.lines().count(), 5);
+    }
+
+    #[test]
+    fn quiet_mode_only_writes_documents() {
+        assert_eq!(emit_all(OutputMode::Quiet), "");
+        let mut sink = Vec::new();
+        let mut out = Output::new(OutputMode::Quiet, &mut sink);
+        out.info("info");
+        out.document("{}");
+        assert_eq!(sink, b"{}\n");
+    }
+
+    #[test]
+    fn json_escape_handles_quotes_and_controls() {
+        assert_eq!(json_escape("a\"b\\c\nd\x01"), "a\\\"b\\\\c\\nd\\u0001");
+    }
+}
+
@@ -0,0 +1,140 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Lengths are in hex characters, which is what the user typed.
+    WrongLength { expected: usize, got: usize },
+    OddLength(usize),
+    InvalidChar { index: usize, ch: char },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::WrongLength { expected, got } => {
+                write!(f, "expected {} hex chars, got {}", expected, got)
+            }
+            ParseError::OddLength(len) => {
+                write!(f, "expected an even number of hex chars, got {}", len)
+            }
+            ParseError::InvalidChar { index, ch } => {
+                write!(f, "invalid hex char {:?} at position {}", ch, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn nibble(index: usize, ch: char) -> Result<u8, ParseError> {
+    ch.to_digit(16)
+        .map(|d| d as u8)
+        .ok_or(ParseError::InvalidChar { index, ch })
+}
+
+/// Strict: hex digits only (either case), no prefix, no whitespace.
+pub fn hex_any(s: &str) -> Result<Vec<u8>, ParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() % 2 != 0 {
+        return Err(ParseError::OddLength(chars.len()));
+    }
+    chars
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| Ok((nibble(2 * i, pair[0])? << 4) | nibble(2 * i + 1, pair[1])?))
+        .collect()
+}
+
+/// Strict, and exactly `N` bytes long.
+pub fn hex_exact<const N: usize>(s: &str) -> Result<[u8; N], ParseError> {
+    let got = s.chars().count();
+    if got != 2 * N {
+        return Err(ParseError::WrongLength { expected: 2 * N, got });
+    }
+    let bytes = hex_any(s)?;
+    let mut out = [0u8; N];
+    out.copy_from_slice(&bytes);
+    Ok(out)
+}
+
+/// Drops all whitespace and one leading `0x`/`0X`. Error positions refer to
+/// the cleaned string.
+fn clean(s: &str) -> String {
+    let squeezed: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    match squeezed.strip_prefix("0x").or_else(|| squeezed.strip_prefix("0X")) {
+        Some(rest) => rest.to_string(),
+        None => squeezed,
+    }
+}
+
+pub fn hex_any_lenient(s: &str) -> Result<Vec<u8>, ParseError> {
+    hex_any(&clean(s))
+}
+
+pub fn hex_exact_lenient<const N: usize>(s: &str) -> Result<[u8; N], ParseError> {
+    hex_exact(&clean(s))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn strict_accepts_plain_hex_any_case() {
+        assert_eq!(hex_any("00ffAb").unwrap(), vec![0x00, 0xff, 0xab]);
+        assert_eq!(hex_any("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn strict_rejects_prefix_and_whitespace() {
+        assert_eq!(hex_any("0x00"), Err(ParseError::InvalidChar { index: 1, ch: 'x' }));
+        assert_eq!(hex_any("00 ff"), Err(ParseError::OddLength(5)));
+        assert_eq!(hex_any(" 00ff "), Err(ParseError::InvalidChar { index: 0, ch: ' ' }));
+    }
+
+    #[test]
+    fn exact_length_error_message() {
+        let s = "a".repeat(63);
+        let err = hex_exact::<32>(&s).unwrap_err();
+        assert_eq!(err, ParseError::WrongLength { expected: 64, got: 63 });
+        assert_eq!(err.to_string(), "expected 64 hex chars, got 63");
+    }
+
+    #[test]
+    fn lenient_strips_prefix_and_whitespace() {
+        assert_eq!(hex_any_lenient("  0xDE ad\nbe\tef ").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(hex_exact_lenient::<2>("0X00 01").unwrap(), [0x00, 0x01]);
+        assert!(hex_any_lenient("0x0x00").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(bytes in proptest::collection::vec(any::<u8>(), 0..128)) {
+            prop_assert_eq!(hex_any(&to_hex(&bytes)).unwrap(), bytes.clone());
+            prop_assert_eq!(hex_any(&to_hex(&bytes).to_uppercase()).unwrap(), bytes);
+        }
+
+        #[test]
+        fn prop_round_trip_exact(bytes in any::<[u8; 32]>()) {
+            prop_assert_eq!(hex_exact::<32>(&to_hex(&bytes)).unwrap(), bytes);
+        }
+
+        #[test]
+        fn prop_lenient_round_trip(bytes in proptest::collection::vec(any::<u8>(), 0..64), ws in "[ \t\n]{0,4}") {
+            let s = format!("{}0x{}{}", ws, to_hex(&bytes), ws);
+            prop_assert_eq!(hex_any_lenient(&s).unwrap(), bytes);
+        }
+
+        // Arbitrary input must produce Ok or a ParseError, never a panic.
+        #[test]
+        fn prop_lenient_never_panics(s in "\\PC*") {
+            let _ = hex_any_lenient(&s);
+            let _ = hex_exact_lenient::<32>(&s);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Progress reports for long runs. A `ProgressSink` installed with
+//! `SessionBuilder::with_progress` hears about every unit of work as it
+//! finishes: a keypair, a `key_agg` call, or a leaf or node's share of a
+//! round.
+
+use std::fmt;
+
+/// The parts of a run that report progress, in the order they happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// One unit per keypair.
+    Keygen,
+    /// One unit per aggregated node of the key tree.
+    TreeBuild,
+    /// One unit per leaf drawing nonces, then per node aggregating them.
+    Round1,
+    /// One unit per leaf signing, then per node aggregating the partials.
+    Round2,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Keygen => "keygen",
+            Stage::TreeBuild => "tree build",
+            Stage::Round1 => "round 1",
+            Stage::Round2 => "round 2",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Receives progress as it happens. Leaves work in parallel with the
+/// `parallel` feature, so calls may come from several threads at once and
+/// in no particular order; only their sum per stage is meaningful.
+pub trait ProgressSink: Send + Sync {
+    /// `done` more units of `stage` finished, out of `total` for the stage.
+    fn advance(&self, stage: Stage, done: usize, total: usize);
+}
+
+/// One stage's reporting, handed to the code doing the work. Reports
+/// nothing without a sink.
+#[derive(Clone, Copy)]
+pub(crate) struct StageProgress<'a> {
+    sink: Option<&'a dyn ProgressSink>,
+    stage: Stage,
+    total: usize,
+}
+
+impl<'a> StageProgress<'a> {
+    pub(crate) fn new(sink: Option<&'a dyn ProgressSink>, stage: Stage, total: usize) -> Self {
+        StageProgress { sink, stage, total }
+    }
+
+    #[cfg(feature = "signing")]
+    pub(crate) fn silent() -> Self {
+        StageProgress { sink: None, stage: Stage::Keygen, total: 0 }
+    }
+
+    pub(crate) fn tick(&self) {
+        if let Some(sink) = self.sink {
+            sink.advance(self.stage, 1, self.total);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::{SessionBuilder, tree_verify};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Sums the units per stage and remembers the totals it was told.
+    #[derive(Default)]
+    struct Recorder {
+        seen: Mutex<HashMap<Stage, (usize, usize)>>,
+    }
+
+    impl ProgressSink for Recorder {
+        fn advance(&self, stage: Stage, done: usize, total: usize) {
+            let mut seen = self.seen.lock().unwrap();
+            let entry = seen.entry(stage).or_insert((0, total));
+            assert_eq!(entry.1, total, "{} changed its total", stage);
+            entry.0 += done;
+        }
+    }
+
+    #[test]
+    fn every_stage_reports_its_total_for_16_signers() {
+        let recorder = Arc::new(Recorder::default());
+        let builder = SessionBuilder::new().with_progress(recorder.clone());
+        let keys = builder.generate_keys(16, Keypair::generate);
+        let origins = builder.key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let session = builder.session(&origins.tree, &secret_keys).unwrap();
+        let session = session.round1().unwrap().round2(b"progress").unwrap();
+        assert!(tree_verify(origins.tree.value(), b"progress", session.signature()));
+
+        let seen = recorder.seen.lock().unwrap();
+        // 16 leaves under 15 aggregated nodes; each round visits all 31
+        assert_eq!(seen[&Stage::Keygen], (16, 16));
+        assert_eq!(seen[&Stage::TreeBuild], (15, 15));
+        assert_eq!(seen[&Stage::Round1], (31, 31));
+        assert_eq!(seen[&Stage::Round2], (31, 31));
+    }
+}
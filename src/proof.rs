@@ -0,0 +1,225 @@
+//! Inclusion proofs: evidence, for a third party holding only the root key,
+//! that one signer's key is a leaf of the key tree behind it.
+//!
+//! Encoding, version 1, all keys 33-byte compressed:
+//!
+//! | bytes          | field                                          |
+//! |----------------|------------------------------------------------|
+//! | 1              | version                                        |
+//! | 33             | leaf key                                       |
+//! | 33             | root key                                       |
+//! | 2              | number of path entries, big-endian             |
+//! | 34 per entry   | side (0 left, 1 right), then the sibling key   |
+//!
+//! Path entries are nearest first, as `verify_merkle_path` takes them.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use nested_musig2::params::Params;
+use std::fmt;
+
+use crate::bintree::BinTree;
+use crate::encoding::{DecodeError, POINT_LEN, point_from_bytes, point_to_bytes};
+use crate::error::Error;
+use crate::indexed::{IndexedTree, Side};
+use crate::treemusig::verify_merkle_path;
+
+/// Bumped whenever the encoding changes.
+pub const PROOF_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    Version(u8),
+    Truncated,
+    InvalidSide(u8),
+    TrailingBytes(usize),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::Version(v) => {
+                write!(f, "proof version {} is not supported (expected {})", v, PROOF_VERSION)
+            }
+            ProofError::Truncated => write!(f, "proof is truncated"),
+            ProofError::InvalidSide(b) => write!(f, "invalid side byte {} in proof path", b),
+            ProofError::TrailingBytes(n) => write!(f, "{} bytes left after the proof", n),
+            ProofError::Decode(e) => write!(f, "malformed proof: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProofError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for ProofError {
+    fn from(e: DecodeError) -> Self {
+        ProofError::Decode(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InclusionProof {
+    pub leaf: Secp256k1Point,
+    /// Sibling keys from the leaf up, nearest first, each with the side it
+    /// sits on.
+    pub path: Vec<(Side, Secp256k1Point)>,
+    pub root: Secp256k1Point,
+}
+
+impl InclusionProof {
+    /// The proof for the first leaf of `tree` holding `leaf`.
+    pub fn for_leaf(tree: &BinTree<Secp256k1Point>, leaf: &Secp256k1Point) -> Result<Self, Error> {
+        let indexed = IndexedTree::from_tree(tree);
+        let idx = indexed.find_leaf(leaf).ok_or_else(|| Error::NotALeaf(leaf.clone()))?;
+        Ok(InclusionProof {
+            leaf: leaf.clone(),
+            path: indexed.sided_path_at(idx),
+            root: tree.value().clone(),
+        })
+    }
+
+    /// Checks the path against the root key in the proof. Only meaningful
+    /// to a verifier who already knows that root is the one they care
+    /// about; otherwise use `verify_for_root`.
+    pub fn verify(&self, params: &Params) -> bool {
+        self.verify_for_root(&self.root, params)
+    }
+
+    /// Checks that the path leads from the leaf to `root`, and that `root`
+    /// is the key the proof claims.
+    pub fn verify_for_root(&self, root: &Secp256k1Point, params: &Params) -> bool {
+        *root == self.root && verify_merkle_path(root, &self.leaf, &self.path, params)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![PROOF_VERSION];
+        bytes.extend(point_to_bytes(&self.leaf));
+        bytes.extend(point_to_bytes(&self.root));
+        let count = u16::try_from(self.path.len()).expect("a key tree is far shallower than 2^16 levels");
+        bytes.extend_from_slice(&count.to_be_bytes());
+        for (side, sibling) in &self.path {
+            bytes.push(match side {
+                Side::Left => 0,
+                Side::Right => 1,
+            });
+            bytes.extend(point_to_bytes(sibling));
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofError> {
+        let (&version, rest) = bytes.split_first().ok_or(ProofError::Truncated)?;
+        if version != PROOF_VERSION {
+            return Err(ProofError::Version(version));
+        }
+        let (leaf, rest) = take(rest, POINT_LEN)?;
+        let (root, rest) = take(rest, POINT_LEN)?;
+        let (count, mut rest) = take(rest, 2)?;
+        let count = u16::from_be_bytes([count[0], count[1]]) as usize;
+
+        let mut path = Vec::new();
+        for _ in 0..count {
+            let (entry, tail) = take(rest, 1 + POINT_LEN)?;
+            let side = match entry[0] {
+                0 => Side::Left,
+                1 => Side::Right,
+                b => return Err(ProofError::InvalidSide(b)),
+            };
+            path.push((side, point_from_bytes(&entry[1..])?));
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(ProofError::TrailingBytes(rest.len()));
+        }
+        Ok(InclusionProof {
+            leaf: point_from_bytes(leaf)?,
+            path,
+            root: point_from_bytes(root)?,
+        })
+    }
+}
+
+fn take(bytes: &[u8], n: usize) -> Result<(&[u8], &[u8]), ProofError> {
+    if bytes.len() < n {
+        return Err(ProofError::Truncated);
+    }
+    Ok(bytes.split_at(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::nth_point;
+    use crate::treemusig::build_key_tree;
+
+    /// A tree over `first * G` onwards, so trees from different `first`s
+    /// share no key.
+    fn key_tree(first: u64, n: usize) -> (BinTree<Secp256k1Point>, Vec<Secp256k1Point>) {
+        let pubkeys: Vec<_> = (first..first + n as u64).map(nth_point).collect();
+        (build_key_tree(pubkeys.clone()).unwrap(), pubkeys)
+    }
+
+    #[test]
+    fn every_leaf_proves_and_round_trips() {
+        let params = Params::default();
+        for n in [1, 2, 5, 8] {
+            let (tree, pubkeys) = key_tree(1, n);
+            for pk in &pubkeys {
+                let proof = InclusionProof::for_leaf(&tree, pk).unwrap();
+                assert!(proof.verify_for_root(tree.value(), &params), "n = {}", n);
+                let bytes = proof.to_bytes();
+                assert_eq!(bytes.len(), 1 + 2 * POINT_LEN + 2 + proof.path.len() * (1 + POINT_LEN));
+                let decoded = InclusionProof::from_bytes(&bytes).unwrap();
+                assert_eq!(decoded, proof);
+                assert!(decoded.verify(&params));
+            }
+        }
+    }
+
+    #[test]
+    fn wrong_root_and_flipped_side_fail() {
+        let params = Params::default();
+        let (tree, pubkeys) = key_tree(1, 5);
+        let (other, _) = key_tree(100, 5);
+        let proof = InclusionProof::for_leaf(&tree, &pubkeys[2]).unwrap();
+        assert!(!proof.verify_for_root(other.value(), &params));
+
+        // a proof rewritten to claim the other root fails too
+        let mut claimed = proof.clone();
+        claimed.root = other.value().clone();
+        assert!(!claimed.verify(&params));
+
+        // side byte of the first path entry
+        let mut bytes = proof.to_bytes();
+        let side = 1 + 2 * POINT_LEN + 2;
+        bytes[side] ^= 1;
+        assert!(!InclusionProof::from_bytes(&bytes).unwrap().verify(&params));
+        bytes[side] = 2;
+        assert_eq!(InclusionProof::from_bytes(&bytes).unwrap_err(), ProofError::InvalidSide(2));
+    }
+
+    #[test]
+    fn rejects_bad_encodings() {
+        let (tree, pubkeys) = key_tree(1, 4);
+        let bytes = InclusionProof::for_leaf(&tree, &pubkeys[0]).unwrap().to_bytes();
+        for len in 0..bytes.len() {
+            assert_eq!(InclusionProof::from_bytes(&bytes[..len]).unwrap_err(), ProofError::Truncated, "len = {}", len);
+        }
+        let mut bad = bytes.clone();
+        bad[0] = 9;
+        assert_eq!(InclusionProof::from_bytes(&bad).unwrap_err(), ProofError::Version(9));
+        let mut bad = bytes.clone();
+        bad.push(0);
+        assert_eq!(InclusionProof::from_bytes(&bad).unwrap_err(), ProofError::TrailingBytes(1));
+
+        let stranger = nth_point(100);
+        assert!(matches!(InclusionProof::for_leaf(&tree, &stranger), Err(Error::NotALeaf(_))));
+    }
+}
@@ -0,0 +1,217 @@
+//! The coordinator and signer sides of a signing run over async byte
+//! streams, speaking the `wire` format. Anything that implements
+//! `AsyncRead + AsyncWrite` works: a TCP socket, a pipe to a child process,
+//! or an in-memory duplex in tests.
+//!
+//! Each signer has its own stream, and the coordinator knows which leaf
+//! position is on the other end of each one.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use nested_musig2::params::Params;
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::bintree::BinTree;
+use crate::coordinator::Coordinator;
+use crate::error::Error;
+use crate::signer::Signer;
+use crate::treemusig::Signature;
+use crate::wire::{LEN_PREFIX, WireError, WireMsg, body_len};
+
+#[derive(Debug)]
+pub enum RemoteError {
+    Io(io::Error),
+    Wire(WireError),
+    Signing(Error),
+    /// The coordinator needs one stream per leaf.
+    StreamCount { expected: usize, got: usize },
+    /// The peer sent a well-formed message that does not belong at this
+    /// point of the run, or claims a position other than its stream's.
+    Unexpected { position: usize, round: u8 },
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::Io(e) => write!(f, "{}", e),
+            RemoteError::Wire(e) => write!(f, "{}", e),
+            RemoteError::Signing(e) => write!(f, "{}", e),
+            RemoteError::StreamCount { expected, got } => write!(f, "expected {} signer streams, got {}", expected, got),
+            RemoteError::Unexpected { position, round } => {
+                write!(f, "unexpected message on the stream for position {} in round {}", position, round)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RemoteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RemoteError::Io(e) => Some(e),
+            RemoteError::Wire(e) => Some(e),
+            RemoteError::Signing(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RemoteError {
+    fn from(e: io::Error) -> Self {
+        RemoteError::Io(e)
+    }
+}
+
+impl From<WireError> for RemoteError {
+    fn from(e: WireError) -> Self {
+        RemoteError::Wire(e)
+    }
+}
+
+impl From<Error> for RemoteError {
+    fn from(e: Error) -> Self {
+        RemoteError::Signing(e)
+    }
+}
+
+pub async fn write_msg<W: AsyncWrite + Unpin>(stream: &mut W, msg: &WireMsg) -> Result<(), RemoteError> {
+    stream.write_all(&msg.to_frame()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Reads one frame. The length prefix is checked before the body is read,
+/// so an oversized prefix never allocates.
+pub async fn read_msg<R: AsyncRead + Unpin>(stream: &mut R) -> Result<WireMsg, RemoteError> {
+    let mut prefix = [0u8; LEN_PREFIX];
+    stream.read_exact(&mut prefix).await?;
+    let mut body = vec![0u8; body_len(prefix)?];
+    stream.read_exact(&mut body).await?;
+    Ok(WireMsg::from_body(&body)?)
+}
+
+/// Runs both rounds for `tree` with one signer at the end of each stream,
+/// `streams[i]` being the signer at leaf position `i`.
+pub async fn coordinate<S: AsyncRead + AsyncWrite + Unpin>(
+    tree: &BinTree<Secp256k1Point>,
+    streams: &mut [S],
+    params: &Params,
+) -> Result<Signature, RemoteError> {
+    let mut coordinator = Coordinator::with_params(tree, params.clone());
+    if streams.len() != coordinator.signer_count() {
+        return Err(RemoteError::StreamCount { expected: coordinator.signer_count(), got: streams.len() });
+    }
+
+    for (position, stream) in streams.iter_mut().enumerate() {
+        match read_msg(stream).await? {
+            WireMsg::Round1 { position: claimed, out } if claimed as usize == position => {
+                coordinator.add_round1(position, out)?
+            }
+            _ => return Err(RemoteError::Unexpected { position, round: 1 }),
+        }
+    }
+    coordinator.aggregate_round1()?;
+
+    for (position, stream) in streams.iter_mut().enumerate() {
+        let (outs_by_depth, merkle_path) = coordinator.round2_inputs(position)?;
+        write_msg(stream, &WireMsg::Round2Inputs { outs_by_depth, merkle_path }).await?;
+    }
+    for (position, stream) in streams.iter_mut().enumerate() {
+        match read_msg(stream).await? {
+            WireMsg::Round2 { position: claimed, prime } if claimed as usize == position => {
+                coordinator.add_round2(position, prime)?
+            }
+            _ => return Err(RemoteError::Unexpected { position, round: 2 }),
+        }
+    }
+    Ok(coordinator.aggregate_round2()?)
+}
+
+/// The signer's side: sends its round 1 output, waits for its round 2
+/// inputs and sends back its partial signature for `msg`.
+pub async fn participate<S: AsyncRead + AsyncWrite + Unpin>(signer: &mut Signer, msg: &[u8], stream: &mut S) -> Result<(), RemoteError> {
+    let position = signer.position();
+    let out = signer.round1()?;
+    write_msg(stream, &WireMsg::Round1 { position: position as u32, out }).await?;
+
+    let WireMsg::Round2Inputs { outs_by_depth, merkle_path } = read_msg(stream).await? else {
+        return Err(RemoteError::Unexpected { position, round: 2 });
+    };
+    let prime = signer.round2(&outs_by_depth, msg, &merkle_path)?;
+    write_msg(stream, &WireMsg::Round2 { position: position as u32, prime }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::signer::signers_for_tree;
+    use crate::treemusig::{build_key_tree, tree_verify};
+    use tokio::io::{DuplexStream, duplex};
+
+    const MSG: &[u8] = b"over a duplex";
+
+    fn setup(n: usize) -> (BinTree<Secp256k1Point>, Vec<Signer>) {
+        let keys: Vec<_> = (0..n).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let signers = signers_for_tree(&tree, keys, &Params::default()).unwrap();
+        (tree, signers)
+    }
+
+    /// One duplex per signer, with each signer's end driven on its own task.
+    fn spawn_signers(signers: Vec<Signer>) -> (Vec<DuplexStream>, Vec<tokio::task::JoinHandle<Result<(), RemoteError>>>) {
+        signers
+            .into_iter()
+            .map(|mut signer| {
+                let (ours, mut theirs) = duplex(4096);
+                let task = tokio::spawn(async move { participate(&mut signer, MSG, &mut theirs).await });
+                (ours, task)
+            })
+            .unzip()
+    }
+
+    #[tokio::test]
+    async fn four_signers_over_duplex_streams() {
+        let (tree, signers) = setup(4);
+        let (mut streams, tasks) = spawn_signers(signers);
+        let sig = coordinate(&tree, &mut streams, &Params::default()).await.unwrap();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert!(tree_verify(tree.value(), MSG, &sig));
+    }
+
+    #[tokio::test]
+    async fn malformed_frames_are_errors() {
+        let (mut ours, mut theirs) = duplex(4096);
+        let mut frame = WireMsg::Round1 { position: 0, out: nested_musig2::round1::sign_round1(2).unwrap().0 }.to_frame();
+        frame[LEN_PREFIX] = 0xee;
+        ours.write_all(&frame).await.unwrap();
+        assert!(matches!(read_msg(&mut theirs).await, Err(RemoteError::Wire(WireError::Version(0xee)))));
+
+        // the stream ends halfway through a body
+        ours.write_all(&[0, 0, 0, 10, 1, 1]).await.unwrap();
+        drop(ours);
+        let err = read_msg(&mut theirs).await.unwrap_err();
+        assert!(matches!(err, RemoteError::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof));
+
+        let (mut ours, mut theirs) = duplex(64);
+        ours.write_all(&[0xff; LEN_PREFIX]).await.unwrap();
+        assert!(matches!(read_msg(&mut theirs).await, Err(RemoteError::Wire(WireError::BodyTooLarge(_)))));
+    }
+
+    #[tokio::test]
+    async fn a_signer_claiming_another_position_is_rejected() {
+        let (tree, mut signers) = setup(2);
+        let (mut streams, _) = spawn_signers(signers.split_off(1));
+        let (ours, mut theirs) = duplex(4096);
+        streams.insert(0, ours);
+        let out = signers[0].round1().unwrap();
+        write_msg(&mut theirs, &WireMsg::Round1 { position: 1, out }).await.unwrap();
+
+        let err = coordinate(&tree, &mut streams, &Params::default()).await.unwrap_err();
+        assert!(matches!(err, RemoteError::Unexpected { position: 0, round: 1 }));
+
+        let err = coordinate(&tree, &mut streams[..1], &Params::default()).await.unwrap_err();
+        assert_eq!(err.to_string(), "expected 2 signer streams, got 1");
+    }
+}
@@ -0,0 +1,69 @@
+//! A signing run summarised as one serializable value, for tools that read
+//! the demo's output rather than a person.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use serde::{Deserialize, Serialize};
+
+use crate::bintree::BinTree;
+use crate::encoding::{point_hex, signature_hex};
+use crate::parse::to_hex;
+use crate::treemusig::Signature;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningReport {
+    /// Number of signers.
+    pub n: usize,
+    pub height: usize,
+    pub leaf_count: usize,
+    /// Compressed root key, hex.
+    pub root_pubkey: String,
+    /// Every node's key, hex, by depth: `levels[0]` is the root alone and
+    /// the last entry holds the deepest leaves.
+    pub levels: Vec<Vec<String>>,
+    pub message: String,
+    pub signature: String,
+    pub verified: bool,
+}
+
+impl SigningReport {
+    pub fn new(tree: &BinTree<Secp256k1Point>, msg: &[u8], sig: &Signature, verified: bool) -> Self {
+        SigningReport {
+            n: tree.leaf_count(),
+            height: tree.height(),
+            leaf_count: tree.leaf_count(),
+            root_pubkey: point_hex(tree.value()),
+            levels: tree.levels().into_iter().map(|level| level.into_iter().map(point_hex).collect()).collect(),
+            message: to_hex(msg),
+            signature: signature_hex(sig),
+            verified,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a report always serializes")
+    }
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::{build_key_tree, tree_sign, tree_verify};
+    use std::collections::HashMap;
+
+    #[test]
+    fn report_round_trips_through_json() {
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let sig = tree_sign(&tree, &secret_keys, b"json").unwrap();
+        let report = SigningReport::new(&tree, b"json", &sig, tree_verify(tree.value(), b"json", &sig));
+
+        let back: SigningReport = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(back, report);
+        assert_eq!(report.levels.iter().map(Vec::len).sum::<usize>(), tree.levels().iter().map(Vec::len).sum::<usize>());
+        assert_eq!(report.levels[0], vec![report.root_pubkey.clone()]);
+        assert_eq!(report.message, "6a736f6e");
+        assert!(report.verified);
+    }
+}
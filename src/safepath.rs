@@ -0,0 +1,150 @@
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum PathError {
+    Workdir { path: PathBuf, error: io::Error },
+    /// Leads out of `--workdir`, through `..` or as an absolute path.
+    OutsideWorkdir(PathBuf),
+    /// A symlink where the path, or under `--workdir` any directory on the
+    /// way to it, should be.
+    Symlink(PathBuf),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Workdir { path, error } => write!(f, "cannot use {} as --workdir: {}", path.display(), error),
+            PathError::OutsideWorkdir(path) => write!(f, "{} is outside --workdir", path.display()),
+            PathError::Symlink(path) => write!(f, "refusing to follow the symlink at {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for PathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PathError::Workdir { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Checks the paths of files read or written for the command line. A
+/// symlink planted where a secret key file or session is written would
+/// send it elsewhere, so the file itself is never a symlink. With a
+/// `--workdir`, relative paths are taken from it, absolute ones must be
+/// inside it, and nothing below it may be a symlink, so `..` is the only
+/// way out and is refused too.
+///
+/// The checks look at the file system before the file is opened; whoever
+/// can swap a directory in between can still win the race.
+#[derive(Debug, Clone, Default)]
+pub struct SafePath {
+    workdir: Option<PathBuf>,
+}
+
+impl SafePath {
+    pub fn new(workdir: Option<&Path>) -> Result<Self, PathError> {
+        let workdir = workdir
+            .map(|path| fs::canonicalize(path).map_err(|error| PathError::Workdir { path: path.to_path_buf(), error }))
+            .transpose()?;
+        Ok(SafePath { workdir })
+    }
+
+    /// The path to open for `path`, which need not exist yet.
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf, PathError> {
+        let Some(workdir) = &self.workdir else {
+            refuse_symlink(path)?;
+            return Ok(path.to_path_buf());
+        };
+        let relative = if path.is_absolute() {
+            path.strip_prefix(workdir).map_err(|_| PathError::OutsideWorkdir(path.to_path_buf()))?
+        } else {
+            path
+        };
+        let mut resolved = workdir.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+                    refuse_symlink(&resolved)?;
+                }
+                Component::CurDir => {}
+                // every directory pushed was checked not to be a symlink,
+                // so popping one goes back where it came from
+                Component::ParentDir if resolved != *workdir => {
+                    resolved.pop();
+                }
+                _ => return Err(PathError::OutsideWorkdir(path.to_path_buf())),
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+/// A path that does not exist yet is fine; opening it reports the rest.
+fn refuse_symlink(path: &Path) -> Result<(), PathError> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => Err(PathError::Symlink(path.to_path_buf())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workdir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ark-usecase-workdir-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    #[test]
+    fn paths_stay_inside_the_workdir() {
+        let dir = workdir("inside");
+        let paths = SafePath::new(Some(&dir)).unwrap();
+        assert_eq!(paths.resolve(Path::new("keys.txt")).unwrap(), dir.join("keys.txt"));
+        assert_eq!(paths.resolve(Path::new("./sub/../sub/sig.bin")).unwrap(), dir.join("sub/sig.bin"));
+        assert_eq!(paths.resolve(&dir.join("sub/tree.txt")).unwrap(), dir.join("sub/tree.txt"));
+
+        for escape in ["..", "sub/../../x", "../outside"] {
+            assert!(matches!(paths.resolve(Path::new(escape)), Err(PathError::OutsideWorkdir(_))), "{}", escape);
+        }
+        let outside = std::env::temp_dir().join("ark-usecase-elsewhere");
+        assert!(matches!(paths.resolve(&outside), Err(PathError::OutsideWorkdir(_))));
+
+        // without a workdir, paths are used as given
+        let anywhere = SafePath::default();
+        assert_eq!(anywhere.resolve(Path::new("../x")).unwrap(), PathBuf::from("../x"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_workdir() {
+        let err = SafePath::new(Some(Path::new("/nonexistent/ark-usecase-workdir"))).unwrap_err();
+        assert!(matches!(err, PathError::Workdir { .. }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_refused() {
+        use std::os::unix::fs::symlink;
+        let dir = workdir("symlink");
+        symlink(std::env::temp_dir(), dir.join("out")).unwrap();
+        symlink(dir.join("missing"), dir.join("sub/keys.txt")).unwrap();
+
+        let paths = SafePath::new(Some(&dir)).unwrap();
+        for linked in ["out", "out/keys.txt", "sub/keys.txt"] {
+            assert!(matches!(paths.resolve(Path::new(linked)), Err(PathError::Symlink(_))), "{}", linked);
+        }
+        // the file itself is checked without a workdir as well
+        let err = SafePath::default().resolve(&dir.join("sub/keys.txt")).unwrap_err();
+        assert!(matches!(err, PathError::Symlink(_)));
+        assert!(SafePath::default().resolve(&dir.join("sub/sig.bin")).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! Owners for secret material that wipe it when dropped and never print it.
+
+use crypto_rs::secp256k1::Secp256k1Scalar;
+use nested_musig2::round1::Round1State;
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// A leaf's long-term secret key.
+pub(crate) struct SecretScalar(Secp256k1Scalar);
+
+impl SecretScalar {
+    pub(crate) fn new(sk: Secp256k1Scalar) -> Self {
+        SecretScalar(sk)
+    }
+
+    pub(crate) fn expose(&self) -> &Secp256k1Scalar {
+        &self.0
+    }
+}
+
+impl Zeroize for SecretScalar {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretScalar {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretScalar {}
+
+impl fmt::Debug for SecretScalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretScalar(<redacted>)")
+    }
+}
+
+/// A leaf's secret round 1 nonces, which must be used for one signature only.
+pub(crate) struct SecretNonces(Round1State);
+
+impl SecretNonces {
+    pub(crate) fn new(state: Round1State) -> Self {
+        SecretNonces(state)
+    }
+
+    pub(crate) fn expose(&self) -> &Round1State {
+        &self.0
+    }
+
+    /// Hands the nonces to `sign_prime`, which consumes them; what is left
+    /// behind here is empty.
+    pub(crate) fn into_inner(mut self) -> Round1State {
+        Round1State(std::mem::take(&mut self.0.0))
+    }
+}
+
+impl Zeroize for SecretNonces {
+    fn zeroize(&mut self) {
+        self.0.0.zeroize();
+    }
+}
+
+impl Drop for SecretNonces {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretNonces {}
+
+impl fmt::Debug for SecretNonces {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretNonces(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::round1_state_to_bytes;
+    use nested_musig2::keygen::keygen;
+
+    #[test]
+    fn debug_never_shows_secrets() {
+        let sk = keygen().sk;
+        let shown = format!("{:?}", SecretScalar::new(sk.clone()));
+        assert_eq!(shown, "SecretScalar(<redacted>)");
+        assert!(!shown.contains(&format!("{:?}", sk)));
+
+        let (_, state) = nested_musig2::round1::sign_round1(2).unwrap();
+        assert_eq!(format!("{:?}", SecretNonces::new(state)), "SecretNonces(<redacted>)");
+    }
+
+    #[test]
+    fn nonces_move_out_whole() {
+        let (_, state) = nested_musig2::round1::sign_round1(2).unwrap();
+        let before = round1_state_to_bytes(&state);
+        let nonces = SecretNonces::new(state);
+        assert_eq!(round1_state_to_bytes(nonces.expose()), before);
+        assert_eq!(round1_state_to_bytes(&nonces.into_inner()), before);
+    }
+}
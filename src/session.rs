@@ -0,0 +1,340 @@
+//! Signing sessions that outlive the process: run round 1, save, and run
+//! round 2 later from the saved file.
+//!
+//! A saved session holds the leaf secret keys and, between the rounds, their
+//! secret nonces, so the file is as sensitive as a key file and `save`
+//! creates it readable by its owner only. Round 2 drops the nonces and keys
+//! and marks the session spent; save again afterwards so the file can never
+//! be loaded to sign a second message.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::params::Params;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, io};
+#[cfg(feature = "cli")]
+use std::{fs, io::Write, path::Path};
+
+use crate::audit::{AuditReport, Phase, audit_state};
+use crate::bintree::BinTree;
+use crate::encoding::{
+    DecodeError, point_from_bytes, point_to_bytes, round1_out_from_bytes, round1_out_to_bytes,
+    round1_state_from_bytes, round1_state_to_bytes, scalar_from_bytes, scalar_to_bytes,
+};
+use crate::error::Error;
+use crate::indexed::IndexedTree;
+use crate::secret::{SecretNonces, SecretScalar};
+use crate::treemusig::{NodeState, Signature, StateMap, check_audit, leaf_states, root_signature, round1, round2};
+
+/// Bumped whenever the file layout changes.
+pub const SESSION_VERSION: u32 = 5;
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    Decode(DecodeError),
+    Version(u32),
+    /// The session already signed a message.
+    Spent,
+    /// A node record names an index past the end of the key tree.
+    UnknownNode(u64),
+    /// Two node records name the same node.
+    DuplicateNode(u64),
+    /// The node records do not fit the key tree, e.g. a leaf without its
+    /// secret key or an internal node with one.
+    State(AuditReport),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Io(e) => write!(f, "{}", e),
+            SessionError::Encoding(e) => write!(f, "malformed session file: {}", e),
+            SessionError::Decode(e) => write!(f, "malformed session file: {}", e),
+            SessionError::Version(v) => {
+                write!(f, "session file version {} is not supported (expected {})", v, SESSION_VERSION)
+            }
+            SessionError::Spent => write!(f, "session already signed a message and cannot be used again"),
+            SessionError::UnknownNode(idx) => write!(f, "malformed session file: no node {} in the key tree", idx),
+            SessionError::DuplicateNode(idx) => write!(f, "malformed session file: node {} is recorded twice", idx),
+            SessionError::State(report) => write!(f, "malformed session file: node states do not fit the key tree:\n{}", report),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SessionError::Io(e) => Some(e),
+            SessionError::Encoding(e) => Some(e.as_ref()),
+            SessionError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> Self {
+        SessionError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SessionError {
+    fn from(e: bincode::Error) -> Self {
+        SessionError::Encoding(e)
+    }
+}
+
+impl From<DecodeError> for SessionError {
+    fn from(e: DecodeError) -> Self {
+        SessionError::Decode(e)
+    }
+}
+
+/// On-disk form. Curve types are stored as their byte encodings.
+#[derive(Serialize, Deserialize)]
+struct SessionFile {
+    version: u32,
+    /// Set once round 2 has run.
+    spent: bool,
+    tree: BinTree<Vec<u8>>,
+    nodes: Vec<NodeRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeRecord {
+    /// The node's `IndexedTree` index, i.e. its preorder position in `tree`.
+    node: u64,
+    secret_key: Option<Vec<u8>>,
+    state: Option<Vec<Vec<u8>>>,
+    out: Option<Vec<Vec<u8>>>,
+    out_internal: Option<Vec<Vec<u8>>>,
+}
+
+/// An owned signing run over one key tree, for signers whose secrets live
+/// in this process. Unlike `SigningSession` it can be saved after round 1.
+/// Session files do not record protocol parameters, so a session always
+/// signs under `Params::default()`.
+pub struct Session {
+    tree: IndexedTree<Secp256k1Point>,
+    state_map: StateMap,
+    spent: bool,
+}
+
+impl Session {
+    pub fn new(tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>) -> Result<Self, Error> {
+        let tree = IndexedTree::from_tree(tree);
+        let state_map = leaf_states(&tree, secret_keys)?;
+        check_audit(&tree, &state_map, Phase::Setup)?;
+        Ok(Session { tree, state_map, spent: false })
+    }
+
+    /// The key the session signs for.
+    pub fn root_key(&self) -> &Secp256k1Point {
+        &self.tree.get(self.tree.root()).value
+    }
+
+    /// The key tree, e.g. for reporting on a loaded session.
+    pub fn key_tree(&self) -> BinTree<Secp256k1Point> {
+        self.tree.to_tree()
+    }
+
+    pub fn round1(&mut self) -> Result<(), Error> {
+        round1(&self.tree, &mut self.state_map, &Params::default())?;
+        check_audit(&self.tree, &self.state_map, Phase::Round1)?;
+        Ok(())
+    }
+
+    /// Signs `msg`. Round 2 drops every leaf's nonces and secret key and
+    /// marks the session spent, so a session signs at most one message.
+    pub fn round2(&mut self, msg: &[u8]) -> Result<Signature, Error> {
+        round2(&self.tree, &mut self.state_map, msg, &Params::default())?;
+        self.spent = true;
+        check_audit(&self.tree, &self.state_map, Phase::Round2)?;
+        root_signature(&self.tree, &self.state_map)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SessionError> {
+        let nodes = self
+            .state_map
+            .iter()
+            .map(|(&idx, state)| NodeRecord {
+                node: idx as u64,
+                secret_key: state.secret_key.as_ref().map(|sk| scalar_to_bytes(sk.expose())),
+                state: state.state.as_ref().map(|nonces| round1_state_to_bytes(nonces.expose())),
+                out: state.out.as_ref().map(round1_out_to_bytes),
+                out_internal: state.out_internal.as_ref().map(round1_out_to_bytes),
+            })
+            .collect();
+        let file = SessionFile {
+            version: SESSION_VERSION,
+            spent: self.spent,
+            tree: self.tree.to_tree().map(point_to_bytes),
+            nodes,
+        };
+        Ok(bincode::serialize(&file)?)
+    }
+
+    /// Refuses a session saved after round 2.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SessionError> {
+        let file: SessionFile = bincode::deserialize(bytes)?;
+        if file.version != SESSION_VERSION {
+            return Err(SessionError::Version(file.version));
+        }
+        if file.spent {
+            return Err(SessionError::Spent);
+        }
+        let tree = IndexedTree::from_tree(&decode_tree(&file.tree)?);
+        let mut state_map = StateMap::new();
+        for record in &file.nodes {
+            if record.node >= tree.node_count() as u64 {
+                return Err(SessionError::UnknownNode(record.node));
+            }
+            let (idx, state) = decode_record(record)?;
+            if state_map.insert(idx, state).is_some() {
+                return Err(SessionError::DuplicateNode(record.node));
+            }
+        }
+        // Saved either before round 1 or between the rounds; checked in
+        // every build, as the file is input rather than state built here.
+        let phase = if state_map.values().any(|state| state.out.is_some()) { Phase::Round1 } else { Phase::Setup };
+        let report = audit_state(&tree, &state_map, phase);
+        if !report.is_clean() {
+            return Err(SessionError::State(report));
+        }
+        Ok(Session { tree, state_map, spent: false })
+    }
+
+    /// Writes the session to `path`, which on Unix is left readable and
+    /// writable by its owner only.
+    #[cfg(feature = "cli")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SessionError> {
+        let bytes = self.to_bytes()?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // `mode` only applies when the file is created here
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        Ok(file.write_all(&bytes)?)
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SessionError> {
+        Session::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// Stored aggregates are decoded as given rather than recomputed, so a
+/// loaded tree signs under exactly the root key it was saved with. Runs on
+/// `fold`'s explicit stack, however deep the stored tree.
+fn decode_tree(tree: &BinTree<Vec<u8>>) -> Result<BinTree<Secp256k1Point>, DecodeError> {
+    tree.fold(
+        |pk| Ok(BinTree::Leaf(point_from_bytes(pk)?)),
+        |left, right, value| Ok(BinTree::from_parts(left?, right.transpose()?, point_from_bytes(value)?)),
+    )
+}
+
+fn decode_record(record: &NodeRecord) -> Result<(usize, NodeState), DecodeError> {
+    let state = NodeState {
+        secret_key: record.secret_key.as_deref().map(scalar_from_bytes).transpose()?.map(SecretScalar::new),
+        state: record.state.as_deref().map(round1_state_from_bytes).transpose()?.map(SecretNonces::new),
+        out: record.out.as_deref().map(round1_out_from_bytes).transpose()?,
+        out_internal: record.out_internal.as_deref().map(round1_out_from_bytes).transpose()?,
+        out_prime: None,
+        state_prime: None,
+    };
+    Ok((record.node as usize, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::{build_key_tree, tree_sign, tree_verify};
+
+    fn keys(n: usize) -> (BinTree<Secp256k1Point>, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+        let keys: Vec<_> = (0..n).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        (tree, keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect())
+    }
+
+    #[test]
+    fn rounds_in_separate_sessions_match_single_shot() {
+        for n in [2, 5] {
+            let (tree, secret_keys) = keys(n);
+            let msg = b"resumed";
+            let single = tree_sign(&tree, &secret_keys, msg).unwrap();
+
+            let mut first = Session::new(&tree, &secret_keys).unwrap();
+            first.round1().unwrap();
+            let saved = first.to_bytes().unwrap();
+            drop(first);
+
+            let mut second = Session::from_bytes(&saved).unwrap();
+            assert!(second.root_key() == tree.value());
+            let resumed = second.round2(msg).unwrap();
+            // Nonces differ between runs, so the signatures do too; both must
+            // verify under the same root key.
+            assert!(tree_verify(tree.value(), msg, &single));
+            assert!(tree_verify(tree.value(), msg, &resumed), "n = {}", n);
+            assert!(matches!(Session::from_bytes(&second.to_bytes().unwrap()), Err(SessionError::Spent)));
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn save_and_load_through_a_file() {
+        let (tree, secret_keys) = keys(3);
+        let path = std::env::temp_dir().join(format!("ark-usecase-session-{}", std::process::id()));
+        let mut session = Session::new(&tree, &secret_keys).unwrap();
+        session.round1().unwrap();
+        session.save(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let mut loaded = Session::load(&path).unwrap();
+        let sig = loaded.round2(b"from disk").unwrap();
+        assert!(tree_verify(loaded.root_key(), b"from disk", &sig));
+
+        // Once round 2 ran, the re-saved session is refused, and in memory
+        // it keeps only the root's entry, with no nonces to sign with.
+        loaded.save(&path).unwrap();
+        assert!(matches!(Session::load(&path), Err(SessionError::Spent)));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded.round2(b"other"), Err(Error::MissingNodeState(_))));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(Session::from_bytes(&[1, 2, 3]), Err(SessionError::Encoding(_))));
+
+        let (tree, secret_keys) = keys(2);
+        let mut bytes = Session::new(&tree, &secret_keys).unwrap().to_bytes().unwrap();
+        // the version is the first field
+        bytes[0] = 9;
+        assert!(matches!(Session::from_bytes(&bytes), Err(SessionError::Version(9))));
+        assert!(matches!(Session::load("/nonexistent/ark-usecase-session"), Err(SessionError::Io(_))));
+    }
+
+    #[test]
+    fn rejects_records_that_do_not_fit_the_tree() {
+        let (tree, secret_keys) = keys(2);
+        let bytes = Session::new(&tree, &secret_keys).unwrap().to_bytes().unwrap();
+        let edited = |edit: fn(&mut SessionFile)| {
+            let mut file: SessionFile = bincode::deserialize(&bytes).unwrap();
+            edit(&mut file);
+            Session::from_bytes(&bincode::serialize(&file).unwrap())
+        };
+        // two leaves and their root
+        assert!(matches!(edited(|file| file.nodes[0].node = 3), Err(SessionError::UnknownNode(3))));
+        assert!(matches!(edited(|file| file.nodes[1].node = file.nodes[0].node), Err(SessionError::DuplicateNode(_))));
+        assert!(matches!(edited(|file| file.nodes[0].secret_key = None), Err(SessionError::State(_))));
+        assert!(matches!(edited(|file| file.nodes[0].node = 0), Err(SessionError::State(_))));
+    }
+}
@@ -0,0 +1,252 @@
+//! The two values a signing run hands to a verifier, as types with one
+//! encoding each: bytes, lowercase hex for `Display` and `FromStr`, and the
+//! same hex string through serde.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use nested_musig2::params::Params;
+use nested_musig2::round2::ver;
+use std::{fmt, str::FromStr};
+
+use crate::encoding::{
+    DecodeError, POINT_LEN, SIGNATURE_LEN, point_from_bytes, point_to_bytes, signature_from_bytes, signature_to_bytes,
+};
+use crate::parse::{hex_any, to_hex};
+use crate::treemusig::Signature;
+
+/// A key tree's root key: the aggregate of every signer's key, and all a
+/// verifier needs besides the signature. 33 bytes, compressed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AggregatedKey(Secp256k1Point);
+
+impl AggregatedKey {
+    pub fn new(point: Secp256k1Point) -> Self {
+        AggregatedKey(point)
+    }
+
+    pub fn as_point(&self) -> &Secp256k1Point {
+        &self.0
+    }
+
+    pub fn into_point(self) -> Secp256k1Point {
+        self.0
+    }
+
+    pub fn to_bytes(&self) -> [u8; POINT_LEN] {
+        point_to_bytes(&self.0).try_into().expect("points encode to POINT_LEN bytes")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        point_from_bytes(bytes).map(AggregatedKey)
+    }
+}
+
+impl From<Secp256k1Point> for AggregatedKey {
+    fn from(point: Secp256k1Point) -> Self {
+        AggregatedKey(point)
+    }
+}
+
+/// A signature from the root of a key tree: its point, then its 32-byte
+/// big-endian scalar, 65 bytes in all.
+#[derive(Clone)]
+pub struct TreeSignature(Signature);
+
+impl TreeSignature {
+    pub fn new(sig: Signature) -> Self {
+        TreeSignature(sig)
+    }
+
+    pub fn as_inner(&self) -> &Signature {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Signature {
+        self.0
+    }
+
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_LEN] {
+        signature_to_bytes(&self.0).try_into().expect("signatures encode to SIGNATURE_LEN bytes")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        signature_from_bytes(bytes).map(TreeSignature)
+    }
+
+    /// Whether this signs `msg` under `key`.
+    pub fn verify(&self, params: &Params, key: &AggregatedKey, msg: &[u8]) -> bool {
+        ver(params, key.as_point(), msg, &self.0)
+    }
+}
+
+impl From<Signature> for TreeSignature {
+    fn from(sig: Signature) -> Self {
+        TreeSignature(sig)
+    }
+}
+
+// Encodings are canonical, so equal bytes mean equal values.
+impl PartialEq for TreeSignature {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for TreeSignature {}
+
+/// Everything a verifier fixes up front, the protocol parameters and the
+/// key, so that checking many signatures under one key repeats neither.
+#[derive(Clone)]
+pub struct VerifyCtx {
+    params: Params,
+    key: AggregatedKey,
+}
+
+impl VerifyCtx {
+    pub fn new(params: Params, key: &AggregatedKey) -> Self {
+        VerifyCtx { params, key: key.clone() }
+    }
+
+    pub fn key(&self) -> &AggregatedKey {
+        &self.key
+    }
+
+    /// Whether `sig` signs `msg` under the context's key.
+    pub fn verify(&self, msg: &[u8], sig: &TreeSignature) -> bool {
+        sig.verify(&self.params, &self.key, msg)
+    }
+}
+
+/// `Debug`, `Display` and `FromStr` as hex, plus serde as the same string.
+macro_rules! hex_encoded {
+    ($ty:ident) => {
+        impl fmt::Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_tuple(stringify!($ty)).field(&self.to_string()).finish()
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&to_hex(&self.to_bytes()))
+            }
+        }
+
+        /// Strict hex, either case; no prefix or whitespace.
+        impl FromStr for $ty {
+            type Err = DecodeError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $ty::from_bytes(&hex_any(s)?)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let hex = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+                hex.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+hex_encoded!(AggregatedKey);
+hex_encoded!(TreeSignature);
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::encoding::SCALAR_LEN;
+    use crate::keys::Keypair;
+    use crate::treemusig::{build_key_tree, tree_sign};
+    use std::collections::HashMap;
+
+    fn signed(msg: &[u8]) -> (AggregatedKey, TreeSignature) {
+        let keys: Vec<_> = (0..4).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let sig = tree_sign(&tree, &secret_keys, msg).unwrap();
+        (AggregatedKey::new(tree.value().clone()), TreeSignature::new(sig))
+    }
+
+    #[test]
+    fn round_trips_through_bytes_and_hex() {
+        let (key, sig) = signed(b"newtypes");
+        assert_eq!(AggregatedKey::from_bytes(&key.to_bytes()).unwrap(), key);
+        assert_eq!(TreeSignature::from_bytes(&sig.to_bytes()).unwrap(), sig);
+
+        let (key_hex, sig_hex) = (key.to_string(), sig.to_string());
+        assert_eq!((key_hex.len(), sig_hex.len()), (2 * POINT_LEN, 2 * SIGNATURE_LEN));
+        assert_eq!(key_hex.parse::<AggregatedKey>().unwrap(), key);
+        assert_eq!(sig_hex.to_uppercase().parse::<TreeSignature>().unwrap(), sig);
+
+        let back: TreeSignature = sig_hex.parse().unwrap();
+        assert!(back.verify(&Params::default(), &key_hex.parse().unwrap(), b"newtypes"));
+        assert!(!back.verify(&Params::default(), &key, b"newtypez"));
+    }
+
+    #[test]
+    fn one_context_checks_many_signatures() {
+        let (key, sig) = signed(b"first");
+        let ctx = VerifyCtx::new(Params::default(), &key);
+        assert_eq!(ctx.key(), &key);
+        assert!(ctx.verify(b"first", &sig));
+        assert!(!ctx.verify(b"second", &sig));
+
+        let (other_key, other_sig) = signed(b"first");
+        assert!(!ctx.verify(b"first", &other_sig));
+        assert!(VerifyCtx::new(Params::default(), &other_key).verify(b"first", &other_sig));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_through_json() {
+        let (key, sig) = signed(b"json");
+        let json = serde_json::to_string(&(&key, &sig)).unwrap();
+        assert_eq!(json, format!("[\"{}\",\"{}\"]", key, sig));
+        let (key_back, sig_back): (AggregatedKey, TreeSignature) = serde_json::from_str(&json).unwrap();
+        assert_eq!((key_back, sig_back), (key, sig));
+
+        assert!(serde_json::from_str::<AggregatedKey>("\"02\"").is_err());
+        assert!(serde_json::from_str::<TreeSignature>("65").is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_lengths() {
+        let (key, sig) = signed(b"lengths");
+        let (key_bytes, sig_bytes) = (key.to_bytes(), sig.to_bytes());
+        let wrong = |expected, got| DecodeError::WrongLength { expected, got };
+        assert_eq!(AggregatedKey::from_bytes(&key_bytes[1..]).unwrap_err(), wrong(POINT_LEN, POINT_LEN - 1));
+        assert_eq!(TreeSignature::from_bytes(&sig_bytes[..64]).unwrap_err(), wrong(SIGNATURE_LEN, 64));
+        assert_eq!(TreeSignature::from_bytes(&[sig_bytes.as_slice(), &[0]].concat()).unwrap_err(), wrong(SIGNATURE_LEN, 66));
+        assert!(matches!(format!("{}0", key).parse::<AggregatedKey>(), Err(DecodeError::Hex(_))));
+        assert!(matches!(" ".repeat(66).parse::<AggregatedKey>(), Err(DecodeError::Hex(_))));
+    }
+
+    #[test]
+    fn rejects_non_canonical_encodings() {
+        let (key, sig) = signed(b"canonical");
+        // the scalar half at or above the group order is not reduced
+        let mut high = sig.to_bytes();
+        high[POINT_LEN..].copy_from_slice(&[0xff; SCALAR_LEN]);
+        assert!(TreeSignature::from_bytes(&high).is_err());
+
+        // 0x04 is the uncompressed prefix, 0x05 nothing at all
+        for prefix in [0x04, 0x05] {
+            let mut bytes = key.to_bytes();
+            bytes[0] = prefix;
+            assert!(AggregatedKey::from_bytes(&bytes).is_err(), "prefix {:#04x}", prefix);
+        }
+        // an x coordinate at or above the field prime
+        let mut past_p = [0xffu8; POINT_LEN];
+        past_p[0] = 0x02;
+        assert!(AggregatedKey::from_bytes(&past_p).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! One participant: its own secret key and nonce state, nothing else. Every
+//! other input it needs comes from the `Coordinator` and is public.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{params::Params, round1::{Round1Out, sign_round1}, round2::sign_prime};
+
+use crate::bintree::BinTree;
+use crate::error::Error;
+use crate::keys::Keypair;
+use crate::secret::{SecretNonces, SecretScalar};
+use crate::treemusig::NONCES;
+
+pub struct Signer {
+    pk: Secp256k1Point,
+    sk: SecretScalar,
+    /// Left-to-right index of this signer's leaf in the key tree.
+    position: usize,
+    state: Option<SecretNonces>,
+    params: Params,
+}
+
+impl Signer {
+    pub fn new(keypair: Keypair, position: usize) -> Self {
+        Signer::with_params(keypair, position, Params::default())
+    }
+
+    /// A signer using `params`, which must match its `Coordinator`'s.
+    pub fn with_params(keypair: Keypair, position: usize, params: Params) -> Self {
+        Signer {
+            pk: keypair.pk,
+            sk: SecretScalar::new(keypair.sk),
+            position,
+            state: None,
+            params,
+        }
+    }
+
+    pub fn pk(&self) -> &Secp256k1Point {
+        &self.pk
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Draws fresh nonces, keeping their secret half for `round2`. Fails if
+    /// the last round 1 has not been used by a `round2` yet.
+    pub fn round1(&mut self) -> Result<Round1Out, Error> {
+        if self.state.is_some() {
+            return Err(Error::RoundRepeated { pubkey: self.pk.clone(), round: 1 });
+        }
+        let (out, state) = sign_round1(NONCES).map_err(|e| Error::Round1Failed(format!("{:?}", e)))?;
+        self.state = Some(SecretNonces::new(state));
+        Ok(out)
+    }
+
+    /// Signs with the nonces from `round1`, which are used up: a second call
+    /// needs a new `round1` first. `outs_by_depth` and `merkle_path` come from
+    /// `Coordinator::round2_inputs`.
+    pub fn round2(
+        &mut self,
+        outs_by_depth: &[Round1Out],
+        msg: &[u8],
+        merkle_path: &[Vec<Secp256k1Point>],
+    ) -> Result<(Secp256k1Point, Secp256k1Scalar), Error> {
+        let state = self.state.take().ok_or_else(|| Error::IncompleteNodeState {
+            pubkey: self.pk.clone(),
+            field: "state",
+        })?;
+        // sign_prime takes the path as a `&Vec`
+        sign_prime(&self.params, state.into_inner(), outs_by_depth, self.sk.expose(), msg, &merkle_path.to_vec())
+            .map_err(|e| Error::Round2Failed(format!("{:?}", e)))
+    }
+}
+
+/// One `Signer` per leaf of `tree`, each handed the keypair for its leaf.
+/// A key that sits at several leaves needs one keypair per leaf.
+pub fn signers_for_tree(tree: &BinTree<Secp256k1Point>, mut keys: Vec<Keypair>, params: &Params) -> Result<Vec<Signer>, Error> {
+    let mut signers = Vec::with_capacity(keys.len());
+    for (position, pk) in tree.leaves().enumerate() {
+        let i = keys.iter().position(|kp| kp.pk == *pk).ok_or(Error::UnknownSigner(position))?;
+        signers.push(Signer::with_params(keys.swap_remove(i), position, params.clone()));
+    }
+    Ok(signers)
+}
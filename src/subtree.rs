@@ -0,0 +1,154 @@
+//! Signing with one subtree of the key tree. The subtree's signers produce
+//! an ordinary tree signature for the subtree's aggregate key, and reveal
+//! the sibling keys on the way up so a verifier holding only the root key
+//! can check that the subtree key is committed to by it.
+//!
+//! The result is not a signature under the root key: only the full signer
+//! set can produce one of those.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+#[cfg(feature = "signing")]
+use crypto_rs::secp256k1::Secp256k1Scalar;
+use nested_musig2::params::Params;
+#[cfg(feature = "signing")]
+use std::collections::HashMap;
+
+#[cfg(feature = "signing")]
+use crate::bintree::BinTree;
+#[cfg(feature = "signing")]
+use crate::error::Error;
+#[cfg(feature = "signing")]
+use crate::indexed::IndexedTree;
+use crate::indexed::Side;
+#[cfg(feature = "signing")]
+use crate::treemusig::tree_sign_with;
+use crate::treemusig::{Signature, tree_verify_with, verify_merkle_path};
+
+#[derive(Debug, Clone)]
+pub struct SubtreeSignature {
+    pub subtree_key: Secp256k1Point,
+    /// Sibling keys from the subtree root up, nearest first, each with the
+    /// side it sits on.
+    pub path: Vec<(Side, Secp256k1Point)>,
+    pub sig: Signature,
+}
+
+/// Signs `msg` with the subtree at arena index `node` of `tree` (preorder,
+/// 0 is the root). `secret_keys` only needs the subtree's leaves.
+#[cfg(feature = "signing")]
+pub fn subtree_sign(
+    tree: &BinTree<Secp256k1Point>,
+    node: usize,
+    secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    msg: &[u8],
+) -> Result<SubtreeSignature, Error> {
+    subtree_sign_with(tree, node, secret_keys, msg, &Params::default())
+}
+
+#[cfg(feature = "signing")]
+pub fn subtree_sign_with(
+    tree: &BinTree<Secp256k1Point>,
+    node: usize,
+    secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    msg: &[u8],
+    params: &Params,
+) -> Result<SubtreeSignature, Error> {
+    let indexed = IndexedTree::from_tree(tree);
+    if node >= indexed.node_count() {
+        return Err(Error::UnknownNode(node));
+    }
+    let subtree = indexed.subtree(node);
+    let sig = tree_sign_with(&subtree, secret_keys, msg, params)?;
+    Ok(SubtreeSignature {
+        subtree_key: subtree.value().clone(),
+        path: indexed.sided_path_at(node),
+        sig,
+    })
+}
+
+/// Checks that the revealed path aggregates up to `root` and that the
+/// signature is valid for the subtree key.
+pub fn subtree_verify(root: &Secp256k1Point, msg: &[u8], proof: &SubtreeSignature) -> bool {
+    subtree_verify_with(root, msg, proof, &Params::default())
+}
+
+pub fn subtree_verify_with(root: &Secp256k1Point, msg: &[u8], proof: &SubtreeSignature, params: &Params) -> bool {
+    verify_merkle_path(root, &proof.subtree_key, &proof.path, params)
+        && tree_verify_with(&proof.subtree_key, msg, &proof.sig, params)
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use crate::treemusig::{build_key_tree, tree_verify};
+
+    fn eight() -> (BinTree<Secp256k1Point>, Vec<Keypair>) {
+        let keys: Vec<_> = (0..8).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        (tree, keys)
+    }
+
+    /// Secrets for the leaves under `node` only.
+    fn subtree_secrets(tree: &BinTree<Secp256k1Point>, node: usize, keys: &[Keypair]) -> HashMap<Secp256k1Point, Secp256k1Scalar> {
+        let subtree = IndexedTree::from_tree(tree).subtree(node);
+        keys.iter().filter(|kp| subtree.contains_leaf(&kp.pk)).map(|kp| (kp.pk.clone(), kp.sk.clone())).collect()
+    }
+
+    #[test]
+    fn two_of_eight_subtree_verifies() {
+        let (tree, keys) = eight();
+        // preorder: 0 root, 1 left half, 2 its left pair
+        let secrets = subtree_secrets(&tree, 2, &keys);
+        assert_eq!(secrets.len(), 2);
+
+        let proof = subtree_sign(&tree, 2, &secrets, b"subtree").unwrap();
+        assert_eq!(proof.path.len(), 2);
+        assert!(subtree_verify(tree.value(), b"subtree", &proof));
+        assert!(!subtree_verify(tree.value(), b"other message", &proof));
+        // the subtree signature is not a signature under the root key
+        assert!(!tree_verify(tree.value(), b"subtree", &proof.sig));
+    }
+
+    #[test]
+    fn tampered_path_fails() {
+        let (tree, keys) = eight();
+        let secrets = subtree_secrets(&tree, 2, &keys);
+        let proof = subtree_sign(&tree, 2, &secrets, b"subtree").unwrap();
+
+        let mut forged = proof.clone();
+        forged.path[0].1 = Keypair::generate().pk;
+        assert!(!subtree_verify(tree.value(), b"subtree", &forged));
+
+        let mut flipped = proof.clone();
+        flipped.path[1].0 = Side::Left;
+        assert!(!subtree_verify(tree.value(), b"subtree", &flipped));
+
+        let mut truncated = proof;
+        truncated.path.pop();
+        assert!(!subtree_verify(tree.value(), b"subtree", &truncated));
+    }
+
+    #[test]
+    fn root_and_leaf_subtrees() {
+        let (tree, keys) = eight();
+        let all: HashMap<_, _> = keys.iter().map(|kp| (kp.pk.clone(), kp.sk.clone())).collect();
+        let proof = subtree_sign(&tree, 0, &all, b"whole tree").unwrap();
+        assert!(proof.path.is_empty());
+        assert!(subtree_verify(tree.value(), b"whole tree", &proof));
+
+        // a single leaf signs alone and reveals all three siblings
+        let proof = subtree_sign(&tree, 3, &subtree_secrets(&tree, 3, &keys), b"one").unwrap();
+        assert_eq!(proof.path.len(), 3);
+        assert!(subtree_verify(tree.value(), b"one", &proof));
+    }
+
+    #[test]
+    fn bad_index_and_missing_secret() {
+        let (tree, keys) = eight();
+        assert!(matches!(subtree_sign(&tree, 15, &HashMap::new(), b"m"), Err(Error::UnknownNode(15))));
+        // node 1 covers four leaves, but only node 2's two secrets are given
+        let secrets = subtree_secrets(&tree, 2, &keys);
+        assert!(matches!(subtree_sign(&tree, 1, &secrets, b"m"), Err(Error::MissingNodeState(_))));
+    }
+}
@@ -0,0 +1,139 @@
+//! BIP341 key tweaking for the root key, so a tree's root can be used as a
+//! taproot internal key.
+//!
+//! Only the output key is computed here. Signatures from `tree_sign` are
+//! still for the untweaked root key: signing for the output key needs the
+//! tweak folded into the round 2 challenge, which `nested_musig2` does not
+//! support yet.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use sha2::{Digest, Sha256};
+
+use crate::encoding::{point_from_bytes, point_to_bytes, scalar_from_bytes};
+use crate::error::Error;
+
+/// Parity of a point's y coordinate, which BIP341 needs alongside the x-only
+/// output key to spend through a script path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    Even,
+    Odd,
+}
+
+#[derive(Debug, Clone)]
+pub struct TweakedKey {
+    /// `P + t*G`, with `P` the internal key lifted to even y.
+    pub output_key: Secp256k1Point,
+    /// `t = hash_TapTweak(x(P) || merkle_root)`.
+    pub tweak: Secp256k1Scalar,
+}
+
+impl TweakedKey {
+    /// The 32-byte x-only key that goes into the witness program.
+    pub fn x_only(&self) -> [u8; 32] {
+        x_only(&self.output_key)
+    }
+}
+
+/// BIP340 tagged hash: `sha256(sha256(tag) || sha256(tag) || msg)`.
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    hasher.update(tag);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+pub fn x_only(point: &Secp256k1Point) -> [u8; 32] {
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&point_to_bytes(point)[1..]);
+    x
+}
+
+fn parity(point: &Secp256k1Point) -> Parity {
+    // compressed encodings start with 0x02 for even y, 0x03 for odd
+    match point_to_bytes(point)[0] {
+        0x02 => Parity::Even,
+        _ => Parity::Odd,
+    }
+}
+
+/// Tweaks `internal_key` per BIP341. `None` is the key-path-only case, with
+/// no script tree committed to. Returns the output key and its y parity.
+pub fn taproot_tweak(internal_key: &Secp256k1Point, merkle_root: Option<[u8; 32]>) -> Result<(TweakedKey, Parity), Error> {
+    let x = x_only(internal_key);
+    let mut even = vec![0x02];
+    even.extend_from_slice(&x);
+    let even = point_from_bytes(&even).expect("the x coordinate of a valid point lifts");
+
+    let hash = match &merkle_root {
+        Some(root) => tagged_hash("TapTweak", &[&x, root]),
+        None => tagged_hash("TapTweak", &[&x]),
+    };
+    // fails with probability about 2^-128
+    let tweak = scalar_from_bytes(&hash).map_err(|_| Error::InvalidTweak)?;
+    let output_key = even + Secp256k1Point::generator() * &tweak;
+    let parity = parity(&output_key);
+    Ok((TweakedKey { output_key, tweak }, parity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::scalar_to_bytes;
+    use crate::parse::{hex_exact, to_hex};
+
+    fn lift(x_hex: &str) -> Secp256k1Point {
+        let mut bytes = vec![0x02];
+        bytes.extend_from_slice(&hex_exact::<32>(x_hex).unwrap());
+        point_from_bytes(&bytes).unwrap()
+    }
+
+    // Vectors from the BIP341 wallet test vectors, `scriptPubKey` section.
+    #[test]
+    fn key_path_only_vector() {
+        let internal = lift("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let (tweaked, parity) = taproot_tweak(&internal, None).unwrap();
+        assert_eq!(parity, Parity::Odd);
+        assert_eq!(
+            to_hex(&scalar_to_bytes(&tweaked.tweak)),
+            "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70"
+        );
+        assert_eq!(
+            to_hex(&tweaked.x_only()),
+            "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"
+        );
+    }
+
+    #[test]
+    fn script_tree_vector() {
+        let internal = lift("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let merkle_root = hex_exact::<32>("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21").unwrap();
+        let (tweaked, parity) = taproot_tweak(&internal, Some(merkle_root)).unwrap();
+        assert_eq!(parity, Parity::Odd);
+        assert_eq!(
+            to_hex(&scalar_to_bytes(&tweaked.tweak)),
+            "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001"
+        );
+        assert_eq!(
+            to_hex(&tweaked.x_only()),
+            "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"
+        );
+    }
+
+    #[test]
+    fn internal_key_parity_does_not_matter() {
+        let internal = lift("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let mut odd = point_to_bytes(&internal);
+        odd[0] = 0x03;
+        let odd = point_from_bytes(&odd).unwrap();
+        let (a, pa) = taproot_tweak(&internal, None).unwrap();
+        let (b, pb) = taproot_tweak(&odd, None).unwrap();
+        assert_eq!(a.x_only(), b.x_only());
+        assert_eq!(pa, pb);
+        assert_eq!(pa, parity(&a.output_key));
+    }
+}
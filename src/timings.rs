@@ -0,0 +1,98 @@
+//! Where the time goes in a signing run, per phase and per tree depth.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use std::{fmt, time::{Duration, Instant}};
+
+use crate::indexed::IndexedTree;
+
+/// Filled in by `timed_tree_sign`. Work the `parallel` feature spreads over
+/// threads is summed, so a level can take longer here than on the clock.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Building the key tree.
+    pub key_aggregation: Duration,
+    /// One entry per tree level, root first: nonce generation at the leaves
+    /// and round 1 aggregation at the nodes above them.
+    pub round1_by_depth: Vec<Duration>,
+    /// One entry per tree level, root first: partial signatures at the
+    /// leaves and their aggregation at the nodes above them.
+    pub round2_by_depth: Vec<Duration>,
+    /// Checking the root signature.
+    pub verification: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.key_aggregation
+            + self.round1_by_depth.iter().sum::<Duration>()
+            + self.round2_by_depth.iter().sum::<Duration>()
+            + self.verification
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {:>5} {:>12}", "phase", "depth", "time")?;
+        writeln!(f, "{:<16} {:>5} {:>12?}", "key aggregation", "-", self.key_aggregation)?;
+        for (depth, time) in self.round1_by_depth.iter().enumerate() {
+            writeln!(f, "{:<16} {:>5} {:>12?}", "round 1", depth, time)?;
+        }
+        for (depth, time) in self.round2_by_depth.iter().enumerate() {
+            writeln!(f, "{:<16} {:>5} {:>12?}", "round 2", depth, time)?;
+        }
+        writeln!(f, "{:<16} {:>5} {:>12?}", "verification", "-", self.verification)?;
+        write!(f, "{:<16} {:>5} {:>12?}", "total", "", self.total())
+    }
+}
+
+/// Adds up time per depth for one round. The round functions take an
+/// `Option<&mut DepthClock>` and only read the clock when it is `Some`.
+pub(crate) struct DepthClock {
+    depths: Vec<usize>,
+    by_depth: Vec<Duration>,
+}
+
+impl DepthClock {
+    pub(crate) fn new(tree: &IndexedTree<Secp256k1Point>) -> Self {
+        let depths = tree.depths();
+        let height = depths.iter().max().map_or(0, |d| d + 1);
+        DepthClock { depths, by_depth: vec![Duration::ZERO; height] }
+    }
+
+    pub(crate) fn add(&mut self, idx: usize, elapsed: Duration) {
+        self.by_depth[self.depths[idx]] += elapsed;
+    }
+
+    pub(crate) fn into_durations(self) -> Vec<Duration> {
+        self.by_depth
+    }
+}
+
+pub(crate) fn start(clock: &Option<&mut DepthClock>) -> Option<Instant> {
+    clock.as_ref().map(|_| Instant::now())
+}
+
+pub(crate) fn stop(clock: &mut Option<&mut DepthClock>, idx: usize, started: Option<Instant>) {
+    if let (Some(clock), Some(started)) = (clock.as_deref_mut(), started) {
+        clock.add(idx, started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_has_a_row_per_level() {
+        let timings = Timings {
+            key_aggregation: Duration::from_millis(1),
+            round1_by_depth: vec![Duration::from_millis(2); 3],
+            round2_by_depth: vec![Duration::from_millis(3); 3],
+            verification: Duration::from_millis(4),
+        };
+        assert_eq!(timings.total(), Duration::from_millis(20));
+        let table = timings.to_string();
+        assert_eq!(table.lines().count(), 1 + 1 + 3 + 3 + 1 + 1);
+        assert_eq!(table.lines().filter(|l| l.starts_with("round 2")).count(), 3);
+    }
+}
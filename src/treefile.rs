@@ -0,0 +1,101 @@
+//! Key tree files: one level of the tree per line, root first, each node's
+//! 33-byte compressed key as hex, separated by spaces. Blank lines are
+//! skipped; line numbers in errors are 1-based and count them.
+//!
+//! Parsing only reads the keys; `treemusig::import_key_tree` checks that
+//! they form a valid tree.
+
+use crypto_rs::secp256k1::Secp256k1Point;
+use std::fmt;
+
+use crate::bintree::BinTree;
+use crate::encoding::{DecodeError, POINT_LEN, point_from_bytes, point_hex};
+use crate::parse::{ParseError, hex_exact};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeLineError {
+    Hex(ParseError),
+    Point(DecodeError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeFileError {
+    pub line: usize,
+    pub error: TreeLineError,
+}
+
+impl fmt::Display for TreeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.error {
+            TreeLineError::Hex(e) => write!(f, "{}", e),
+            TreeLineError::Point(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TreeFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.error {
+            TreeLineError::Hex(e) => Some(e),
+            TreeLineError::Point(e) => Some(e),
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<Vec<Secp256k1Point>, TreeLineError> {
+    line.split_whitespace()
+        .map(|key| {
+            let bytes = hex_exact::<POINT_LEN>(key).map_err(TreeLineError::Hex)?;
+            point_from_bytes(&bytes).map_err(TreeLineError::Point)
+        })
+        .collect()
+}
+
+/// The levels in `text`, root first. Stops at the first bad line.
+pub fn parse_key_tree(text: &str) -> Result<Vec<Vec<Secp256k1Point>>, TreeFileError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_line(line).map_err(|error| TreeFileError { line: i + 1, error }))
+        .collect()
+}
+
+pub fn format_key_tree(tree: &BinTree<Secp256k1Point>) -> String {
+    tree.levels()
+        .into_iter()
+        .map(|level| {
+            let keys: Vec<String> = level.into_iter().map(point_hex).collect();
+            format!("{}\n", keys.join(" "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::nth_point;
+    use crate::treemusig::{build_key_tree, import_key_tree};
+
+    #[test]
+    fn formatted_tree_imports_unchanged() {
+        for n in [1, 2, 5, 8] {
+            let tree = build_key_tree((1..=n).map(nth_point).collect()).unwrap();
+            let text = format_key_tree(&tree);
+            assert_eq!(text.lines().count(), tree.height());
+            assert_eq!(import_key_tree(parse_key_tree(&text).unwrap()).unwrap(), tree, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn bad_lines_are_numbered() {
+        let pk = point_hex(&nth_point(1));
+        let err = parse_key_tree(&format!("{}\n\n{} zz\n", pk, pk)).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(matches!(err.error, TreeLineError::Hex(_)));
+
+        // right length, but 0x05 is no point prefix
+        let err = parse_key_tree(&format!("05{}\n", &pk[2..])).unwrap_err();
+        assert_eq!(err, TreeFileError { line: 1, error: TreeLineError::Point(DecodeError::InvalidPoint) });
+    }
+}
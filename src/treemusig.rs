@@ -0,0 +1,1765 @@
+//! Merkelized nested MuSig2 over a binary key tree.
+//!
+//! ```
+//! use ark_usecase::treemusig::{build_key_tree, tree_sign, tree_verify};
+//! use nested_musig2::keygen::keygen;
+//! use std::collections::HashMap;
+//!
+//! let keys: Vec<_> = (0..5).map(|_| keygen()).collect();
+//! let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect())?;
+//! let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+//!
+//! let sig = tree_sign(&tree, &secret_keys, b"tx digest")?;
+//! assert!(tree_verify(tree.value(), b"tx digest", &sig));
+//! # Ok::<(), ark_usecase::Error>(())
+//! ```
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{keyagg::key_agg, params::Params, round2::ver};
+#[cfg(feature = "signing")]
+use nested_musig2::{round1::{Round1Out, Round1State, sign_agg, sign_agg_ext, sign_round1}, round2::{sign_agg_prime, sign_prime}};
+use std::collections::HashSet;
+#[cfg(feature = "signing")]
+use std::{collections::HashMap, fmt, sync::Arc, time::{Duration, Instant}};
+
+#[cfg(feature = "signing")]
+use crate::audit::{AuditReport, Phase, audit_state};
+use crate::bintree::{BinTree, BuildError, LeafOrigins};
+use crate::encoding::point_to_bytes;
+use crate::error::Error;
+use crate::indexed::Side;
+#[cfg(feature = "signing")]
+use crate::indexed::{IndexedTree, NodeEntry};
+#[cfg(feature = "signing")]
+use crate::keys::Keypair;
+use crate::progress::{ProgressSink, Stage, StageProgress};
+#[cfg(feature = "signing")]
+use crate::secret::{SecretNonces, SecretScalar};
+#[cfg(feature = "signing")]
+use crate::timings::{self, DepthClock, Timings};
+
+/// Nonces each signer draws in round 1, i.e. points in a `Round1Out`.
+pub const NONCES: usize = 2;
+
+/// Most levels a key tree may have, leaves included. `nested_musig2` does
+/// not publish a depth bound for its `Params`, so the bound is ours: it caps
+/// the length of every `outs_by_depth` and merkle path handed to
+/// `sign_prime`, and a balanced tree this tall has 2^31 leaves.
+pub const MAX_TREE_HEIGHT: usize = 32;
+
+/// Final tree signature: the aggregated `(state_prime, out_prime)` at the root.
+pub type Signature = (Secp256k1Point, Secp256k1Scalar);
+
+/// A leaf holds its secret key and nonces until its round 2 signature is
+/// made, then drops (and so wipes) both.
+#[cfg(feature = "signing")]
+#[derive(Default)]
+pub(crate) struct NodeState {
+    pub(crate) secret_key: Option<SecretScalar>,
+    pub(crate) state: Option<SecretNonces>,
+    pub(crate) out: Option<Round1Out>,
+    pub(crate) out_internal: Option<Round1Out>,
+    pub(crate) out_prime: Option<Secp256k1Scalar>,
+    pub(crate) state_prime: Option<Secp256k1Point>,
+}
+
+/// Round 1 outputs only show whether they are set; the secrets go through
+/// their own redacting `Debug`.
+#[cfg(feature = "signing")]
+impl fmt::Debug for NodeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeState")
+            .field("secret_key", &self.secret_key)
+            .field("state", &self.state)
+            .field("out", &self.out.is_some())
+            .field("out_internal", &self.out_internal.is_some())
+            .field("out_prime", &self.out_prime)
+            .field("state_prime", &self.state_prime)
+            .finish()
+    }
+}
+
+/// Signing state per node, keyed by the node's `IndexedTree` index rather
+/// than its key, so a key that appears at two leaves gets two entries.
+#[cfg(feature = "signing")]
+pub(crate) type StateMap = HashMap<usize, NodeState>;
+
+/// The node whose entry `idx` uses: a single-child node carries its child's
+/// key unchanged and has no entry of its own.
+#[cfg(feature = "signing")]
+pub(crate) fn state_idx(tree: &IndexedTree<Secp256k1Point>, mut idx: usize) -> usize {
+    while let NodeEntry { left: Some(left), right: None, .. } = tree.get(idx) {
+        idx = *left;
+    }
+    idx
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn node_state<'a>(tree: &IndexedTree<Secp256k1Point>, state_map: &'a StateMap, idx: usize) -> Result<&'a NodeState, Error> {
+    let idx = state_idx(tree, idx);
+    state_map.get(&idx).ok_or_else(|| Error::MissingNodeState(tree.get(idx).value.clone()))
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn node_state_mut<'a>(tree: &IndexedTree<Secp256k1Point>, state_map: &'a mut StateMap, idx: usize) -> Result<&'a mut NodeState, Error> {
+    let idx = state_idx(tree, idx);
+    state_map.get_mut(&idx).ok_or_else(|| Error::MissingNodeState(tree.get(idx).value.clone()))
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn field<T: Clone>(value: &Option<T>, pk: &Secp256k1Point, field: &'static str) -> Result<T, Error> {
+    value.clone().ok_or_else(|| Error::IncompleteNodeState { pubkey: pk.clone(), field })
+}
+
+/// Round 1 in two passes: every leaf draws its nonces (in parallel with the
+/// `parallel` feature, as the leaves are independent), then the outputs are
+/// aggregated up the tree sequentially, in the same order as always.
+#[cfg(feature = "signing")]
+pub(crate) fn round1(tree: &IndexedTree<Secp256k1Point>, state_map: &mut StateMap, params: &Params) -> Result<(), Error> {
+    round1_timed(tree, state_map, params, None, None)
+}
+
+/// Rejects a tree taller than `max` levels, `MAX_TREE_HEIGHT` before any
+/// signing work.
+#[cfg(feature = "signing")]
+pub(crate) fn check_height(tree: &IndexedTree<Secp256k1Point>, max: usize) -> Result<(), Error> {
+    let height = tree.height();
+    if height > max {
+        return Err(Error::TreeTooDeep { height, max, deepest: tree.get(tree.deepest_leaf()).value.clone() });
+    }
+    Ok(())
+}
+
+/// `check_height` for a tree that has not been indexed.
+pub(crate) fn check_key_tree_height(tree: &BinTree<Secp256k1Point>, max: usize) -> Result<(), Error> {
+    let height = tree.height();
+    if height > max {
+        return Err(Error::TreeTooDeep { height, max, deepest: tree.deepest_leaf().clone() });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn round1_timed(
+    tree: &IndexedTree<Secp256k1Point>,
+    state_map: &mut StateMap,
+    params: &Params,
+    clock: Option<&mut DepthClock>,
+    sink: Option<&dyn ProgressSink>,
+) -> Result<(), Error> {
+    round1_faulty(tree, state_map, params, clock, sink, |_| false)
+}
+
+/// A fault injected into a signing run at one `IndexedTree` index, to
+/// exercise what a misbehaving signer or aggregator does to the signature.
+#[cfg(feature = "signing")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Fault {
+    /// `sign_prime` fails at this leaf.
+    Fail,
+    /// This two-child node combines its children right one first, while
+    /// the key tree keeps them left one first.
+    Swap,
+}
+
+/// `round1_timed` with the children's outputs combined in the wrong order
+/// at every two-child node `swap` picks.
+#[cfg(feature = "signing")]
+pub(crate) fn round1_faulty(
+    tree: &IndexedTree<Secp256k1Point>,
+    state_map: &mut StateMap,
+    params: &Params,
+    mut clock: Option<&mut DepthClock>,
+    sink: Option<&dyn ProgressSink>,
+    swap: impl Fn(usize) -> bool,
+) -> Result<(), Error> {
+    check_height(tree, MAX_TREE_HEIGHT)?;
+    let leaves: Vec<usize> = tree.leaf_indices().collect();
+    // Fail on a missing or already used entry before spending time on
+    // nonces.
+    for &idx in &leaves {
+        if node_state(tree, state_map, idx)?.out.is_some() {
+            return Err(Error::RoundRepeated { pubkey: tree.get(idx).value.clone(), round: 1 });
+        }
+    }
+    let progress = StageProgress::new(sink, Stage::Round1, round_units(tree));
+    for (idx, out, state, elapsed) in leaf_round1(leaves, clock.is_some(), progress)? {
+        let entry = node_state_mut(tree, state_map, idx)?;
+        entry.out = Some(out);
+        entry.state = Some(SecretNonces::new(state));
+        if let (Some(clock), Some(elapsed)) = (clock.as_deref_mut(), elapsed) {
+            clock.add(idx, elapsed);
+        }
+    }
+    aggregate_round1(tree, state_map, params, clock, progress, swap)
+}
+
+/// One leaf's round 1 result, with how long it took when `timed`.
+#[cfg(feature = "signing")]
+type LeafNonces = (usize, Round1Out, Round1State, Option<Duration>);
+
+#[cfg(feature = "signing")]
+fn leaf_round1(leaves: Vec<usize>, timed: bool, progress: StageProgress<'_>) -> Result<Vec<LeafNonces>, Error> {
+    let one = |idx| {
+        let started = timed.then(Instant::now);
+        let (out, state) = sign_round1(NONCES).map_err(|e| Error::Round1Failed(format!("{:?}", e)))?;
+        progress.tick();
+        Ok::<_, Error>((idx, out, state, started.map(|s| s.elapsed())))
+    };
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        leaves.into_par_iter().map(one).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        leaves.into_iter().map(one).collect()
+    }
+}
+
+/// Children come after their parent in the arena, so walking it backwards
+/// reaches every two-child node after both of its children.
+#[cfg(feature = "signing")]
+fn binary_nodes_bottom_up(tree: &IndexedTree<Secp256k1Point>) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+    (0..tree.node_count()).rev().filter_map(|idx| match tree.get(idx) {
+        NodeEntry { left: Some(left), right: Some(right), .. } => Some((idx, *left, *right)),
+        _ => None,
+    })
+}
+
+/// Units of work in a round: one per leaf and one per two-child node.
+#[cfg(feature = "signing")]
+fn round_units(tree: &IndexedTree<Secp256k1Point>) -> usize {
+    tree.leaf_indices().count() + binary_nodes_bottom_up(tree).count()
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn aggregate_round1(
+    tree: &IndexedTree<Secp256k1Point>,
+    state_map: &mut StateMap,
+    params: &Params,
+    mut clock: Option<&mut DepthClock>,
+    progress: StageProgress<'_>,
+    swap: impl Fn(usize) -> bool,
+) -> Result<(), Error> {
+    for (idx, left, right) in binary_nodes_bottom_up(tree) {
+        let started = timings::start(&clock);
+        let out_of = |child: usize| field(&node_state(tree, state_map, child)?.out, &tree.get(child).value, "out");
+        let (left_out, right_out) = (out_of(left)?, out_of(right)?);
+        let outs = if swap(idx) { agg_ordered(right_out, left_out) } else { agg_ordered(left_out, right_out) };
+        let out_internal = sign_agg(&outs).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))?;
+
+        let value = &tree.get(idx).value;
+        let out = sign_agg_ext(params, &out_internal, value).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))?;
+        let state = NodeState {
+            out: Some(out),
+            out_internal: Some(out_internal),
+            ..NodeState::default()
+        };
+        state_map.insert(idx, state);
+        timings::stop(&mut clock, idx, started);
+        progress.tick();
+    }
+    Ok(())
+}
+
+/// Every node's round 1 output alongside its key, in the key tree's shape.
+/// A single-child node shows its child's output, as it has none of its own.
+#[cfg(feature = "signing")]
+pub(crate) fn round1_out_tree(tree: &IndexedTree<Secp256k1Point>, state_map: &StateMap) -> Result<BinTree<(Secp256k1Point, Round1Out)>, Error> {
+    let outs = (0..tree.node_count())
+        .map(|idx| field(&node_state(tree, state_map, idx)?.out, &tree.get(idx).value, "out"))
+        .collect::<Result<Vec<_>, _>>()?;
+    // `map` visits in preorder, the arena's order
+    let mut outs = outs.into_iter();
+    let key_tree = tree.to_tree();
+    let out_tree = key_tree.map(|_| outs.next().expect("one output per node"));
+    Ok(key_tree.zip(&out_tree).expect("built in the key tree's shape"))
+}
+
+/// Combines the children's primes of every two-child node, bottom-up, right
+/// one first where `swap` says so. Only needs the leaves' primes to be in
+/// `state_map`.
+#[cfg(feature = "signing")]
+pub(crate) fn aggregate_round2(
+    tree: &IndexedTree<Secp256k1Point>,
+    state_map: &mut StateMap,
+    mut clock: Option<&mut DepthClock>,
+    progress: StageProgress<'_>,
+    swap: impl Fn(usize) -> bool,
+) -> Result<(), Error> {
+    for (idx, left, right) in binary_nodes_bottom_up(tree) {
+        let started = timings::start(&clock);
+        // the children's primes are spent here, so they are moved, not copied
+        let mut part = |child: usize| {
+            let pk = &tree.get(child).value;
+            let state = node_state_mut(tree, state_map, child)?;
+            let state_prime = state.state_prime.take().ok_or_else(|| Error::IncompleteNodeState { pubkey: pk.clone(), field: "state_prime" })?;
+            let out_prime = state.out_prime.take().ok_or_else(|| Error::IncompleteNodeState { pubkey: pk.clone(), field: "out_prime" })?;
+            Ok::<_, Error>((state_prime, out_prime))
+        };
+        let (left_part, right_part) = (part(left)?, part(right)?);
+        let parts = if swap(idx) { agg_ordered(right_part, left_part) } else { agg_ordered(left_part, right_part) };
+        let (state_prime, out_prime) = sign_agg_prime(&parts).map_err(|e| Error::Round2FailedAt {
+            node: idx,
+            error: Box::new(Error::AggregationFailed(format!("{:?}", e))),
+        })?;
+
+        let state = node_state_mut(tree, state_map, idx)?;
+        state.out_prime = Some(out_prime);
+        state.state_prime = Some(state_prime);
+        timings::stop(&mut clock, idx, started);
+        progress.tick();
+    }
+    Ok(())
+}
+
+/// A leaf's `outs_by_depth` and merkle path, root level first.
+#[cfg(feature = "signing")]
+pub(crate) type Round2Inputs = (Vec<Round1Out>, Vec<Vec<Secp256k1Point>>);
+
+/// What the leaf at `leaf` signs over in round 2: the internal round 1
+/// outputs of its two-child ancestors and its sibling path, both root level
+/// first, one sibling per level.
+#[cfg(feature = "signing")]
+pub(crate) fn leaf_round2_inputs(tree: &IndexedTree<Secp256k1Point>, state_map: &StateMap, leaf: usize) -> Result<Round2Inputs, Error> {
+    let mut inputs = Round2Inputs::default();
+    fill_round2_inputs(tree, leaf, |parent| field(&node_state(tree, state_map, parent)?.out_internal, &tree.get(parent).value, "out_internal"), &mut inputs)?;
+    Ok(inputs)
+}
+
+/// `leaf_round2_inputs` written over `inputs`, reusing its allocations,
+/// with each two-child ancestor's internal output looked up through
+/// `out_internal`. Every leaf's inputs are a path through the same per-node
+/// outputs, so round 2 keeps those once and fills one buffer per thread
+/// rather than holding a copy of the path for every leaf.
+#[cfg(feature = "signing")]
+fn fill_round2_inputs(
+    tree: &IndexedTree<Secp256k1Point>,
+    leaf: usize,
+    mut out_internal: impl FnMut(usize) -> Result<Round1Out, Error>,
+    (outs_by_depth, merkle_path): &mut Round2Inputs,
+) -> Result<(), Error> {
+    outs_by_depth.clear();
+    let mut levels = 0;
+    for idx in std::iter::successors(Some(leaf), |&idx| tree.parent(idx)) {
+        let Some(sibling) = tree.sibling(idx) else {
+            continue;
+        };
+        let parent = tree.parent(idx).expect("a node with a sibling has a parent");
+        outs_by_depth.push(out_internal(parent)?);
+        let sibling = tree.get(sibling).value.clone();
+        match merkle_path.get_mut(levels) {
+            Some(entry) => {
+                entry.clear();
+                entry.push(sibling);
+            }
+            None => merkle_path.push(vec![sibling]),
+        }
+        levels += 1;
+    }
+    merkle_path.truncate(levels);
+    outs_by_depth.reverse();
+    merkle_path.reverse();
+    Ok(())
+}
+
+/// One leaf's round 2 work. It owns the leaf's nonces, which are wiped
+/// when it is dropped right after signing, and borrows its secret key from
+/// the state map.
+#[cfg(feature = "signing")]
+struct LeafJob<'a> {
+    idx: usize,
+    sk: &'a SecretScalar,
+    nonces: SecretNonces,
+}
+
+/// Round 2 in two passes, like round 1: every leaf signs (in parallel with
+/// the `parallel` feature), then the partial signatures are aggregated up
+/// the tree. Each leaf's nonces are moved out of `state_map` for signing
+/// and its secret key is signed with in place; neither is ever copied. State
+/// is dropped as soon as it is spent: the internal round 1 outputs, which
+/// every leaf reads its inputs from, once all leaves have signed, and
+/// everything else once the signature is made, leaving only the root's
+/// entry holding it. If round 2 fails the secret keys stay, for
+/// `Round2Failure::recover`, but the nonces are spent.
+#[cfg(feature = "signing")]
+pub(crate) fn round2(tree: &IndexedTree<Secp256k1Point>, state_map: &mut StateMap, msg: &[u8], params: &Params) -> Result<(), Error> {
+    round2_timed(tree, state_map, msg, params, None)
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn round2_timed(tree: &IndexedTree<Secp256k1Point>, state_map: &mut StateMap, msg: &[u8], params: &Params, clock: Option<&mut DepthClock>) -> Result<(), Error> {
+    round2_faulty(tree, state_map, msg, params, clock, None, |_| None)
+}
+
+/// `round2_timed` with the `fault` injected at every index it picks:
+/// `sign_prime` failing at a leaf, which is how recovery is exercised
+/// without a broken signer, or a node aggregating out of order.
+#[cfg(feature = "signing")]
+pub(crate) fn round2_faulty(
+    tree: &IndexedTree<Secp256k1Point>,
+    state_map: &mut StateMap,
+    msg: &[u8],
+    params: &Params,
+    mut clock: Option<&mut DepthClock>,
+    sink: Option<&dyn ProgressSink>,
+    fault: impl Fn(usize) -> Option<Fault> + Sync,
+) -> Result<(), Error> {
+    let timed = clock.is_some();
+    let progress = StageProgress::new(sink, Stage::Round2, round_units(tree));
+    let root = state_idx(tree, tree.root());
+    if state_map.get(&root).is_some_and(|state| state.out_prime.is_some()) {
+        return Err(Error::RoundRepeated { pubkey: tree.get(root).value.clone(), round: 2 });
+    }
+    for (idx, _, _) in binary_nodes_bottom_up(tree) {
+        field(&node_state(tree, state_map, idx)?.out_internal, &tree.get(idx).value, "out_internal")?;
+    }
+    let mut leaves = Vec::new();
+    for idx in tree.leaf_indices() {
+        let pk = &tree.get(idx).value;
+        let state = node_state_mut(tree, state_map, idx)?;
+        // Signing twice with the same nonces would leak the secret key.
+        if state.out_prime.is_some() {
+            return Err(Error::RoundRepeated { pubkey: pk.clone(), round: 2 });
+        }
+        let nonces = state.state.take().ok_or_else(|| Error::IncompleteNodeState { pubkey: pk.clone(), field: "state" })?;
+        if state.secret_key.is_none() {
+            return Err(Error::IncompleteNodeState { pubkey: pk.clone(), field: "secret_key" });
+        }
+        leaves.push((idx, nonces));
+    }
+    let internal_outs: HashMap<usize, Round1Out> = state_map
+        .iter_mut()
+        .filter_map(|(&idx, state)| {
+            state.out = None;
+            state.out_internal.take().map(|out| (idx, out))
+        })
+        .collect();
+    let jobs: Vec<LeafJob> = leaves
+        .into_iter()
+        .map(|(idx, nonces)| {
+            let sk = node_state(tree, state_map, idx).ok().and_then(|state| state.secret_key.as_ref()).expect("checked above");
+            LeafJob { idx, sk, nonces }
+        })
+        .collect();
+
+    let sign = |inputs: &mut Round2Inputs, job: LeafJob| {
+        let LeafJob { idx, sk, nonces } = job;
+        let started = timed.then(Instant::now);
+        fill_round2_inputs(tree, idx, |parent| Ok(internal_outs[&parent].clone()), inputs)?;
+        let (outs_by_depth, merkle_path) = &*inputs;
+        let signed = if fault(idx) == Some(Fault::Fail) {
+            Err(Error::Round2Failed("injected fault".into()))
+        } else {
+            sign_prime(params, nonces.into_inner(), outs_by_depth, sk.expose(), msg, merkle_path).map_err(|e| Error::Round2Failed(format!("{:?}", e)))
+        };
+        let (state_prime, out_prime) = signed.map_err(|error| Error::Round2FailedAt { node: idx, error: Box::new(error) })?;
+        progress.tick();
+        Ok::<_, Error>((idx, state_prime, out_prime, started.map(|s| s.elapsed())))
+    };
+    #[cfg(feature = "parallel")]
+    let primes: Vec<_> = {
+        use rayon::prelude::*;
+        jobs.into_par_iter().map_init(Round2Inputs::default, sign).collect::<Result<_, _>>()?
+    };
+    #[cfg(not(feature = "parallel"))]
+    let primes: Vec<_> = {
+        let mut inputs = Round2Inputs::default();
+        jobs.into_iter().map(|job| sign(&mut inputs, job)).collect::<Result<_, _>>()?
+    };
+    drop(internal_outs);
+
+    for (idx, state_prime, out_prime, elapsed) in primes {
+        let state = node_state_mut(tree, state_map, idx)?;
+        state.out_prime = Some(out_prime);
+        state.state_prime = Some(state_prime);
+        if let (Some(clock), Some(elapsed)) = (clock.as_deref_mut(), elapsed) {
+            clock.add(idx, elapsed);
+        }
+    }
+    aggregate_round2(tree, state_map, clock, progress, |idx| fault(idx) == Some(Fault::Swap))?;
+    // dropping the leaf entries wipes the secret keys
+    state_map.retain(|&idx, _| idx == root);
+    Ok(())
+}
+
+/// A two-child node's inputs to `key_agg`, `sign_agg` and `sign_agg_prime`:
+/// left child first. `key_agg` weighs each key by its position, so swapping
+/// the children makes a different aggregate key; every place that combines
+/// two children, the merkle path check included, orders them here.
+pub(crate) fn agg_ordered<T>(left: T, right: T) -> [T; 2] {
+    [left, right]
+}
+
+fn key_agg_pair(params: &Params, k1: Secp256k1Point, k2: Secp256k1Point) -> Result<Secp256k1Point, Error> {
+    key_agg(params, &agg_ordered(k1, k2)).map_err(|e| Error::AggregationFailed(format!("{:?}", e)))
+}
+
+pub(crate) fn from_build_error(e: BuildError<Error>) -> Error {
+    match e {
+        BuildError::Empty => Error::EmptyInput,
+        BuildError::Arity(arity) => Error::InvalidArity(arity),
+        BuildError::Aggregation(e) | BuildError::Source { error: e, .. } => e,
+    }
+}
+
+/// How a key tree may be built from the given keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyTreeOptions {
+    /// Lets a key sit at several leaves, where it signs once per leaf and so
+    /// carries that much more weight. Off by default, since a repeated key
+    /// is far more often a mistake than a weighting.
+    pub allow_duplicates: bool,
+    /// Most levels the tree may have, leaves included. Defaults to
+    /// `MAX_TREE_HEIGHT`, above which signing fails anyway; a lower cap
+    /// bounds every signer's round 2 inputs and merkle path.
+    pub max_height: usize,
+}
+
+impl Default for KeyTreeOptions {
+    fn default() -> Self {
+        KeyTreeOptions { allow_duplicates: false, max_height: MAX_TREE_HEIGHT }
+    }
+}
+
+/// Fails on the first key whose encoding was already seen, unless
+/// `options` allows repeats. Compares the encodings rather than the points,
+/// so the check does not depend on how the point type defines equality.
+pub(crate) fn check_duplicates(pubkeys: &[Secp256k1Point], options: KeyTreeOptions) -> Result<(), Error> {
+    if options.allow_duplicates {
+        return Ok(());
+    }
+    let mut seen = HashSet::with_capacity(pubkeys.len());
+    match pubkeys.iter().find(|pk| !seen.insert(point_to_bytes(pk))) {
+        Some(pk) => Err(Error::DuplicateKey(pk.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Aggregates `pubkeys` pairwise into a key tree whose root is the signing
+/// key. Each key may appear only once; see `KeyTreeOptions`.
+pub fn build_key_tree(pubkeys: Vec<Secp256k1Point>) -> Result<BinTree<Secp256k1Point>, Error> {
+    build_key_tree_with(pubkeys, &Params::default())
+}
+
+/// `build_key_tree` under `params`. A tree must be signed and verified with
+/// the same `params` it was built with.
+pub fn build_key_tree_with(pubkeys: Vec<Secp256k1Point>, params: &Params) -> Result<BinTree<Secp256k1Point>, Error> {
+    build_key_tree_opts(pubkeys, KeyTreeOptions::default(), params)
+}
+
+pub fn build_key_tree_opts(pubkeys: Vec<Secp256k1Point>, options: KeyTreeOptions, params: &Params) -> Result<BinTree<Secp256k1Point>, Error> {
+    check_duplicates(&pubkeys, options)?;
+    let tree = BinTree::try_from_vec(pubkeys, |k1, k2| key_agg_pair(params, k1, k2)).map_err(from_build_error)?;
+    check_key_tree_height(&tree, options.max_height)?;
+    Ok(tree)
+}
+
+/// Like `build_key_tree`, but orders the keys by their encoding first, so
+/// everyone holding the same key set gets the same tree and root key.
+pub fn build_sorted_key_tree(pubkeys: Vec<Secp256k1Point>) -> Result<BinTree<Secp256k1Point>, Error> {
+    build_sorted_key_tree_with(pubkeys, &Params::default())
+}
+
+pub fn build_sorted_key_tree_with(pubkeys: Vec<Secp256k1Point>, params: &Params) -> Result<BinTree<Secp256k1Point>, Error> {
+    build_sorted_key_tree_opts(pubkeys, KeyTreeOptions::default(), params)
+}
+
+pub fn build_sorted_key_tree_opts(pubkeys: Vec<Secp256k1Point>, options: KeyTreeOptions, params: &Params) -> Result<BinTree<Secp256k1Point>, Error> {
+    check_duplicates(&pubkeys, options)?;
+    let tree = BinTree::try_from_vec_sorted(pubkeys, point_to_bytes, |k1, k2| key_agg_pair(params, k1, k2))
+        .map_err(from_build_error)?;
+    check_key_tree_height(&tree, options.max_height)?;
+    Ok(tree)
+}
+
+/// `build_sorted_key_tree`, also recording each leaf's position in
+/// `pubkeys` so results can be reported per signer in the caller's order.
+pub fn build_sorted_key_tree_indexed(pubkeys: Vec<Secp256k1Point>) -> Result<LeafOrigins<Secp256k1Point>, Error> {
+    build_sorted_key_tree_indexed_with(pubkeys, &Params::default())
+}
+
+pub fn build_sorted_key_tree_indexed_with(pubkeys: Vec<Secp256k1Point>, params: &Params) -> Result<LeafOrigins<Secp256k1Point>, Error> {
+    build_sorted_key_tree_indexed_opts(pubkeys, KeyTreeOptions::default(), params)
+}
+
+pub fn build_sorted_key_tree_indexed_opts(pubkeys: Vec<Secp256k1Point>, options: KeyTreeOptions, params: &Params) -> Result<LeafOrigins<Secp256k1Point>, Error> {
+    sorted_key_tree_indexed(pubkeys, options, params, None)
+}
+
+fn sorted_key_tree_indexed(
+    pubkeys: Vec<Secp256k1Point>,
+    options: KeyTreeOptions,
+    params: &Params,
+    sink: Option<&dyn ProgressSink>,
+) -> Result<LeafOrigins<Secp256k1Point>, Error> {
+    check_duplicates(&pubkeys, options)?;
+    // every aggregation joins two subtrees into one
+    let progress = StageProgress::new(sink, Stage::TreeBuild, pubkeys.len().saturating_sub(1));
+    let origins = BinTree::try_from_vec_sorted_indexed(pubkeys, point_to_bytes, |k1, k2| {
+        let key = key_agg_pair(params, k1, k2)?;
+        progress.tick();
+        Ok(key)
+    })
+    .map_err(from_build_error)?;
+    check_key_tree_height(&origins.tree, options.max_height)?;
+    Ok(origins)
+}
+
+/// Checks every aggregate key in `tree` against `key_agg` of its children,
+/// e.g. for a tree that was imported rather than built locally.
+pub fn validate_key_tree(tree: &BinTree<Secp256k1Point>) -> Result<(), Error> {
+    validate_key_tree_with(tree, &Params::default())
+}
+
+pub fn validate_key_tree_with(tree: &BinTree<Secp256k1Point>, params: &Params) -> Result<(), Error> {
+    let mut failed = None;
+    let checked = tree.validate(|k1, k2| match key_agg(params, &agg_ordered(k1.clone(), k2.clone())) {
+        Ok(key) => key,
+        Err(e) => {
+            failed.get_or_insert_with(|| Error::AggregationFailed(format!("{:?}", e)));
+            // any value will do; the error below takes precedence
+            k1.clone()
+        }
+    });
+    if let Some(e) = failed {
+        return Err(e);
+    }
+    Ok(checked?)
+}
+
+/// A key tree published by someone else, given as its `levels()`, root
+/// first. Every aggregate is checked before the tree is returned, so a
+/// signer can use it without trusting the publisher.
+pub fn import_key_tree(levels: Vec<Vec<Secp256k1Point>>) -> Result<BinTree<Secp256k1Point>, Error> {
+    import_key_tree_with(levels, &Params::default())
+}
+
+/// `import_key_tree` under `params`, taking a key more than once, as a
+/// weighted tree has it, but no more than `MAX_TREE_HEIGHT` levels.
+pub fn import_key_tree_with(levels: Vec<Vec<Secp256k1Point>>, params: &Params) -> Result<BinTree<Secp256k1Point>, Error> {
+    import_key_tree_opts(levels, KeyTreeOptions { allow_duplicates: true, ..KeyTreeOptions::default() }, params)
+}
+
+/// The height is checked before any aggregate, so a tall tree costs no
+/// `key_agg` calls.
+pub fn import_key_tree_opts(levels: Vec<Vec<Secp256k1Point>>, options: KeyTreeOptions, params: &Params) -> Result<BinTree<Secp256k1Point>, Error> {
+    let tree = BinTree::from_levels(levels)?;
+    check_key_tree_height(&tree, options.max_height)?;
+    check_duplicates(&tree.leaves().cloned().collect::<Vec<_>>(), options)?;
+    validate_key_tree_with(&tree, params)?;
+    Ok(tree)
+}
+
+/// Initial state for every leaf of `tree`. Keys in `secret_keys` that are not
+/// leaves are ignored; a leaf without a secret is an error. A key at several
+/// leaves gets one entry, and later its own nonces, per leaf.
+#[cfg(feature = "signing")]
+pub(crate) fn leaf_states(tree: &IndexedTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>) -> Result<StateMap, Error> {
+    tree.leaf_indices()
+        .map(|idx| {
+            let pk = &tree.get(idx).value;
+            let sk = secret_keys.get(pk).ok_or_else(|| Error::MissingNodeState(pk.clone()))?;
+            let state = NodeState {
+                secret_key: Some(SecretScalar::new(sk.clone())),
+                ..NodeState::default()
+            };
+            Ok((idx, state))
+        })
+        .collect()
+}
+
+/// Runs both rounds over the whole tree and returns the root signature.
+/// Works for any tree `from_vec` builds: a leaf under single-child nodes
+/// simply gets a shorter `outs_by_depth` and merkle path than its
+/// cousins. A single signer is the degenerate case: the root is its leaf,
+/// both lists are empty, and its own `sign_prime` output is the signature
+/// for its own key.
+#[cfg(feature = "signing")]
+pub(crate) fn sign(tree: &IndexedTree<Secp256k1Point>, state_map: &mut StateMap, msg: &[u8], params: &Params) -> Result<Signature, Error> {
+    check_audit(tree, state_map, Phase::Setup)?;
+    round1(tree, state_map, params)?;
+    check_audit(tree, state_map, Phase::Round1)?;
+    round2(tree, state_map, msg, params)?;
+    check_audit(tree, state_map, Phase::Round2)?;
+    root_signature(tree, state_map)
+}
+
+#[cfg(feature = "signing")]
+pub(crate) fn root_signature(tree: &IndexedTree<Secp256k1Point>, state_map: &StateMap) -> Result<Signature, Error> {
+    let root = &tree.get(tree.root()).value;
+    let state = node_state(tree, state_map, tree.root())?;
+    Ok((field(&state.state_prime, root, "state_prime")?, field(&state.out_prime, root, "out_prime")?))
+}
+
+/// `SigningSession` phase: leaf states are set up, no nonces yet.
+#[cfg(feature = "signing")]
+pub struct Fresh;
+
+/// `SigningSession` phase: every node has its round 1 output.
+#[cfg(feature = "signing")]
+pub struct Round1Done;
+
+/// `SigningSession` phase: the root signature is ready.
+#[cfg(feature = "signing")]
+pub struct Round2Done {
+    sig: Signature,
+}
+
+/// The signing flow one phase at a time, for callers that need to observe
+/// or time the rounds separately; `tree_sign` runs all of it. Each round
+/// consumes the session and returns it in the next phase, so rounds can
+/// only run once and in order:
+///
+/// ```compile_fail
+/// # use ark_usecase::treemusig::SigningSession;
+/// # fn f(session: SigningSession) {
+/// session.round2(b"too early");
+/// # }
+/// ```
+///
+/// and one round 1 cannot back two round 2s:
+///
+/// ```compile_fail
+/// # use ark_usecase::treemusig::{Round1Done, SigningSession};
+/// # fn f(session: SigningSession<Round1Done>) {
+/// let first = session.round2(b"one");
+/// let second = session.round2(b"two");
+/// # }
+/// ```
+///
+/// To sign several messages under one tree, keep the `Fresh` session and
+/// call `sign` for each; every call draws its own nonces.
+#[cfg(feature = "signing")]
+pub struct SigningSession<P = Fresh> {
+    tree: IndexedTree<Secp256k1Point>,
+    state_map: StateMap,
+    params: Params,
+    progress: Option<Arc<dyn ProgressSink>>,
+    phase: P,
+}
+
+#[cfg(feature = "signing")]
+impl<P> SigningSession<P> {
+    fn into_phase<Q>(self, phase: Q) -> SigningSession<Q> {
+        SigningSession { tree: self.tree, state_map: self.state_map, params: self.params, progress: self.progress, phase }
+    }
+}
+
+#[cfg(feature = "signing")]
+impl SigningSession<Fresh> {
+    pub fn new(tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>) -> Result<Self, Error> {
+        SigningSession::with_params(tree, secret_keys, Params::default())
+    }
+
+    /// A session that signs under `params`, which must be the ones `tree`
+    /// was built with.
+    pub fn with_params(tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>, params: Params) -> Result<Self, Error> {
+        let tree = IndexedTree::from_tree(tree);
+        let state_map = leaf_states(&tree, secret_keys)?;
+        check_audit(&tree, &state_map, Phase::Setup)?;
+        Ok(SigningSession { tree, state_map, params, progress: None, phase: Fresh })
+    }
+
+    pub fn round1(mut self) -> Result<SigningSession<Round1Done>, Error> {
+        round1_timed(&self.tree, &mut self.state_map, &self.params, None, self.progress.as_deref())?;
+        check_audit(&self.tree, &self.state_map, Phase::Round1)?;
+        Ok(self.into_phase(Round1Done))
+    }
+
+    /// Runs both rounds for `msg` on a copy of the leaf states, leaving this
+    /// session untouched for the next message. Key aggregation is not
+    /// redone; nonces are never shared between calls.
+    pub fn sign(&self, msg: &[u8]) -> Result<Signature, Error> {
+        let mut state_map = self
+            .state_map
+            .iter()
+            .map(|(&idx, state)| {
+                let secret_key = state.secret_key.as_ref().map(|sk| SecretScalar::new(sk.expose().clone()));
+                (idx, NodeState { secret_key, ..NodeState::default() })
+            })
+            .collect();
+        sign(&self.tree, &mut state_map, msg, &self.params)
+    }
+}
+
+#[cfg(feature = "signing")]
+impl SigningSession<Round1Done> {
+    /// The round 1 outputs as a tree zipped with the keys, for callers that
+    /// want them by position rather than by looking each node up.
+    pub fn round1_outs(&self) -> Result<BinTree<(Secp256k1Point, Round1Out)>, Error> {
+        round1_out_tree(&self.tree, &self.state_map)
+    }
+
+    pub fn round2(mut self, msg: &[u8]) -> Result<SigningSession<Round2Done>, Error> {
+        round2_faulty(&self.tree, &mut self.state_map, msg, &self.params, None, self.progress.as_deref(), |_| None)?;
+        check_audit(&self.tree, &self.state_map, Phase::Round2)?;
+        let sig = root_signature(&self.tree, &self.state_map)?;
+        Ok(self.into_phase(Round2Done { sig }))
+    }
+
+    /// Like `round2`, but a failure comes back with the session so it can
+    /// be recovered with `Round2Failure::recover` rather than started over.
+    pub fn try_round2(self, msg: &[u8]) -> Result<SigningSession<Round2Done>, Round2Failure> {
+        self.try_round2_faulty(msg, |_| false)
+    }
+
+    pub(crate) fn try_round2_faulty(mut self, msg: &[u8], fail: impl Fn(usize) -> bool + Sync) -> Result<SigningSession<Round2Done>, Round2Failure> {
+        let fault = |idx| fail(idx).then_some(Fault::Fail);
+        let signed = round2_faulty(&self.tree, &mut self.state_map, msg, &self.params, None, self.progress.as_deref(), fault).and_then(|()| {
+            check_audit(&self.tree, &self.state_map, Phase::Round2)?;
+            root_signature(&self.tree, &self.state_map)
+        });
+        match signed {
+            Ok(sig) => Ok(self.into_phase(Round2Done { sig })),
+            Err(error) => Err(Round2Failure { error, session: self }),
+        }
+    }
+}
+
+/// A failed `try_round2`, holding what `recover` needs to try again.
+#[cfg(feature = "signing")]
+pub struct Round2Failure {
+    pub error: Error,
+    session: SigningSession<Round1Done>,
+}
+
+#[cfg(feature = "signing")]
+impl fmt::Debug for Round2Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Round2Failure").field("error", &self.error).finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "signing")]
+impl fmt::Display for Round2Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+#[cfg(feature = "signing")]
+impl std::error::Error for Round2Failure {}
+
+#[cfg(feature = "signing")]
+impl Round2Failure {
+    /// The key tree node the failure was traced to, if it was.
+    pub fn node(&self) -> Option<usize> {
+        match self.error {
+            Error::Round2FailedAt { node, .. } => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Back to round 1 done, with fresh nonces at every leaf, ready for
+    /// another `round2`. Round 2 takes all the leaves' nonces before any
+    /// leaf signs, so a failure leaves none of them unspent. Errors not
+    /// traced to a node are returned as they are.
+    pub fn recover(self) -> Result<SigningSession<Round1Done>, Error> {
+        if self.node().is_none() {
+            return Err(self.error);
+        }
+        let SigningSession { tree, mut state_map, params, progress, .. } = self.session;
+        let redraw = tree.leaf_indices().collect();
+        for state in state_map.values_mut() {
+            state.out_prime = None;
+            state.state_prime = None;
+        }
+        for (idx, out, state, _) in leaf_round1(redraw, false, StageProgress::silent())? {
+            let entry = node_state_mut(&tree, &mut state_map, idx)?;
+            entry.out = Some(out);
+            entry.state = Some(SecretNonces::new(state));
+        }
+        // so every aggregate changes, the root's included
+        aggregate_round1(&tree, &mut state_map, &params, None, StageProgress::silent(), |_| false)?;
+        check_audit(&tree, &state_map, Phase::Round1)?;
+        Ok(SigningSession { tree, state_map, params, progress, phase: Round1Done })
+    }
+}
+
+#[cfg(feature = "signing")]
+impl SigningSession<Round2Done> {
+    pub fn signature(&self) -> &Signature {
+        &self.phase.sig
+    }
+}
+
+/// Sets up a signing run from the keys on: generating them, building their
+/// key tree as `build_sorted_key_tree_indexed` does, and starting the
+/// session, all under one `Params`. A sink installed with `with_progress`
+/// hears about each of those steps and about both rounds.
+#[cfg(feature = "signing")]
+#[derive(Clone)]
+pub struct SessionBuilder {
+    params: Params,
+    options: KeyTreeOptions,
+    progress: Option<Arc<dyn ProgressSink>>,
+}
+
+#[cfg(feature = "signing")]
+impl Default for SessionBuilder {
+    fn default() -> Self {
+        SessionBuilder { params: Params::default(), options: KeyTreeOptions::default(), progress: None }
+    }
+}
+
+#[cfg(feature = "signing")]
+impl SessionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn params(mut self, params: Params) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn options(mut self, options: KeyTreeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets `KeyTreeOptions::max_height`, which `session` also holds a tree
+    /// built elsewhere to.
+    pub fn max_height(mut self, max_height: usize) -> Self {
+        self.options.max_height = max_height;
+        self
+    }
+
+    pub fn with_progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    /// `n` keypairs from `draw`, e.g. `Keypair::generate` or a closure over
+    /// a seeded RNG.
+    pub fn generate_keys(&self, n: usize, mut draw: impl FnMut() -> Keypair) -> Vec<Keypair> {
+        let progress = StageProgress::new(self.progress.as_deref(), Stage::Keygen, n);
+        (0..n)
+            .map(|_| {
+                let kp = draw();
+                progress.tick();
+                kp
+            })
+            .collect()
+    }
+
+    pub fn key_tree(&self, pubkeys: Vec<Secp256k1Point>) -> Result<LeafOrigins<Secp256k1Point>, Error> {
+        sorted_key_tree_indexed(pubkeys, self.options, &self.params, self.progress.as_deref())
+    }
+
+    pub fn session(&self, tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>) -> Result<SigningSession, Error> {
+        check_key_tree_height(tree, self.options.max_height)?;
+        let mut session = SigningSession::with_params(tree, secret_keys, self.params.clone())?;
+        session.progress = self.progress.clone();
+        Ok(session)
+    }
+}
+
+/// Debug builds audit the state map after every round, and stop with
+/// `Error::StateAudit` rather than sign on from a state out of step.
+#[cfg(feature = "signing")]
+pub(crate) fn check_audit(tree: &IndexedTree<Secp256k1Point>, state_map: &StateMap, phase: Phase) -> Result<(), Error> {
+    if cfg!(debug_assertions) {
+        let report = audit_state(tree, state_map, phase);
+        if !report.is_clean() {
+            return Err(Error::StateAudit { phase, report });
+        }
+    }
+    Ok(())
+}
+
+/// Signs `msg` with every leaf of `tree`; `secret_keys` maps each leaf pubkey
+/// to its secret. The same key may sit at several leaves.
+#[cfg(feature = "signing")]
+pub fn tree_sign(tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>, msg: &[u8]) -> Result<Signature, Error> {
+    tree_sign_with(tree, secret_keys, msg, &Params::default())
+}
+
+#[cfg(feature = "signing")]
+pub fn tree_sign_with(tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>, msg: &[u8], params: &Params) -> Result<Signature, Error> {
+    let session = SigningSession::with_params(tree, secret_keys, params.clone())?.round1()?.round2(msg)?;
+    Ok(session.signature().clone())
+}
+
+/// `tree_sign_with`, but auditing the state map after setup and after each
+/// round in any build, not only debug ones, and returning every report.
+#[cfg(feature = "signing")]
+pub fn audited_tree_sign_with(
+    tree: &BinTree<Secp256k1Point>,
+    secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    msg: &[u8],
+    params: &Params,
+) -> Result<(Signature, Vec<(Phase, AuditReport)>), Error> {
+    let tree = IndexedTree::from_tree(tree);
+    let mut state_map = leaf_states(&tree, secret_keys)?;
+    let mut reports = vec![(Phase::Setup, audit_state(&tree, &state_map, Phase::Setup))];
+    round1(&tree, &mut state_map, params)?;
+    reports.push((Phase::Round1, audit_state(&tree, &state_map, Phase::Round1)));
+    round2(&tree, &mut state_map, msg, params)?;
+    reports.push((Phase::Round2, audit_state(&tree, &state_map, Phase::Round2)));
+    Ok((root_signature(&tree, &state_map)?, reports))
+}
+
+pub fn tree_verify(root_pk: &Secp256k1Point, msg: &[u8], sig: &Signature) -> bool {
+    tree_verify_with(root_pk, msg, sig, &Params::default())
+}
+
+pub fn tree_verify_with(root_pk: &Secp256k1Point, msg: &[u8], sig: &Signature, params: &Params) -> bool {
+    ver(params, root_pk, msg, sig)
+}
+
+/// Builds the sorted key tree for `pubkeys`, signs `msg` with it and checks
+/// the signature, timing each step. Returns the tree, the signature and
+/// whether it verified, with the timings.
+#[cfg(feature = "signing")]
+pub fn timed_tree_sign(
+    pubkeys: Vec<Secp256k1Point>,
+    secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>,
+    msg: &[u8],
+    params: &Params,
+) -> Result<(BinTree<Secp256k1Point>, Signature, bool, Timings), Error> {
+    let started = Instant::now();
+    let tree = build_sorted_key_tree_with(pubkeys, params)?;
+    let key_aggregation = started.elapsed();
+
+    let indexed = IndexedTree::from_tree(&tree);
+    let mut state_map = leaf_states(&indexed, secret_keys)?;
+    let mut round1_clock = DepthClock::new(&indexed);
+    round1_timed(&indexed, &mut state_map, params, Some(&mut round1_clock), None)?;
+    let mut round2_clock = DepthClock::new(&indexed);
+    round2_timed(&indexed, &mut state_map, msg, params, Some(&mut round2_clock))?;
+    let sig = root_signature(&indexed, &state_map)?;
+
+    let started = Instant::now();
+    let verified = tree_verify_with(tree.value(), msg, &sig, params);
+    let verification = started.elapsed();
+
+    let timings = Timings {
+        key_aggregation,
+        round1_by_depth: round1_clock.into_durations(),
+        round2_by_depth: round2_clock.into_durations(),
+        verification,
+    };
+    Ok((tree, sig, verified, timings))
+}
+
+/// Checks that `leaf` is committed to by `root`: aggregating it with each
+/// sibling in `path` (nearest first, on the side given) must end at `root`.
+/// Paths come from `IndexedTree::sided_path_at`.
+pub fn verify_merkle_path(root: &Secp256k1Point, leaf: &Secp256k1Point, path: &[(Side, Secp256k1Point)], params: &Params) -> bool {
+    let mut acc = leaf.clone();
+    for (side, sibling) in path {
+        let pair = match side {
+            Side::Left => agg_ordered(sibling.clone(), acc),
+            Side::Right => agg_ordered(acc, sibling.clone()),
+        };
+        match key_agg(params, &pair) {
+            Ok(key) => acc = key,
+            Err(_) => return false,
+        }
+    }
+    acc == *root
+}
+
+#[cfg(all(test, feature = "signing"))]
+pub(crate) fn setup(n: u32) -> (IndexedTree<Secp256k1Point>, StateMap) {
+    let keys: Vec<_> = (0..n).map(|_| nested_musig2::keygen::keygen()).collect();
+    let btree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+    let tree = IndexedTree::from_tree(&btree);
+    let secret_keys = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let state_map = leaf_states(&tree, &secret_keys).unwrap();
+    (tree, state_map)
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::bintree::TreeShape;
+    use crate::encoding::round1_out_to_bytes;
+    use proptest::prelude::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    fn sign_and_verify(n: u32) -> bool {
+        let (btree, mut state_map) = setup(n);
+        let msg = b"test tx message";
+        let sig = sign(&btree, &mut state_map, msg, &Params::default()).unwrap();
+        tree_verify(&btree.get(btree.root()).value, msg, &sig)
+    }
+
+    #[test]
+    fn power_of_two_counts_verify() {
+        for n in [2, 4, 8] {
+            assert!(sign_and_verify(n), "n = {}", n);
+        }
+    }
+
+    // Odd leftovers are carried up through single-child nodes by `from_vec`,
+    // so some leaves have fewer cosigning siblings than others.
+    #[test]
+    fn non_power_of_two_counts_verify() {
+        for n in [3, 5, 6, 7, 9] {
+            assert!(sign_and_verify(n), "n = {}", n);
+        }
+    }
+
+    // A cosigner dropping out: the tree is re-aggregated in place and the
+    // remaining keys still produce a signature for the new root key.
+    #[test]
+    fn reduced_tree_still_verifies() {
+        let keys: Vec<_> = (0..5).map(|_| nested_musig2::keygen::keygen()).collect();
+        let mut tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.iter().map(|kp| (kp.pk.clone(), kp.sk.clone())).collect();
+        let params = Params::default();
+
+        assert!(tree.remove_leaf(&keys[1].pk, |k1, k2| key_agg(&params, &[k1.clone(), k2.clone()]).unwrap()));
+        assert_eq!(tree.leaf_count(), 4);
+        assert!(!tree.contains_leaf(&keys[1].pk));
+
+        let msg = b"test tx message";
+        let sig = tree_sign(&tree, &secret_keys, msg).unwrap();
+        assert!(tree_verify(tree.value(), msg, &sig));
+    }
+
+    #[test]
+    fn built_key_tree_validates() {
+        let pubkeys = (0..8).map(|_| nested_musig2::keygen::keygen().pk).collect();
+        let tree = build_key_tree(pubkeys).unwrap();
+        assert!(validate_key_tree(&tree).is_ok());
+
+        let BinTree::Node { left, right, value: _, .. } = &tree else {
+            panic!("expected Node");
+        };
+        // claim the left child's key as the root key
+        let value = left.value().clone();
+        let forged = BinTree::from_parts((**left).clone(), right.as_deref().cloned(), value);
+        match validate_key_tree(&forged) {
+            Err(Error::InvalidKeyTree(e)) => assert_eq!((e.depth, e.position), (0, 0)),
+            other => panic!("expected InvalidKeyTree, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn imported_key_tree_is_checked() {
+        let pubkeys: Vec<_> = (0..5).map(|_| nested_musig2::keygen::keygen().pk).collect();
+        let tree = build_key_tree(pubkeys.clone()).unwrap();
+        let levels = || tree.levels().into_iter().map(|level| level.into_iter().cloned().collect::<Vec<_>>()).collect::<Vec<_>>();
+        assert_eq!(import_key_tree(levels()).unwrap(), tree);
+
+        let mut forged = levels();
+        forged[1][0] = pubkeys[0].clone();
+        assert!(matches!(import_key_tree(forged), Err(Error::InvalidKeyTree(_))));
+        let mut short = levels();
+        short[2].pop();
+        assert!(matches!(import_key_tree(short), Err(Error::TreeLevels(_))));
+    }
+
+    #[test]
+    fn sorted_key_tree_ignores_key_order() {
+        let pubkeys: Vec<_> = (0..6).map(|_| nested_musig2::keygen::keygen().pk).collect();
+        let mut reversed = pubkeys.clone();
+        reversed.reverse();
+        let a = build_sorted_key_tree(pubkeys).unwrap();
+        let b = build_sorted_key_tree(reversed).unwrap();
+        assert!(a == b);
+    }
+
+    #[test]
+    fn sorted_tree_maps_keys_back_to_input_positions() {
+        let pubkeys: Vec<_> = (0..7).map(|_| nested_musig2::keygen::keygen().pk).collect();
+        let origins = build_sorted_key_tree_indexed(pubkeys.clone()).unwrap();
+        assert_eq!(origins.tree, build_sorted_key_tree(pubkeys.clone()).unwrap());
+        for (i, pk) in pubkeys.iter().enumerate() {
+            assert_eq!(origins.leaf_index_of(pk), Some(i));
+        }
+        assert_eq!(origins.leaf_index_of(origins.tree.value()), None);
+    }
+
+    #[test]
+    fn both_tree_shapes_verify() {
+        let params = Params::default();
+        for shape in [TreeShape::Balanced, TreeShape::LeftList] {
+            let keys: Vec<_> = (0..5).map(|_| nested_musig2::keygen::keygen()).collect();
+            let pubkeys = keys.iter().map(|kp| kp.pk.clone()).collect();
+            let tree = BinTree::from_vec_shaped(pubkeys, shape, |k1, k2| key_agg(&params, &[k1, k2]).unwrap());
+            let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+
+            let msg = b"test tx message";
+            let sig = tree_sign(&tree, &secret_keys, msg).unwrap();
+            assert!(tree_verify(tree.value(), msg, &sig), "shape = {:?}", shape);
+        }
+    }
+
+    // The message goes into the challenge hash as-is, so any length works,
+    // not just 32-byte digests.
+    #[test]
+    fn message_length_boundaries() {
+        for msg in [&b""[..], &[7u8; 32][..], &[7u8; 33][..], &[7u8; 1000][..]] {
+            let (tree, mut state_map) = setup(3);
+            let sig = sign(&tree, &mut state_map, msg, &Params::default()).unwrap();
+            assert!(tree_verify(&tree.get(tree.root()).value, msg, &sig), "len = {}", msg.len());
+        }
+    }
+
+    #[test]
+    fn sixteen_leaves_verify() {
+        assert!(sign_and_verify(16));
+    }
+
+    #[test]
+    fn session_runs_phase_by_phase() {
+        let keys: Vec<_> = (0..4).map(|_| nested_musig2::keygen::keygen()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+
+        let session = SigningSession::new(&tree, &secret_keys).unwrap();
+        let session = session.round1().unwrap();
+        let session = session.round2(b"phases").unwrap();
+        assert!(tree_verify(tree.value(), b"phases", session.signature()));
+    }
+
+    #[test]
+    fn round1_outs_follow_the_key_tree() {
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let session = SigningSession::new(&tree, &secret_keys).unwrap().round1().unwrap();
+
+        let (key_tree, outs) = session.round1_outs().unwrap().unzip();
+        assert_eq!(key_tree, tree);
+        assert!(outs.same_shape(&tree));
+        let root_state = node_state(&session.tree, &session.state_map, 0).unwrap();
+        assert_eq!(outs.value().0, root_state.out.as_ref().unwrap().0);
+        let session = session.round2(b"zipped").unwrap();
+        assert!(tree_verify(tree.value(), b"zipped", session.signature()));
+    }
+
+    #[test]
+    fn rounds_cannot_repeat() {
+        let (tree, mut state_map) = setup(4);
+        round1(&tree, &mut state_map, &Params::default()).unwrap();
+        assert!(matches!(round1(&tree, &mut state_map, &Params::default()), Err(Error::RoundRepeated { round: 1, .. })));
+
+        round2(&tree, &mut state_map, b"first", &Params::default()).unwrap();
+        let r = round2(&tree, &mut state_map, b"second", &Params::default());
+        assert!(matches!(r, Err(Error::RoundRepeated { round: 2, .. })));
+    }
+
+    #[test]
+    fn failed_round2_recovers_with_fresh_nonces() {
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let session = SigningSession::new(&tree, &secret_keys).unwrap().round1().unwrap();
+        let bad = session.tree.leaf_indices().nth(2).unwrap();
+        let outs_before: HashMap<usize, Vec<u8>> = session.state_map.iter().filter_map(|(&idx, s)| Some((idx, round1_out_to_bytes(s.out.as_ref()?)))).collect();
+
+        let failure = session.try_round2_faulty(b"retry", |idx| idx == bad).unwrap_err();
+        assert_eq!(failure.node(), Some(bad));
+        // the secret keys were signed with in place, so the failure left
+        // them, but every leaf's nonces went out to sign before the fault
+        for idx in failure.session.tree.leaf_indices() {
+            assert!(failure.session.state_map[&idx].secret_key.is_some(), "leaf {}", idx);
+            assert!(failure.session.state_map[&idx].state.is_none(), "leaf {}", idx);
+        }
+        let session = failure.recover().unwrap();
+        // so all are new, not only those under the failed leaf
+        for idx in session.tree.leaf_indices() {
+            assert_ne!(round1_out_to_bytes(session.state_map[&idx].out.as_ref().unwrap()), outs_before[&idx], "leaf {}", idx);
+        }
+        let session = session.try_round2(b"retry").unwrap();
+        assert!(tree_verify(tree.value(), b"retry", session.signature()));
+    }
+
+    #[test]
+    fn only_traced_failures_recover() {
+        let (tree, state_map) = setup(4);
+        let failure = Round2Failure {
+            error: Error::EmptyInput,
+            session: SigningSession { tree, state_map, params: Params::default(), progress: None, phase: Round1Done },
+        };
+        assert!(failure.node().is_none());
+        assert!(matches!(failure.recover(), Err(Error::EmptyInput)));
+    }
+
+    /// A left-leaning chain with `n` leaves and so `n` levels.
+    fn chain(n: usize) -> (BinTree<Secp256k1Point>, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+        let keys: Vec<_> = (0..n).map(|_| nested_musig2::keygen::keygen()).collect();
+        let mut tree = BinTree::Leaf(keys[0].pk.clone());
+        for kp in &keys[1..] {
+            let value = key_agg_pair(&Params::default(), tree.value().clone(), kp.pk.clone()).unwrap();
+            tree = BinTree::node(tree, BinTree::leaf(kp.pk.clone()), value);
+        }
+        (tree, keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect())
+    }
+
+    #[test]
+    fn tree_height_is_bounded() {
+        let (tree, secret_keys) = chain(MAX_TREE_HEIGHT);
+        assert_eq!(tree.height(), MAX_TREE_HEIGHT);
+        let sig = tree_sign(&tree, &secret_keys, b"tall").unwrap();
+        assert!(tree_verify(tree.value(), b"tall", &sig));
+
+        let (tree, secret_keys) = chain(MAX_TREE_HEIGHT + 1);
+        let err = SigningSession::new(&tree, &secret_keys).unwrap().round1().err().unwrap();
+        // a left-leaning chain is deepest at its first leaf
+        let first = tree.leaves().next().unwrap().clone();
+        assert!(matches!(&err, Error::TreeTooDeep { height, max: MAX_TREE_HEIGHT, deepest } if *height == MAX_TREE_HEIGHT + 1 && *deepest == first));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "key tree has 33 levels but at most 32 are supported, i.e. up to 2147483648 signers in a balanced tree; the deepest leaf is {}",
+                crate::encoding::point_fingerprint(&first)
+            )
+        );
+    }
+
+    #[test]
+    fn height_cap_is_configurable() {
+        // five keys pair up into four levels
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|kp| kp.pk.clone()).collect();
+        let capped = KeyTreeOptions { max_height: 3, ..KeyTreeOptions::default() };
+        let params = Params::default();
+        fn too_deep<T>(result: Result<T, Error>) -> bool {
+            matches!(result, Err(Error::TreeTooDeep { height: 4, max: 3, .. }))
+        }
+
+        assert!(too_deep(build_key_tree_opts(pubkeys.clone(), capped, &params)));
+        assert!(too_deep(build_sorted_key_tree_opts(pubkeys.clone(), capped, &params)));
+        assert!(too_deep(build_sorted_key_tree_indexed_opts(pubkeys.clone(), capped, &params)));
+        assert!(too_deep(SessionBuilder::new().max_height(3).key_tree(pubkeys.clone())));
+
+        let tree = build_key_tree(pubkeys).unwrap();
+        assert_eq!(tree.height(), 4);
+        let levels = tree.levels().into_iter().map(|level| level.into_iter().cloned().collect()).collect::<Vec<_>>();
+        assert!(too_deep(import_key_tree_opts(levels.clone(), capped, &params)));
+        assert_eq!(import_key_tree_with(levels, &params).unwrap(), tree);
+
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        assert!(too_deep(SessionBuilder::new().max_height(3).session(&tree, &secret_keys)));
+        assert!(SessionBuilder::new().max_height(4).session(&tree, &secret_keys).is_ok());
+    }
+
+    // The same signer at two of four leaves, i.e. with twice the weight.
+    fn duplicate_signer_tree() -> (BinTree<Secp256k1Point>, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+        let keys: Vec<_> = (0..3).map(|_| nested_musig2::keygen::keygen()).collect();
+        let pubkeys = vec![keys[0].pk.clone(), keys[1].pk.clone(), keys[0].pk.clone(), keys[2].pk.clone()];
+        let tree = build_key_tree_opts(pubkeys, KeyTreeOptions { allow_duplicates: true, ..KeyTreeOptions::default() }, &Params::default()).unwrap();
+        (tree, keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect())
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected_unless_allowed() {
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate().pk).collect();
+        let repeated = vec![keys[0].clone(), keys[1].clone(), keys[2].clone(), keys[1].clone()];
+        assert!(matches!(build_key_tree(repeated.clone()), Err(Error::DuplicateKey(pk)) if pk == keys[1]));
+        assert!(matches!(build_sorted_key_tree(repeated.clone()), Err(Error::DuplicateKey(_))));
+        assert!(matches!(build_sorted_key_tree_indexed(repeated.clone()), Err(Error::DuplicateKey(_))));
+
+        let allowed = KeyTreeOptions { allow_duplicates: true, ..KeyTreeOptions::default() };
+        assert_eq!(build_key_tree_opts(repeated.clone(), allowed, &Params::default()).unwrap().leaf_count(), 4);
+        let origins = build_sorted_key_tree_indexed_opts(repeated, allowed, &Params::default()).unwrap();
+        assert_eq!(origins.positions.len(), 4);
+
+        assert_eq!(build_key_tree(keys.clone()).unwrap().leaf_count(), 3);
+        assert!(check_duplicates(&keys, KeyTreeOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn duplicate_leaf_keys_verify() {
+        let (tree, secret_keys) = duplicate_signer_tree();
+        assert_eq!(tree.leaf_count(), 4);
+        let sig = tree_sign(&tree, &secret_keys, b"weighted").unwrap();
+        assert!(tree_verify(tree.value(), b"weighted", &sig));
+    }
+
+    // Keyed by pubkey, the second leaf's entry used to replace the first's,
+    // leaving one nonce pair for two signing slots.
+    #[test]
+    fn duplicate_leaf_keys_keep_separate_state() {
+        let (btree, secret_keys) = duplicate_signer_tree();
+        let tree = IndexedTree::from_tree(&btree);
+        let mut state_map = leaf_states(&tree, &secret_keys).unwrap();
+        assert_eq!(state_map.len(), 4);
+
+        round1(&tree, &mut state_map, &Params::default()).unwrap();
+        let leaves: Vec<usize> = tree.leaf_indices().collect();
+        assert!(tree.get(leaves[0]).value == tree.get(leaves[2]).value);
+        let out_bytes = |idx| round1_out_to_bytes(state_map[&idx].out.as_ref().unwrap());
+        assert_ne!(out_bytes(leaves[0]), out_bytes(leaves[2]));
+    }
+
+    #[test]
+    fn signing_leaves_no_secrets_behind() {
+        for n in [1, 2, 5] {
+            let (tree, mut state_map) = setup(n);
+            sign(&tree, &mut state_map, b"wiped", &Params::default()).unwrap();
+            assert!(state_map.values().all(|s| s.secret_key.is_none() && s.state.is_none()), "n = {}", n);
+            // nor anything else but the root's signature
+            let root = &state_map[&state_idx(&tree, tree.root())];
+            assert_eq!(state_map.len(), 1, "n = {}", n);
+            assert!(root.out.is_none() && root.out_internal.is_none() && root.out_prime.is_some(), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn node_state_debug_redacts_secrets() {
+        let kp = nested_musig2::keygen::keygen();
+        let secret = format!("{:?}", kp.sk);
+        let state = NodeState {
+            secret_key: Some(SecretScalar::new(kp.sk)),
+            ..NodeState::default()
+        };
+        let shown = format!("{:?}", state);
+        assert!(shown.contains("<redacted>"));
+        assert!(!shown.contains(&secret));
+    }
+
+    #[test]
+    fn one_session_signs_several_messages() {
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let session = SigningSession::new(&tree, &secret_keys).unwrap();
+
+        let msgs: [&[u8]; 3] = [b"first", b"second", b"third"];
+        let sigs: Vec<_> = msgs.iter().map(|msg| session.sign(msg).unwrap()).collect();
+        for (msg, sig) in msgs.iter().zip(&sigs) {
+            assert!(tree_verify(tree.value(), msg, sig));
+        }
+        // fresh nonces each time, so no two signatures share a nonce point
+        assert!(sigs[0].0 != sigs[1].0 && sigs[1].0 != sigs[2].0 && sigs[0].0 != sigs[2].0);
+        // the session itself never ran a round
+        assert!(session.state_map.values().all(|s| s.state.is_none() && s.secret_key.is_some()));
+    }
+
+    // `Params` only exposes its defaults, so a mismatched instance cannot be
+    // built here; this checks that one explicit instance reaches every step.
+    #[test]
+    fn explicit_params_are_used_throughout() {
+        let params = Params::default();
+        let keys: Vec<_> = (0..5).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree_with(keys.iter().map(|kp| kp.pk.clone()).collect(), &params).unwrap();
+        validate_key_tree_with(&tree, &params).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+
+        let sig = tree_sign_with(&tree, &secret_keys, b"params", &params).unwrap();
+        assert!(tree_verify_with(tree.value(), b"params", &sig, &params));
+        let session = SigningSession::with_params(&tree, &secret_keys, params.clone()).unwrap();
+        let sig = session.sign(b"params").unwrap();
+        assert!(tree_verify_with(tree.value(), b"params", &sig, &params));
+        assert!(!tree_verify_with(tree.value(), b"other", &sig, &params));
+    }
+
+    /// Every leaf's round 2 inputs collected the way round 2 used to:
+    /// recursing over the key tree, pushing each two-child node's internal
+    /// output and the sibling's key on the way down. `next` numbers the
+    /// nodes in preorder, as the arena does.
+    fn reference_round2_inputs(
+        node: &BinTree<Secp256k1Point>,
+        state_map: &StateMap,
+        next: &mut usize,
+        stack: &mut Round2Inputs,
+        out: &mut Vec<Round2Inputs>,
+    ) {
+        let idx = *next;
+        *next += 1;
+        match node {
+            BinTree::Leaf(_) => out.push(stack.clone()),
+            BinTree::Node { left, right: None, .. } => reference_round2_inputs(left, state_map, next, stack, out),
+            BinTree::Node { left, right: Some(right), .. } => {
+                stack.0.push(state_map[&idx].out_internal.clone().unwrap());
+                stack.1.push(vec![right.value().clone()]);
+                reference_round2_inputs(left, state_map, next, stack, out);
+                stack.1.pop();
+                stack.1.push(vec![left.value().clone()]);
+                reference_round2_inputs(right, state_map, next, stack, out);
+                stack.1.pop();
+                stack.0.pop();
+            }
+        }
+    }
+
+    fn round2_inputs_bytes((outs_by_depth, merkle_path): &Round2Inputs) -> (Vec<Vec<Vec<u8>>>, Vec<Vec<Vec<u8>>>) {
+        (
+            outs_by_depth.iter().map(round1_out_to_bytes).collect(),
+            merkle_path.iter().map(|level| level.iter().map(point_to_bytes).collect()).collect(),
+        )
+    }
+
+    // `sign_prime` must get byte for byte what the recursive collection
+    // gave it, whether the inputs come fresh from `leaf_round2_inputs` or
+    // from the buffer round 2 refills for leaf after leaf.
+    #[test]
+    fn round2_inputs_match_recursive_collection() {
+        let mut rng = ChaCha20Rng::seed_from_u64(226);
+        for n in [1, 2, 5, 13, 32] {
+            let keys: Vec<_> = (0..n).map(|_| Keypair::from_rng(&mut rng)).collect();
+            let btree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+            let tree = IndexedTree::from_tree(&btree);
+            let secret_keys = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+            let mut state_map = leaf_states(&tree, &secret_keys).unwrap();
+            round1(&tree, &mut state_map, &Params::default()).unwrap();
+
+            let mut expected = Vec::new();
+            reference_round2_inputs(&btree, &state_map, &mut 0, &mut Round2Inputs::default(), &mut expected);
+            let leaves: Vec<usize> = tree.leaf_indices().collect();
+            assert_eq!(leaves.len(), expected.len());
+
+            let mut reused = Round2Inputs::default();
+            for (&leaf, expected) in leaves.iter().zip(&expected) {
+                let fresh = leaf_round2_inputs(&tree, &state_map, leaf).unwrap();
+                assert_eq!(round2_inputs_bytes(&fresh), round2_inputs_bytes(expected), "n = {}, leaf {}", n, leaf);
+                let out_internal = |parent| Ok(state_map[&parent].out_internal.clone().unwrap());
+                fill_round2_inputs(&tree, leaf, out_internal, &mut reused).unwrap();
+                assert_eq!(round2_inputs_bytes(&reused), round2_inputs_bytes(expected), "n = {}, leaf {}", n, leaf);
+            }
+        }
+    }
+
+    #[test]
+    fn single_signer_signs_for_its_own_key() {
+        let kp = Keypair::generate();
+        let btree = build_key_tree(vec![kp.pk.clone()]).unwrap();
+        assert!(btree.value() == &kp.pk);
+        let tree = IndexedTree::from_tree(&btree);
+        let secret_keys = HashMap::from([(kp.pk.clone(), kp.sk.clone())]);
+        let mut state_map = leaf_states(&tree, &secret_keys).unwrap();
+
+        round1(&tree, &mut state_map, &Params::default()).unwrap();
+        let (outs_by_depth, merkle_path) = leaf_round2_inputs(&tree, &state_map, tree.root()).unwrap();
+        assert!(outs_by_depth.is_empty() && merkle_path.is_empty());
+        round2(&tree, &mut state_map, b"alone", &Params::default()).unwrap();
+        assert_eq!(state_map.len(), 1);
+
+        let sig = root_signature(&tree, &state_map).unwrap();
+        assert!(tree_verify(&kp.pk, b"alone", &sig));
+        assert!(!tree_verify(&kp.pk, b"other", &sig));
+    }
+
+    #[test]
+    fn timings_cover_every_level() {
+        let keys: Vec<_> = (0..8).map(|_| Keypair::generate()).collect();
+        let pubkeys = keys.iter().map(|kp| kp.pk.clone()).collect();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let (tree, sig, verified, timings) = timed_tree_sign(pubkeys, &secret_keys, b"timed", &Params::default()).unwrap();
+        assert!(verified);
+        assert!(tree_verify(tree.value(), b"timed", &sig));
+
+        assert_eq!(timings.round1_by_depth.len(), tree.height());
+        assert_eq!(timings.round2_by_depth.len(), tree.height());
+        // eight leaves: every level has nodes doing work in both rounds
+        assert!(timings.round1_by_depth.iter().chain(&timings.round2_by_depth).all(|d| !d.is_zero()));
+        assert!(!timings.key_aggregation.is_zero());
+        assert!(!timings.verification.is_zero());
+    }
+
+    #[test]
+    fn merkle_paths_verify_for_every_leaf() {
+        let params = Params::default();
+        for n in 2..=16 {
+            let tree = IndexedTree::from_tree(&build_key_tree(seeded_pubkeys(n, n as u64)).unwrap());
+            let root = &tree.get(tree.root()).value;
+            for leaf in tree.leaf_indices() {
+                let path = tree.sided_path_at(leaf);
+                assert!(verify_merkle_path(root, &tree.get(leaf).value, &path, &params), "n = {}, leaf {}", n, leaf);
+            }
+        }
+    }
+
+    #[test]
+    fn altered_merkle_paths_fail() {
+        let params = Params::default();
+        let tree = IndexedTree::from_tree(&build_key_tree(seeded_pubkeys(8, 1)).unwrap());
+        let root = &tree.get(tree.root()).value;
+        let leaf = tree.leaf_indices().nth(2).unwrap();
+        let pk = &tree.get(leaf).value;
+        let path = tree.sided_path_at(leaf);
+        assert_eq!(path.len(), 3);
+        assert!(verify_merkle_path(root, pk, &path, &params));
+
+        // another leaf's key in place of the nearest sibling
+        let mut swapped = path.clone();
+        swapped[0].1 = tree.get(tree.leaf_indices().nth(5).unwrap()).value.clone();
+        assert!(!verify_merkle_path(root, pk, &swapped, &params));
+
+        let mut flipped = path.clone();
+        flipped[1].0 = match flipped[1].0 {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        };
+        assert!(!verify_merkle_path(root, pk, &flipped, &params));
+
+        assert!(!verify_merkle_path(root, pk, &path[..2], &params));
+        assert!(!verify_merkle_path(root, pk, &[], &params));
+        // the right path for the wrong leaf
+        let other = &tree.get(tree.leaf_indices().next().unwrap()).value;
+        assert!(!verify_merkle_path(root, other, &path, &params));
+    }
+
+    #[test]
+    fn swapping_children_at_one_node_changes_the_key() {
+        let params = Params::default();
+        let keys: Vec<_> = (0..4).map(|_| Keypair::generate()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+        let BinTree::Node { left, right: Some(right), .. } = &tree else {
+            panic!("expected a two-child root");
+        };
+        let BinTree::Node { left: a, right: Some(b), value: ab, .. } = left.as_ref() else {
+            panic!("expected two leaves under the root's left child");
+        };
+
+        // the same two leaves, right one first, with the aggregates redone
+        let ba = key_agg_pair(&params, b.value().clone(), a.value().clone()).unwrap();
+        assert!(ba != *ab);
+        let swapped_root = key_agg_pair(&params, ba.clone(), right.value().clone()).unwrap();
+        assert!(swapped_root != *tree.value());
+        let swapped = BinTree::node(BinTree::node((**b).clone(), (**a).clone(), ba), (**right).clone(), swapped_root);
+        validate_key_tree(&swapped).unwrap();
+
+        // both rounds follow the swapped order, so it signs for its own key only
+        let sig = tree_sign(&swapped, &secret_keys, b"order").unwrap();
+        assert!(tree_verify(swapped.value(), b"order", &sig));
+        assert!(!tree_verify(tree.value(), b"order", &sig));
+
+        // and so does the merkle path check: `a`'s sibling on the other side
+        // leads to the swapped root
+        let path = [(Side::Right, b.value().clone()), (Side::Right, right.value().clone())];
+        assert!(verify_merkle_path(tree.value(), a.value(), &path, &params));
+        let flipped = [(Side::Left, b.value().clone()), (Side::Right, right.value().clone())];
+        assert!(!verify_merkle_path(tree.value(), a.value(), &flipped, &params));
+        assert!(verify_merkle_path(swapped.value(), a.value(), &flipped, &params));
+    }
+
+    // The key tree stays as built; only the signing run combines one node's
+    // children right one first, in round 1 or in round 2. Whatever comes
+    // out must not pass as a signature for the root key.
+    #[test]
+    fn swapped_aggregation_at_one_node_does_not_verify() {
+        let params = Params::default();
+        let msg = b"order";
+        for round in [0, 1, 2] {
+            let (tree, mut state_map) = setup(4);
+            let root_key = tree.get(tree.root()).value.clone();
+            let node = tree.get(tree.root()).left.unwrap();
+            assert!(tree.get(node).right.is_some(), "a two-child node under the root");
+            let swap = |r: u8| move |idx: usize| round == r && idx == node;
+
+            round1_faulty(&tree, &mut state_map, &params, None, None, swap(1)).unwrap();
+            let signed = round2_faulty(&tree, &mut state_map, msg, &params, None, None, |idx| swap(2)(idx).then_some(Fault::Swap))
+                .and_then(|()| root_signature(&tree, &state_map));
+            let verified = signed.is_ok_and(|sig| tree_verify(&root_key, msg, &sig));
+            // round 0 swaps nowhere, so the hook alone breaks nothing
+            assert_eq!(verified, round == 0, "swapped in round {}", round);
+        }
+    }
+
+    #[test]
+    fn empty_input_is_an_error() {
+        assert!(matches!(build_key_tree(vec![]), Err(Error::EmptyInput)));
+    }
+
+    #[test]
+    fn leaf_without_secret_is_missing_state() {
+        let keys: Vec<_> = (0..3).map(|_| nested_musig2::keygen::keygen()).collect();
+        let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+        let mut secret_keys: HashMap<_, _> = keys.iter().map(|kp| (kp.pk.clone(), kp.sk.clone())).collect();
+        secret_keys.remove(&keys[1].pk);
+
+        match tree_sign(&tree, &secret_keys, b"msg") {
+            Err(Error::MissingNodeState(pk)) => assert!(pk == keys[1].pk),
+            other => panic!("expected MissingNodeState, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn round1_reports_missing_leaf_state() {
+        let (tree, mut state_map) = setup(4);
+        let leaf = tree.leaf_indices().next().unwrap();
+        state_map.remove(&leaf);
+        assert!(matches!(round1(&tree, &mut state_map, &Params::default()), Err(Error::MissingNodeState(_))));
+    }
+
+    #[test]
+    fn round2_before_round1_is_an_error() {
+        // Internal nodes only get an entry during round1.
+        let (tree, mut state_map) = setup(2);
+        let r = round2(&tree, &mut state_map, b"msg", &Params::default());
+        assert!(matches!(r, Err(Error::MissingNodeState(_))));
+
+        // A lone leaf has an entry, but no nonce state yet.
+        let (tree, mut state_map) = setup(1);
+        let r = round2(&tree, &mut state_map, b"msg", &Params::default());
+        assert!(matches!(r, Err(Error::IncompleteNodeState { field: "state", .. })));
+    }
+
+    fn seeded_pubkeys(n: usize, seed: u64) -> Vec<Secp256k1Point> {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        (0..n).map(|_| Keypair::from_rng(&mut rng).pk).collect()
+    }
+
+    proptest! {
+        // Each case builds a real key tree, so keep the count modest.
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        // Property 1: a leaf's merkle path has one sibling per two-child
+        // ancestor (every ancestor, for power-of-two n), and folding the
+        // leaf key with its siblings through key_agg, on the side the tree
+        // has them, gives the root key.
+        #[test]
+        fn prop_merkle_path_rebuilds_root(n in 1usize..64, seed in any::<u64>()) {
+            let pubkeys = seeded_pubkeys(n, seed);
+            let tree = build_key_tree(pubkeys.clone()).unwrap();
+            let indexed = IndexedTree::from_tree(&tree);
+            let params = Params::default();
+            for (depth, pk) in tree.leaf_depths() {
+                let path = tree.merkle_path(pk).unwrap();
+                if n.is_power_of_two() {
+                    prop_assert_eq!(path.len(), depth);
+                }
+
+                let mut siblings = path.iter();
+                let mut acc = pk.clone();
+                for idx in indexed.path_to_root(indexed.find_leaf(pk).unwrap()) {
+                    let Some(sibling) = indexed.sibling(idx) else { continue };
+                    let expected = siblings.next();
+                    prop_assert!(expected == Some(&indexed.get(sibling).value));
+                    let parent = indexed.get(indexed.parent(idx).unwrap());
+                    let pair = if parent.left == Some(idx) {
+                        [acc, indexed.get(sibling).value.clone()]
+                    } else {
+                        [indexed.get(sibling).value.clone(), acc]
+                    };
+                    acc = key_agg(&params, &pair).unwrap();
+                }
+                prop_assert!(siblings.next().is_none());
+                prop_assert!(acc == *tree.value());
+            }
+        }
+
+        // Property 2: two leaves under the same node have paths that differ
+        // only in their nearest sibling (the first entry), which is each
+        // other.
+        #[test]
+        fn prop_sibling_leaf_paths(n in 2usize..64, seed in any::<u64>()) {
+            let tree = build_key_tree(seeded_pubkeys(n, seed)).unwrap();
+            let indexed = IndexedTree::from_tree(&tree);
+            for idx in 0..indexed.node_count() {
+                let entry = indexed.get(idx);
+                let (Some(l), Some(r)) = (entry.left, entry.right) else { continue };
+                let (l, r) = (indexed.get(l), indexed.get(r));
+                if !(l.is_leaf() && r.is_leaf()) {
+                    continue;
+                }
+                let l_path = tree.merkle_path(&l.value).unwrap();
+                let r_path = tree.merkle_path(&r.value).unwrap();
+                prop_assert!(l_path[0] == r.value && r_path[0] == l.value);
+                prop_assert!(l_path[1..] == r_path[1..]);
+            }
+        }
+    }
+}
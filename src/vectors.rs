@@ -0,0 +1,210 @@
+//! Known-answer test vectors: signer keys derived from a seed as `--seed`
+//! derives them, the root key they aggregate to and a signature under it.
+//!
+//! Signing nonces always come from the OS RNG, so a replay cannot reproduce
+//! a signature byte for byte. It pins the root key instead, and checks that
+//! both the recorded signature and a fresh one verify under it.
+
+use nested_musig2::params::Params;
+#[cfg(feature = "signing")]
+use rand_chacha::ChaCha20Rng;
+#[cfg(feature = "signing")]
+use rand_core::SeedableRng;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "signing")]
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::Error;
+#[cfg(feature = "signing")]
+use crate::keys::Keypair;
+#[cfg(feature = "signing")]
+use crate::parse::to_hex;
+use crate::parse::{ParseError, hex_any};
+use crate::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+#[cfg(feature = "signing")]
+use crate::treemusig::SessionBuilder;
+
+/// Bumped whenever the file layout changes.
+pub const VECTORS_VERSION: u32 = 1;
+
+/// The signer counts `--gen-vectors` covers: every tree shape up to 8, and
+/// either side of a full 16-leaf tree, where the odd-n paths change.
+pub const VECTOR_COUNTS: [usize; 11] = [1, 2, 3, 4, 5, 6, 7, 8, 15, 16, 17];
+
+#[derive(Debug)]
+pub enum VectorError {
+    Json(serde_json::Error),
+    Version(u32),
+    Message(ParseError),
+    Signing(Error),
+    /// The seed's keys no longer aggregate to the recorded root key.
+    RootKey { expected: AggregatedKey, got: AggregatedKey },
+    /// The recorded signature does not verify under the recorded root key.
+    RecordedSignature,
+    /// A fresh signature does not verify under the root key.
+    FreshSignature,
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorError::Json(e) => write!(f, "malformed test vector file: {}", e),
+            VectorError::Version(v) => {
+                write!(f, "test vector file version {} is not supported (expected {})", v, VECTORS_VERSION)
+            }
+            VectorError::Message(e) => write!(f, "invalid hex message: {}", e),
+            VectorError::Signing(e) => write!(f, "{}", e),
+            VectorError::RootKey { expected, got } => write!(f, "root key is {}, expected {}", got, expected),
+            VectorError::RecordedSignature => write!(f, "recorded signature does not verify under the root key"),
+            VectorError::FreshSignature => write!(f, "fresh signature does not verify under the root key"),
+        }
+    }
+}
+
+impl std::error::Error for VectorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VectorError::Json(e) => Some(e),
+            VectorError::Message(e) => Some(e),
+            VectorError::Signing(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for VectorError {
+    fn from(e: serde_json::Error) -> Self {
+        VectorError::Json(e)
+    }
+}
+
+impl From<ParseError> for VectorError {
+    fn from(e: ParseError) -> Self {
+        VectorError::Message(e)
+    }
+}
+
+impl From<Error> for VectorError {
+    fn from(e: Error) -> Self {
+        VectorError::Signing(e)
+    }
+}
+
+/// One case, signed under `Params::default()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    pub seed: u64,
+    pub n: usize,
+    /// Hex.
+    pub message: String,
+    pub root_key: AggregatedKey,
+    pub signature: TreeSignature,
+}
+
+impl TestVector {
+    /// Signs `msg` with `n` signers keyed from `seed` and records the
+    /// outcome.
+    #[cfg(feature = "signing")]
+    pub fn generate(seed: u64, n: usize, msg: &[u8]) -> Result<Self, Error> {
+        let (root_key, signature) = sign_case(seed, n, msg)?;
+        Ok(TestVector { seed, n, message: to_hex(msg), root_key, signature })
+    }
+
+    /// Whether the recorded signature verifies under the recorded root key.
+    pub fn verifies(&self) -> Result<bool, VectorError> {
+        let msg = hex_any(&self.message)?;
+        Ok(VerifyCtx::new(Params::default(), &self.root_key).verify(&msg, &self.signature))
+    }
+
+    /// Signs the case afresh and checks it against what was recorded.
+    #[cfg(feature = "signing")]
+    pub fn replay(&self) -> Result<(), VectorError> {
+        let msg = hex_any(&self.message)?;
+        let (root_key, signature) = sign_case(self.seed, self.n, &msg)?;
+        if self.root_key != root_key {
+            return Err(VectorError::RootKey { expected: self.root_key.clone(), got: root_key });
+        }
+        let ctx = VerifyCtx::new(Params::default(), &root_key);
+        if !ctx.verify(&msg, &self.signature) {
+            return Err(VectorError::RecordedSignature);
+        }
+        if !ctx.verify(&msg, &signature) {
+            return Err(VectorError::FreshSignature);
+        }
+        Ok(())
+    }
+}
+
+/// What `--gen-vectors` writes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorFile {
+    pub version: u32,
+    pub cases: Vec<TestVector>,
+}
+
+impl VectorFile {
+    pub fn new(cases: Vec<TestVector>) -> Self {
+        VectorFile { version: VECTORS_VERSION, cases }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("test vectors always serialize")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, VectorError> {
+        let file: VectorFile = serde_json::from_str(text)?;
+        if file.version != VECTORS_VERSION {
+            return Err(VectorError::Version(file.version));
+        }
+        Ok(file)
+    }
+}
+
+/// Keys, key tree and both rounds as the CLI does them with `--seed`.
+#[cfg(feature = "signing")]
+fn sign_case(seed: u64, n: usize, msg: &[u8]) -> Result<(AggregatedKey, TreeSignature), Error> {
+    let builder = SessionBuilder::new();
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let keys = builder.generate_keys(n, || Keypair::from_rng(&mut rng));
+    let origins = builder.key_tree(keys.iter().map(|kp| kp.pk.clone()).collect())?;
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let session = builder.session(&origins.tree, &secret_keys)?.round1()?.round2(msg)?;
+    Ok((AggregatedKey::new(origins.tree.value().clone()), TreeSignature::new(session.signature().clone())))
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_vectors_replay() {
+        let cases = [3, 4].map(|n| TestVector::generate(9, n, b"vectors").unwrap());
+        let json = VectorFile::new(cases.to_vec()).to_json();
+        let mut file = VectorFile::from_json(&json).unwrap();
+        assert_eq!(file.cases, cases);
+        for case in &file.cases {
+            assert!(case.verifies().unwrap());
+            case.replay().unwrap();
+        }
+
+        file.version += 1;
+        assert!(matches!(VectorFile::from_json(&file.to_json()), Err(VectorError::Version(2))));
+        assert!(matches!(VectorFile::from_json("[]"), Err(VectorError::Json(_))));
+        // a case without its root key and signature is not a vector
+        let bare = r#"{"version":1,"cases":[{"seed":1,"n":3,"message":"6d"}]}"#;
+        assert!(matches!(VectorFile::from_json(bare), Err(VectorError::Json(_))));
+    }
+
+    #[test]
+    fn replay_catches_a_changed_root_key() {
+        let mut case = TestVector::generate(9, 5, b"vectors").unwrap();
+        case.root_key = TestVector::generate(10, 5, b"vectors").unwrap().root_key;
+        assert!(matches!(case.replay(), Err(VectorError::RootKey { .. })));
+
+        // a signature over another message, under the right root key
+        case.root_key = TestVector::generate(9, 5, b"vectors").unwrap().root_key;
+        case.signature = TestVector::generate(9, 5, b"other").unwrap().signature;
+        assert!(matches!(case.replay(), Err(VectorError::RecordedSignature)));
+    }
+}
@@ -0,0 +1,58 @@
+//! Entry points for a web page. Build with
+//! `cargo build --target wasm32-unknown-unknown --no-default-features --features wasm`
+//! and bind with `wasm-bindgen`; keys come from `getrandom`'s JS backend.
+
+use nested_musig2::params::Params;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::keys::Keypair;
+use crate::report::SigningReport;
+use crate::signature::{AggregatedKey, TreeSignature, VerifyCtx};
+use crate::treemusig::SessionBuilder;
+
+/// Signs `msg` with `n` fresh signers over their sorted key tree and returns
+/// the run as the object `--output json` prints. Throws for `n` of zero.
+#[wasm_bindgen]
+pub fn demo_sign(n: u32, msg: &[u8]) -> Result<JsValue, JsError> {
+    let report = sign_report(n as usize, msg)?;
+    Ok(serde_wasm_bindgen::to_value(&report)?)
+}
+
+/// Whether `sig_hex` signs `msg` under the root key `root_hex`, as
+/// `demo_sign` reports them. Malformed hex is simply not a valid signature.
+#[wasm_bindgen]
+pub fn demo_verify(root_hex: &str, msg: &[u8], sig_hex: &str) -> bool {
+    match (root_hex.parse::<AggregatedKey>(), sig_hex.parse::<TreeSignature>()) {
+        (Ok(root), Ok(sig)) => VerifyCtx::new(Params::default(), &root).verify(msg, &sig),
+        _ => false,
+    }
+}
+
+pub(crate) fn sign_report(n: usize, msg: &[u8]) -> Result<SigningReport, Error> {
+    let builder = SessionBuilder::new();
+    let keys = builder.generate_keys(n, Keypair::generate);
+    let origins = builder.key_tree(keys.iter().map(|kp| kp.pk.clone()).collect())?;
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let session = builder.session(&origins.tree, &secret_keys)?.round1()?.round2(msg)?;
+    let sig = session.signature();
+    let ctx = VerifyCtx::new(Params::default(), &AggregatedKey::new(origins.tree.value().clone()));
+    Ok(SigningReport::new(&origins.tree, msg, sig, ctx.verify(msg, &TreeSignature::new(sig.clone()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_verifies_through_demo_verify() {
+        let report = sign_report(4, b"web").unwrap();
+        assert!(report.verified);
+        assert_eq!(report.n, 4);
+        assert!(demo_verify(&report.root_pubkey, b"web", &report.signature));
+        assert!(!demo_verify(&report.root_pubkey, b"wet", &report.signature));
+        assert!(!demo_verify("02", b"web", &report.signature));
+        assert!(matches!(sign_report(0, b"web"), Err(Error::EmptyInput)));
+    }
+}
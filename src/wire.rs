@@ -0,0 +1,335 @@
+//! Binary framing for the messages a `Coordinator` and its `Signer`s
+//! exchange, so they can run in separate processes.
+//!
+//! A frame is a 4-byte big-endian body length followed by the body. The body
+//! starts with the protocol version and a message tag; the rest depends on
+//! the tag. Counts are big-endian, points and scalars use the `encoding`
+//! forms. Decoding never trusts a length it has not checked against the
+//! bytes actually present.
+
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::round1::Round1Out;
+use std::fmt;
+
+use crate::encoding::{DecodeError, POINT_LEN, SCALAR_LEN, point_from_bytes, point_to_bytes, scalar_from_bytes, scalar_to_bytes};
+
+/// Bumped whenever a message layout changes.
+pub const WIRE_VERSION: u8 = 1;
+/// Bytes in the length prefix.
+pub const LEN_PREFIX: usize = 4;
+/// Bodies longer than this are rejected before anything is allocated for
+/// them. Round 2 inputs for a tree of height `h` hold about `3h` points, so
+/// this leaves plenty of room.
+pub const MAX_BODY_LEN: usize = 1 << 20;
+
+const TAG_ROUND1: u8 = 1;
+const TAG_ROUND2_INPUTS: u8 = 2;
+const TAG_ROUND2: u8 = 3;
+
+#[derive(Debug, Clone)]
+pub enum WireMsg {
+    /// Signer to coordinator: its round 1 output.
+    Round1 { position: u32, out: Round1Out },
+    /// Coordinator to signer: what `Signer::round2` needs, root level first.
+    Round2Inputs {
+        outs_by_depth: Vec<Round1Out>,
+        merkle_path: Vec<Vec<Secp256k1Point>>,
+    },
+    /// Signer to coordinator: its partial signature.
+    Round2 {
+        position: u32,
+        prime: (Secp256k1Point, Secp256k1Scalar),
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    /// The bytes ran out before the message did.
+    Truncated,
+    /// The length prefix does not match the bytes that follow it.
+    LengthMismatch { declared: usize, got: usize },
+    BodyTooLarge(usize),
+    Version(u8),
+    UnknownTag(u8),
+    /// A message decoded but bytes were left over.
+    TrailingBytes(usize),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Truncated => write!(f, "message is truncated"),
+            WireError::LengthMismatch { declared, got } => {
+                write!(f, "frame declares {} body bytes but has {}", declared, got)
+            }
+            WireError::BodyTooLarge(len) => write!(f, "frame body of {} bytes exceeds {}", len, MAX_BODY_LEN),
+            WireError::Version(v) => {
+                write!(f, "wire protocol version {} is not supported (expected {})", v, WIRE_VERSION)
+            }
+            WireError::UnknownTag(tag) => write!(f, "unknown message tag {}", tag),
+            WireError::TrailingBytes(n) => write!(f, "{} bytes left after the message", n),
+            WireError::Decode(e) => write!(f, "malformed message: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WireError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WireError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for WireError {
+    fn from(e: DecodeError) -> Self {
+        WireError::Decode(e)
+    }
+}
+
+impl WireMsg {
+    /// The body: version, tag and payload, without the length prefix.
+    pub fn to_body(&self) -> Vec<u8> {
+        let mut body = vec![WIRE_VERSION];
+        match self {
+            WireMsg::Round1 { position, out } => {
+                body.push(TAG_ROUND1);
+                body.extend_from_slice(&position.to_be_bytes());
+                put_points(&mut body, &out.0);
+            }
+            WireMsg::Round2Inputs { outs_by_depth, merkle_path } => {
+                body.push(TAG_ROUND2_INPUTS);
+                put_count(&mut body, outs_by_depth.len());
+                for out in outs_by_depth {
+                    put_points(&mut body, &out.0);
+                }
+                put_count(&mut body, merkle_path.len());
+                for level in merkle_path {
+                    put_points(&mut body, level);
+                }
+            }
+            WireMsg::Round2 { position, prime } => {
+                body.push(TAG_ROUND2);
+                body.extend_from_slice(&position.to_be_bytes());
+                body.extend(point_to_bytes(&prime.0));
+                body.extend(scalar_to_bytes(&prime.1));
+            }
+        }
+        body
+    }
+
+    /// The whole frame, length prefix included.
+    pub fn to_frame(&self) -> Vec<u8> {
+        let body = self.to_body();
+        let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+        frame.extend(body);
+        frame
+    }
+
+    pub fn from_body(body: &[u8]) -> Result<Self, WireError> {
+        check_body_len(body.len())?;
+        let mut r = Reader(body);
+        let version = r.u8()?;
+        if version != WIRE_VERSION {
+            return Err(WireError::Version(version));
+        }
+        let msg = match r.u8()? {
+            TAG_ROUND1 => WireMsg::Round1 { position: r.u32()?, out: Round1Out(r.points()?) },
+            TAG_ROUND2_INPUTS => {
+                let outs_by_depth = (0..r.count()?).map(|_| r.points().map(Round1Out)).collect::<Result<_, _>>()?;
+                let merkle_path = (0..r.count()?).map(|_| r.points()).collect::<Result<_, _>>()?;
+                WireMsg::Round2Inputs { outs_by_depth, merkle_path }
+            }
+            TAG_ROUND2 => {
+                let position = r.u32()?;
+                let point = point_from_bytes(r.take(POINT_LEN)?)?;
+                let scalar = scalar_from_bytes(r.take(SCALAR_LEN)?)?;
+                WireMsg::Round2 { position, prime: (point, scalar) }
+            }
+            tag => return Err(WireError::UnknownTag(tag)),
+        };
+        if !r.0.is_empty() {
+            return Err(WireError::TrailingBytes(r.0.len()));
+        }
+        Ok(msg)
+    }
+
+    pub fn from_frame(frame: &[u8]) -> Result<Self, WireError> {
+        if frame.len() < LEN_PREFIX {
+            return Err(WireError::Truncated);
+        }
+        let (prefix, body) = frame.split_at(LEN_PREFIX);
+        let declared = body_len(prefix.try_into().expect("split at LEN_PREFIX"))?;
+        if declared != body.len() {
+            return Err(WireError::LengthMismatch { declared, got: body.len() });
+        }
+        WireMsg::from_body(body)
+    }
+}
+
+/// Reads a length prefix, rejecting lengths over `MAX_BODY_LEN`.
+pub fn body_len(prefix: [u8; LEN_PREFIX]) -> Result<usize, WireError> {
+    let len = u32::from_be_bytes(prefix) as usize;
+    check_body_len(len)?;
+    Ok(len)
+}
+
+fn check_body_len(len: usize) -> Result<(), WireError> {
+    if len > MAX_BODY_LEN {
+        return Err(WireError::BodyTooLarge(len));
+    }
+    Ok(())
+}
+
+/// Lists are prefixed with a 2-byte count.
+fn put_count(body: &mut Vec<u8>, count: usize) {
+    let count = u16::try_from(count).expect("lists are bounded by the tree height or nonce count");
+    body.extend_from_slice(&count.to_be_bytes());
+}
+
+fn put_points(body: &mut Vec<u8>, points: &[Secp256k1Point]) {
+    put_count(body, points.len());
+    for point in points {
+        body.extend(point_to_bytes(point));
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], WireError> {
+        if self.0.len() < n {
+            return Err(WireError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, WireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, WireError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().expect("took 4 bytes")))
+    }
+
+    fn count(&mut self) -> Result<usize, WireError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().expect("took 2 bytes")) as usize)
+    }
+
+    fn points(&mut self) -> Result<Vec<Secp256k1Point>, WireError> {
+        let count = self.count()?;
+        // Checked before collecting so a large count cannot allocate ahead
+        // of the bytes backing it.
+        if self.0.len() < count * POINT_LEN {
+            return Err(WireError::Truncated);
+        }
+        (0..count).map(|_| Ok(point_from_bytes(self.take(POINT_LEN)?)?)).collect()
+    }
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use crate::keys::Keypair;
+    use proptest::prelude::*;
+
+    fn point() -> Secp256k1Point {
+        Keypair::generate().pk
+    }
+
+    fn samples() -> Vec<WireMsg> {
+        let (out, _) = nested_musig2::round1::sign_round1(2).unwrap();
+        vec![
+            WireMsg::Round1 { position: 7, out: out.clone() },
+            WireMsg::Round2Inputs {
+                outs_by_depth: vec![out.clone(), out],
+                merkle_path: vec![vec![point()], vec![], vec![point(), point()]],
+            },
+            WireMsg::Round2 { position: 3, prime: (point(), Keypair::generate().sk) },
+        ]
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        for msg in samples() {
+            let frame = msg.to_frame();
+            // Every field is a point, scalar or integer with a fixed encoding,
+            // so re-encoding the decoded message reproduces the frame.
+            assert_eq!(WireMsg::from_frame(&frame).unwrap().to_frame(), frame);
+            assert_eq!(body_len(frame[..LEN_PREFIX].try_into().unwrap()).unwrap(), frame.len() - LEN_PREFIX);
+        }
+    }
+
+    #[test]
+    fn every_truncation_is_an_error() {
+        for msg in samples() {
+            let body = msg.to_body();
+            for len in 0..body.len() {
+                assert_eq!(WireMsg::from_body(&body[..len]).unwrap_err(), WireError::Truncated, "len = {}", len);
+            }
+            let frame = msg.to_frame();
+            for len in 0..frame.len() {
+                assert!(WireMsg::from_frame(&frame[..len]).is_err(), "len = {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        let body = samples().remove(0).to_body();
+
+        let mut bad = body.clone();
+        bad[0] = WIRE_VERSION + 1;
+        assert_eq!(WireMsg::from_body(&bad).unwrap_err(), WireError::Version(WIRE_VERSION + 1));
+        assert_eq!(
+            WireMsg::from_body(&bad).unwrap_err().to_string(),
+            format!("wire protocol version {} is not supported (expected {})", WIRE_VERSION + 1, WIRE_VERSION)
+        );
+
+        let mut bad = body.clone();
+        bad[1] = 9;
+        assert_eq!(WireMsg::from_body(&bad).unwrap_err(), WireError::UnknownTag(9));
+
+        let mut bad = body.clone();
+        bad.push(0);
+        assert_eq!(WireMsg::from_body(&bad).unwrap_err(), WireError::TrailingBytes(1));
+
+        // the first point's prefix byte, after version, tag, position and count
+        let mut bad = body.clone();
+        bad[8] = 0x05;
+        assert_eq!(WireMsg::from_body(&bad).unwrap_err(), WireError::Decode(DecodeError::InvalidPoint));
+
+        let mut frame = samples().remove(0).to_frame();
+        frame[3] += 1;
+        assert!(matches!(WireMsg::from_frame(&frame), Err(WireError::LengthMismatch { .. })));
+        assert_eq!(body_len([0xff; 4]).unwrap_err(), WireError::BodyTooLarge(u32::MAX as usize));
+
+        // huge counts with nothing behind them
+        let bad = [WIRE_VERSION, TAG_ROUND1, 0, 0, 0, 0, 0xff, 0xff];
+        assert_eq!(WireMsg::from_body(&bad).unwrap_err(), WireError::Truncated);
+        let bad = [WIRE_VERSION, TAG_ROUND2_INPUTS, 0xff, 0xff];
+        assert_eq!(WireMsg::from_body(&bad).unwrap_err(), WireError::Truncated);
+    }
+
+    proptest! {
+        // Property 1: decoding arbitrary bytes returns, it never panics.
+        #[test]
+        fn prop_decode_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let _ = WireMsg::from_body(&bytes);
+            let _ = WireMsg::from_frame(&bytes);
+        }
+
+        // Property 2: a valid header with an arbitrary payload still only
+        // produces an error or a message.
+        #[test]
+        fn prop_decode_with_valid_header(tag in 0u8..5, payload in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let mut body = vec![WIRE_VERSION, tag];
+            body.extend(payload);
+            let _ = WireMsg::from_body(&body);
+        }
+    }
+}
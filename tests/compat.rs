@@ -0,0 +1,43 @@
+//! Golden values that only change if `nested_musig2` changes how it
+//! aggregates keys. Archived signatures verify under keys built this way, so
+//! an upstream bump that moves any of these must fail here rather than in
+//! production. Signing nonces are always random, so no signature is pinned;
+//! `vectors.rs` checks that signatures still verify.
+//!
+//! To regenerate, after confirming the upstream change is intended: run
+//! `cargo test --test compat` and paste the constants the failure prints.
+#![cfg(feature = "signing")]
+
+use ark_usecase::encoding::point_hex;
+use ark_usecase::keys::Keypair;
+use ark_usecase::treemusig::build_key_tree;
+use crypto_rs::secp256k1::Secp256k1Point;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+/// Keys are drawn as `--seed 224` draws them.
+const SEED: u64 = 224;
+
+/// `key_agg` of the first two seeded keys, left then right.
+const PAIR_KEY: &str = "";
+/// Root of `build_key_tree` over the first four seeded keys.
+const ROOT_KEY: &str = "";
+
+fn seeded_keys(n: usize) -> Vec<Secp256k1Point> {
+    let mut rng = ChaCha20Rng::seed_from_u64(SEED);
+    (0..n).map(|_| Keypair::from_rng(&mut rng).pk).collect()
+}
+
+#[test]
+fn aggregated_keys_match_the_goldens() {
+    let keys = seeded_keys(4);
+    let pair = point_hex(build_key_tree(keys[..2].to_vec()).unwrap().value());
+    let root = point_hex(build_key_tree(keys).unwrap().value());
+    assert!(
+        pair == PAIR_KEY && root == ROOT_KEY,
+        "aggregated keys moved; if the nested_musig2 change is intended, regenerate with:\n\
+         const PAIR_KEY: &str = \"{}\";\nconst ROOT_KEY: &str = \"{}\";",
+        pair,
+        root
+    );
+}
@@ -0,0 +1,84 @@
+//! Full signing runs through the public API for every n up to `MAX_N`.
+#![cfg(feature = "signing")]
+
+use ark_usecase::bintree::BinTree;
+use ark_usecase::flat::{flat_key, flat_sign};
+use ark_usecase::kary::{build_kary_key_tree, kary_tree_sign};
+use ark_usecase::keys::Keypair;
+use ark_usecase::treemusig::{Signature, SigningSession, build_key_tree};
+use crypto_rs::secp256k1::{Secp256k1Point, Secp256k1Scalar};
+use nested_musig2::{params::Params, round2::ver};
+use std::collections::HashMap;
+
+/// Keeps the whole file to a few seconds; the benches cover larger trees.
+const MAX_N: usize = 32;
+const MSG: &[u8] = b"e2e message";
+
+fn key_set(n: usize) -> (BinTree<Secp256k1Point>, HashMap<Secp256k1Point, Secp256k1Scalar>) {
+    let keys: Vec<Keypair> = (0..n).map(|_| Keypair::generate()).collect();
+    let tree = build_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect()).unwrap();
+    let secret_keys = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    (tree, secret_keys)
+}
+
+fn sign(tree: &BinTree<Secp256k1Point>, secret_keys: &HashMap<Secp256k1Point, Secp256k1Scalar>, msg: &[u8]) -> Signature {
+    let session = SigningSession::new(tree, secret_keys).unwrap();
+    let session = session.round1().unwrap().round2(msg).unwrap();
+    session.signature().clone()
+}
+
+#[test]
+fn every_n_signs_and_verifies() {
+    let params = Params::default();
+    for n in 1..=MAX_N {
+        let (tree, secret_keys) = key_set(n);
+        let sig = sign(&tree, &secret_keys, MSG);
+        assert!(ver(&params, tree.value(), MSG, &sig), "n = {}", n);
+        assert!(!ver(&params, tree.value(), b"e2e messagf", &sig), "mutated message, n = {}", n);
+    }
+}
+
+#[test]
+fn signature_from_another_tree_is_rejected() {
+    let params = Params::default();
+    for n in [1, 2, 3, 8, 13] {
+        let (tree, secret_keys) = key_set(n);
+        let (other, other_secret_keys) = key_set(n);
+        let foreign = sign(&other, &other_secret_keys, MSG);
+        assert!(ver(&params, other.value(), MSG, &foreign));
+        assert!(!ver(&params, tree.value(), MSG, &foreign), "n = {}", n);
+
+        let own = sign(&tree, &secret_keys, MSG);
+        assert!(!ver(&params, other.value(), MSG, &own), "n = {}", n);
+    }
+}
+
+#[test]
+fn tree_and_flat_modes_both_verify() {
+    let params = Params::default();
+    for n in [1, 2, 5, 16] {
+        let keys: Vec<Keypair> = (0..n).map(|_| Keypair::generate()).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|kp| kp.pk.clone()).collect();
+        let tree = build_key_tree(pubkeys.clone()).unwrap();
+        let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+
+        let tree_sig = sign(&tree, &secret_keys, MSG);
+        assert!(ver(&params, tree.value(), MSG, &tree_sig), "tree, n = {}", n);
+
+        let key = flat_key(&pubkeys, &params).unwrap();
+        let flat_sig = flat_sign(&pubkeys, &secret_keys, MSG, &params).unwrap();
+        assert!(ver(&params, &key, MSG, &flat_sig), "flat, n = {}", n);
+    }
+}
+
+#[test]
+fn arity_three_tree_verifies() {
+    let params = Params::default();
+    let keys: Vec<Keypair> = (0..9).map(|_| Keypair::generate()).collect();
+    let tree = build_kary_key_tree(keys.iter().map(|kp| kp.pk.clone()).collect(), 3).unwrap();
+    assert_eq!(tree.height(), 3);
+    let secret_keys: HashMap<_, _> = keys.into_iter().map(|kp| (kp.pk, kp.sk)).collect();
+    let sig = kary_tree_sign(&tree, &secret_keys, MSG).unwrap();
+    assert!(ver(&params, tree.value(), MSG, &sig));
+    assert!(!ver(&params, tree.value(), b"e2e messagf", &sig));
+}
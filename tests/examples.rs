@@ -0,0 +1,18 @@
+//! Runs the embedding examples, so they keep compiling and signing against
+//! the public API.
+#![cfg(feature = "signing")]
+
+#[path = "../examples/embedded_coordinator.rs"]
+mod embedded_coordinator;
+#[path = "../examples/embedded_participant.rs"]
+mod embedded_participant;
+
+#[test]
+fn embedded_coordinator_signs() {
+    embedded_coordinator::main().unwrap();
+}
+
+#[test]
+fn embedded_participant_signs() {
+    embedded_participant::main().unwrap();
+}
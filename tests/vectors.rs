@@ -0,0 +1,38 @@
+//! Replays the known-answer vectors in `vectors.json`. Regenerate them with
+//! `cargo run -- --gen-vectors tests/vectors.json --seed 1` after a change
+//! that is meant to move the root keys.
+#![cfg(feature = "json")]
+
+use ark_usecase::vectors::{VECTOR_COUNTS, VectorFile};
+
+const VECTORS: &str = include_str!("vectors.json");
+
+fn vectors() -> VectorFile {
+    VectorFile::from_json(VECTORS)
+        .unwrap_or_else(|e| panic!("{}; regenerate with `cargo run -- --gen-vectors tests/vectors.json --seed 1`", e))
+}
+
+/// Needs no signing code, so it runs under `verify-only` too.
+#[test]
+fn every_recorded_signature_verifies() {
+    let file = vectors();
+    for case in &file.cases {
+        assert!(case.verifies().unwrap_or_else(|e| panic!("n = {}: {}", case.n, e)), "n = {}", case.n);
+    }
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn every_vector_replays() {
+    let file = vectors();
+    for case in &file.cases {
+        case.replay().unwrap_or_else(|e| panic!("n = {}: {}", case.n, e));
+    }
+}
+
+#[test]
+fn vectors_cover_the_usual_counts() {
+    let file = vectors();
+    let counts: Vec<usize> = file.cases.iter().map(|case| case.n).collect();
+    assert_eq!(counts, VECTOR_COUNTS);
+}
@@ -0,0 +1,39 @@
+//! Builds the crate as a verifier would, with `--no-default-features
+//! --features verify-only`, and runs the tests that still apply there: the
+//! library's own and the recorded vectors. Each build gets its own target
+//! directory, so the outer `cargo test` holding the usual one does not
+//! block it.
+#![cfg(all(feature = "signing", not(target_arch = "wasm32")))]
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn cargo(args: &[&str], target: &str) -> Output {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(target);
+    Command::new(env!("CARGO"))
+        .args(args)
+        .arg("--target-dir")
+        .arg(target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("cargo runs")
+}
+
+#[test]
+fn verification_tests_pass_without_signing() {
+    let out = cargo(
+        &["test", "--no-default-features", "--features", "verify-only,json", "--lib", "--test", "vectors"],
+        "verify-only",
+    );
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    // the library tests and the vectors both ran
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(stdout.matches("test result: ok").count(), 2, "{}", stdout);
+}
+
+#[test]
+fn verify_only_refuses_signing() {
+    let out = cargo(&["check", "--lib", "--no-default-features", "--features", "verify-only,signing"], "verify-only-signing");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("\"verify-only\" feature excludes signing"));
+}
@@ -0,0 +1,18 @@
+//! The JS entry points, run in a wasm runtime:
+//! `wasm-pack test --node -- --no-default-features --features wasm`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use ark_usecase::report::SigningReport;
+use ark_usecase::wasm::{demo_sign, demo_verify};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn four_signers_sign_and_verify() {
+    let signed = demo_sign(4, b"wasm").unwrap_or_else(|_| panic!("4 signers failed to sign"));
+    let report: SigningReport = serde_wasm_bindgen::from_value(signed).unwrap();
+    assert_eq!((report.n, report.height), (4, 3));
+    assert!(report.verified);
+    assert!(demo_verify(&report.root_pubkey, b"wasm", &report.signature));
+    assert!(!demo_verify(&report.root_pubkey, b"wasn", &report.signature));
+    assert!(demo_sign(0, b"wasm").is_err());
+}